@@ -0,0 +1,422 @@
+//! x86-64 assembly generation, for `--target x86_64-asm`.
+//!
+//! The output is a complete program for the GNU assembler, runtime
+//! included, that needs neither a C compiler nor a C library:
+//!
+//! ```text
+//! as -o prog.o prog.s && ld -o prog prog.o
+//! ```
+//!
+//! Each CFG block becomes a label, and control moves between them with
+//! plain jumps; GOSUB keeps the addresses to return to on a stack of its
+//! own. Variables are doubles in `.bss`, and expressions are computed in
+//! `%xmm0`, with the left operand of an operator saved on the machine stack
+//! while the right one is computed.
+
+use crate::cfg::{Cfg, EdgeKind, Step, ENTRY, EXIT};
+use crate::codegen::{CodegenOptions, GOSUB_STACK_SIZE};
+use crate::lexer::Token;
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+use std::collections::{BTreeSet, HashMap};
+
+/// The runtime, pasted at the top of every program.
+pub const RUNTIME: &str = include_str!("runtime/basic_rt_x86_64.s");
+
+pub struct AsmGenerator {
+    options: CodegenOptions,
+    variables: BTreeSet<String>,
+    /// Doubles in `.rodata`, by bit pattern, in the order they were first used.
+    constants: Vec<u64>,
+    constant_labels: HashMap<u64, usize>,
+    strings: Vec<String>,
+    string_labels: HashMap<String, usize>,
+    /// Lines with a jump to a line that does not exist, each getting a stub
+    /// that reports it.
+    undefined: BTreeSet<Option<i64>>,
+}
+
+impl AsmGenerator {
+    pub fn new(options: CodegenOptions) -> Self {
+        AsmGenerator {
+            options,
+            variables: BTreeSet::new(),
+            constants: Vec::new(),
+            constant_labels: HashMap::new(),
+            strings: Vec::new(),
+            string_labels: HashMap::new(),
+            undefined: BTreeSet::new(),
+        }
+    }
+
+    /// The symbol a variable is stored at. Symbols only take ASCII, so other
+    /// characters are spelled out.
+    fn variable(name: &str) -> String {
+        let mut symbol = String::from("var_");
+        for ch in name.chars() {
+            if ch.is_ascii_alphanumeric() || ch == '_' {
+                symbol.push(ch);
+            } else {
+                symbol.push_str(&format!("_{:x}", ch as u32));
+            }
+        }
+        symbol
+    }
+
+    fn collect_variables(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            self.collect_variables_node(&stmt.node);
+        }
+    }
+
+    fn collect_variables_node(&mut self, node: &StatementNode) {
+        match node {
+            StatementNode::Let { var, .. } | StatementNode::Input(var) => {
+                self.variables.insert(var.clone());
+            }
+            StatementNode::For { var, body, .. } => {
+                self.variables.insert(var.clone());
+                self.collect_variables(body);
+            }
+            StatementNode::If { then_part, .. } => self.collect_variables_node(&then_part.node),
+            _ => {}
+        }
+    }
+
+    fn constant(&mut self, value: f64) -> String {
+        let bits = value.to_bits();
+        let next = self.constants.len();
+        let index = *self.constant_labels.entry(bits).or_insert(next);
+        if index == next {
+            self.constants.push(bits);
+        }
+        format!(".LC{}(%rip)", index)
+    }
+
+    /// Load the address and length of `text` for the runtime.
+    fn string(&mut self, text: &str) -> String {
+        let next = self.strings.len();
+        let index = *self.string_labels.entry(text.to_string()).or_insert(next);
+        if index == next {
+            self.strings.push(text.to_string());
+        }
+        format!("    leaq .LS{}(%rip), %rdi\n    movl ${}, %esi\n", index, text.len())
+    }
+
+    /// An operand that can be used where it is, without computing anything.
+    fn operand(&mut self, expr: &Expression) -> Option<String> {
+        match expr {
+            Expression::Number(n) => Some(self.constant(*n as f64)),
+            Expression::Float(f, _) => Some(self.constant(*f)),
+            // A variable nothing assigns is always 0
+            Expression::Variable(name) if !self.variables.contains(name) => Some(self.constant(0.0)),
+            Expression::Variable(name) => Some(format!("{}(%rip)", Self::variable(name))),
+            _ => None,
+        }
+    }
+
+    /// Code that leaves the value of `expr` in `%xmm0`.
+    fn generate_expr(&mut self, expr: &Expression) -> String {
+        if let Some(operand) = self.operand(expr) {
+            return format!("    movsd {}, %xmm0\n", operand);
+        }
+        match expr {
+            Expression::BinaryOp { left, operator, right } => {
+                let mut code = self.generate_operands(left, right);
+                code.push_str(match operator {
+                    BinOp::Add => "    addsd %xmm1, %xmm0\n",
+                    BinOp::Subtract => "    subsd %xmm1, %xmm0\n",
+                    BinOp::Multiply => "    mulsd %xmm1, %xmm0\n",
+                    BinOp::Divide => "    divsd %xmm1, %xmm0\n",
+                    BinOp::Power => "    call rt_pow\n",
+                });
+                code
+            }
+            Expression::FunctionCall { name, args } => {
+                let upper = name.to_uppercase();
+                let mut code = match args.first() {
+                    Some(arg) => self.generate_expr(arg),
+                    // RND on its own is RND(1)
+                    None if upper == "RND" => self.generate_expr(&Expression::Number(1)),
+                    None => String::new(),
+                };
+                code.push_str(match upper.as_str() {
+                    "INT" => "    call rt_floor\n",
+                    "SQR" => "    sqrtsd %xmm0, %xmm0\n",
+                    "ABS" => "    movq %xmm0, %rax\n    btrq $63, %rax\n    movq %rax, %xmm0\n",
+                    "EXP" => "    call rt_exp\n",
+                    "TIMER" => "    call rt_timer\n",
+                    _ => "    call rt_rnd\n",
+                });
+                code
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Code that leaves `left` in `%xmm0` and `right` in `%xmm1`.
+    fn generate_operands(&mut self, left: &Expression, right: &Expression) -> String {
+        let mut code = self.generate_expr(left);
+        match self.operand(right) {
+            Some(operand) => code.push_str(&format!("    movsd {}, %xmm1\n", operand)),
+            None => {
+                code.push_str("    subq $8, %rsp\n    movsd %xmm0, (%rsp)\n");
+                code.push_str(&self.generate_expr(right));
+                code.push_str("    movapd %xmm0, %xmm1\n    movsd (%rsp), %xmm0\n    addq $8, %rsp\n");
+            }
+        }
+        code
+    }
+
+    /// The seed RANDOMIZE uses instead of asking or reading the clock, if
+    /// `--seed` gave one.
+    fn fixed_seed(&mut self) -> Option<String> {
+        let seed = self.options.seed?;
+        Some(format!("    movsd {}, %xmm0\n", self.constant(seed as f64)))
+    }
+
+    /// Code for a statement that runs straight through.
+    fn generate_statement(&mut self, stmt: &Statement) -> String {
+        match &stmt.node {
+            StatementNode::Let { var, value } => {
+                self.generate_expr(value) + &format!("    movsd %xmm0, {}(%rip)\n", Self::variable(var))
+            }
+            StatementNode::Print { items, newline } => {
+                let mut code = String::new();
+                for item in items {
+                    match item {
+                        PrintItem::String(s) => {
+                            code.push_str(&self.string(s));
+                            code.push_str("    call rt_print_string\n");
+                        }
+                        PrintItem::Expr(expr) => {
+                            code.push_str(&self.generate_expr(expr));
+                            code.push_str("    call rt_print_number\n");
+                        }
+                        PrintItem::Comma | PrintItem::Semicolon => {}
+                    }
+                }
+                if *newline {
+                    code.push_str("    call rt_print_newline\n");
+                }
+                code
+            }
+            StatementNode::Input(var) => format!("    call rt_input\n    movsd %xmm0, {}(%rip)\n", Self::variable(var)),
+            StatementNode::Randomize(None) => match self.fixed_seed() {
+                Some(seed) => seed + "    call rt_randomize\n",
+                None => "    call rt_randomize_prompt\n".to_string(),
+            },
+            StatementNode::Randomize(Some(Expression::FunctionCall { name, args })) if name == "TIMER" && args.is_empty() => {
+                let seed = self.fixed_seed().unwrap_or_else(|| "    call rt_timer\n".to_string());
+                seed + "    call rt_randomize\n"
+            }
+            StatementNode::Randomize(Some(seed)) => self.generate_expr(seed) + "    call rt_randomize\n",
+            StatementNode::Rem(comment) if !comment.is_empty() => format!("    # {}\n", comment),
+            _ => String::new(),
+        }
+    }
+
+    fn block_label(block: usize) -> String {
+        if block == EXIT { "rt_exit".to_string() } else { format!(".Lblock{}", block) }
+    }
+
+    /// Where a jump to `target` goes: its block, or a stub reporting the
+    /// missing line.
+    fn label(&mut self, target: Option<usize>, line: Option<i64>) -> String {
+        match target {
+            Some(target) => Self::block_label(target),
+            None => {
+                self.undefined.insert(line);
+                Self::undefined_label(line)
+            }
+        }
+    }
+
+    fn undefined_label(line: Option<i64>) -> String {
+        match line {
+            Some(line) => format!(".Lundefined{}", line),
+            None => ".Lundefined".to_string(),
+        }
+    }
+
+    /// Go to `target` from the end of `block`, unless it comes next anyway.
+    fn goto(&mut self, block: usize, target: Option<usize>, line: Option<i64>) -> String {
+        if target == Some(block + 1) {
+            return String::new();
+        }
+        format!("    jmp {}\n", self.label(target, line))
+    }
+
+    /// Compare `left` and `right` and go to `then` if `op` holds between
+    /// them, or to `otherwise`. NaN compares unequal to everything.
+    #[allow(clippy::too_many_arguments)]
+    fn branch(
+        &mut self,
+        block: usize,
+        left: &Expression,
+        op: &Token,
+        right: &Expression,
+        then: Option<usize>,
+        otherwise: Option<usize>,
+        stmt: &Statement,
+    ) -> String {
+        let mut code = self.generate_operands(left, right);
+        // Compared this way round, "above" and "above or equal" are false
+        // for NaN, which the opposite conditions then catch
+        let (compare, holds, fails) = match op {
+            Token::LessThan => ("%xmm0, %xmm1", "ja", "jbe"),
+            Token::LessOrEqual => ("%xmm0, %xmm1", "jae", "jb"),
+            Token::GreaterThan => ("%xmm1, %xmm0", "ja", "jbe"),
+            Token::GreaterOrEqual => ("%xmm1, %xmm0", "jae", "jb"),
+            _ => ("%xmm1, %xmm0", "", ""),
+        };
+        code.push_str(&format!("    ucomisd {}\n", compare));
+        let equal = |label: &str| format!("    jp 1f\n    je {}\n1:\n", label);
+        let unequal = |label: &str| format!("    jp {}\n    jne {}\n", label, label);
+        // Fall through into the next block where it can
+        let (jump, target, rest) = if then == Some(block + 1) { (false, otherwise, then) } else { (true, then, otherwise) };
+        let label = self.label(target, stmt.label);
+        code.push_str(&match (op, jump) {
+            (Token::Equal, true) | (Token::NotEqual, false) => equal(&label),
+            (Token::NotEqual, true) | (Token::Equal, false) => unequal(&label),
+            (_, true) => format!("    {} {}\n", holds, label),
+            (_, false) => format!("    {} {}\n", fails, label),
+        });
+        code + &self.goto(block, rest, stmt.label)
+    }
+
+    /// The code of `block`, ending with the jump to the next one.
+    fn generate_block(&mut self, cfg: &Cfg, block: usize) -> String {
+        let successor = |kind| cfg.blocks[block].successors.iter().find(|edge| edge.kind == kind).map(|edge| edge.target);
+        if block == ENTRY {
+            return self.goto(block, successor(EdgeKind::Fallthrough), None);
+        }
+        let Some(last) = cfg.blocks[block].steps.last() else {
+            return "    jmp rt_exit\n".to_string();
+        };
+        let mut code = String::new();
+        for step in &cfg.blocks[block].steps {
+            if let Step::Statement(stmt) = step {
+                if let Some(label) = stmt.label {
+                    code.push_str(&format!("    # {}\n", label));
+                }
+                code.push_str(&self.generate_statement(stmt));
+            }
+        }
+        let stmt = last.statement();
+        let line = stmt.label;
+        let transfer = match (last, &stmt.node) {
+            (Step::Next(_), StatementNode::For { var, end, step, .. }) => {
+                let counter = Expression::Variable(var.clone());
+                let step = step.clone().unwrap_or(Expression::Number(1));
+                let mut code = match last.label() {
+                    Some(label) => format!("    # {}\n", label),
+                    None => String::new(),
+                };
+                code.push_str(&self.generate_operands(&counter, &step));
+                code.push_str(&format!("    addsd %xmm1, %xmm0\n    movsd %xmm0, {}(%rip)\n", Self::variable(var)));
+                let again = successor(EdgeKind::Loop);
+                let done = successor(EdgeKind::Fallthrough);
+                code + &self.branch(block, &counter, &Token::LessOrEqual, end, again, done, stmt)
+            }
+            (_, StatementNode::For { var, start, end, .. }) => {
+                let counter = Expression::Variable(var.clone());
+                let mut code = self.generate_expr(start);
+                code.push_str(&format!("    movsd %xmm0, {}(%rip)\n", Self::variable(var)));
+                let body = successor(EdgeKind::Fallthrough);
+                let skip = successor(EdgeKind::LoopExit);
+                code + &self.branch(block, &counter, &Token::LessOrEqual, end, body, skip, stmt)
+            }
+            (_, StatementNode::If { left, op, right, .. }) => {
+                let then = successor(EdgeKind::Then);
+                let otherwise = successor(EdgeKind::Else);
+                self.branch(block, left, op, right, then, otherwise, stmt)
+            }
+            (_, StatementNode::Goto(_)) => self.goto(block, successor(EdgeKind::Goto), line),
+            (_, StatementNode::Gosub(_)) => {
+                let back = cfg.return_point(block);
+                let mut code = format!("    movq ${}, %rdx\n", line.unwrap_or(-1));
+                code.push_str("    movq gosub_sp(%rip), %rax\n");
+                code.push_str("    cmpq $GOSUB_STACK_SIZE, %rax\n");
+                code.push_str("    jae rt_out_of_memory\n");
+                code.push_str("    leaq gosub_stack(%rip), %rcx\n");
+                code.push_str(&format!("    leaq {}(%rip), %rdx\n", Self::block_label(back)));
+                code.push_str("    movq %rdx, (%rcx,%rax,8)\n");
+                code.push_str("    incq %rax\n");
+                code.push_str("    movq %rax, gosub_sp(%rip)\n");
+                code + &format!("    jmp {}\n", self.label(successor(EdgeKind::Gosub), line))
+            }
+            (_, StatementNode::Return) => {
+                let mut code = format!("    movq ${}, %rdx\n", line.unwrap_or(-1));
+                code.push_str("    movq gosub_sp(%rip), %rax\n");
+                code.push_str("    testq %rax, %rax\n");
+                code.push_str("    jz rt_return_without_gosub\n");
+                code.push_str("    decq %rax\n");
+                code.push_str("    movq %rax, gosub_sp(%rip)\n");
+                code.push_str("    leaq gosub_stack(%rip), %rcx\n");
+                code.push_str("    jmp *(%rcx,%rax,8)\n");
+                code
+            }
+            (_, StatementNode::End) => "    jmp rt_exit\n".to_string(),
+            _ => self.goto(block, successor(EdgeKind::Fallthrough), None),
+        };
+        code + &transfer
+    }
+
+    /// `text` as the operand of `.ascii`.
+    fn escape(text: &str) -> String {
+        let mut escaped = String::new();
+        for byte in text.bytes() {
+            match byte {
+                b'"' | b'\\' => escaped.push_str(&format!("\\{}", byte as char)),
+                b' '..=b'~' => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{:03o}", byte)),
+            }
+        }
+        escaped
+    }
+
+    pub fn generate(&mut self, statements: &[Statement]) -> String {
+        self.collect_variables(statements);
+        let cfg = Cfg::new(statements);
+
+        let mut code = String::new();
+        if let Some(seed) = self.fixed_seed() {
+            code.push_str(&seed);
+            code.push_str("    call rt_randomize\n");
+        }
+        for block in 0..cfg.blocks.len() {
+            let lines: Vec<i64> = cfg.blocks[block].steps.iter().filter_map(|step| step.label()).collect();
+            let comment = match (lines.first(), lines.last()) {
+                (Some(first), Some(last)) if first != last => format!("  # {}-{}", first, last),
+                (Some(first), _) => format!("  # {}", first),
+                _ => String::new(),
+            };
+            code.push_str(&format!(".Lblock{}:{}\n", block, comment));
+            code.push_str(&self.generate_block(&cfg, block));
+        }
+        for line in &self.undefined {
+            code.push_str(&format!("{}:\n", Self::undefined_label(*line)));
+            code.push_str(&format!("    movq ${}, %rdx\n", line.unwrap_or(-1)));
+            code.push_str("    jmp rt_undefined_line\n");
+        }
+
+        let mut result = String::from(RUNTIME);
+        result.push_str(&format!("\n# The program\n\n    .set GOSUB_STACK_SIZE, {}\n", GOSUB_STACK_SIZE));
+        result.push_str("\n    .text\n    .globl _start\n_start:\n");
+        result.push_str(&code);
+        result.push_str("\n    .section .rodata\n    .balign 8\n");
+        for (index, bits) in self.constants.iter().enumerate() {
+            result.push_str(&format!(".LC{}: .quad {:#018x}  # {:?}\n", index, bits, f64::from_bits(*bits)));
+        }
+        for (index, text) in self.strings.iter().enumerate() {
+            result.push_str(&format!(".LS{}: .ascii \"{}\"\n", index, Self::escape(text)));
+        }
+        if !self.variables.is_empty() {
+            result.push_str("\n    .bss\n    .balign 8\n");
+            for var in &self.variables {
+                result.push_str(&format!("{}: .zero 8\n", Self::variable(var)));
+            }
+        }
+        result
+    }
+}
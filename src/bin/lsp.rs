@@ -0,0 +1,15 @@
+//! Language server for BASIC sources, speaking LSP over stdin/stdout.
+
+use std::io;
+
+fn main() {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match compiler::lsp::serve(stdin.lock(), stdout.lock()) {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            eprintln!("lsp: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::diagnostic::Diagnostic;
+use crate::fold;
+use crate::parser::{Expression, Statement, StatementNode};
+
+/// A function built into the language.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub signature: &'static str,
+    pub description: &'static str,
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "ABS",
+        signature: "ABS(x)",
+        description: "Absolute value of x.",
+    },
+    Builtin {
+        name: "EXP",
+        signature: "EXP(x)",
+        description: "e raised to the power x.",
+    },
+    Builtin {
+        name: "INT",
+        signature: "INT(x)",
+        description: "Largest integer less than or equal to x.",
+    },
+    Builtin {
+        name: "RND",
+        signature: "RND(x)",
+        description: "Next random number between 0 and 1; RND(0) repeats the last one and RND(x) with x < 0 starts a new sequence.",
+    },
+    Builtin {
+        name: "SQR",
+        signature: "SQR(x)",
+        description: "Square root of x.",
+    },
+    Builtin {
+        name: "TIMER",
+        signature: "TIMER",
+        description: "Seconds since midnight.",
+    },
+];
+
+/// Look up a builtin by name, ignoring case.
+pub fn lookup(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name.eq_ignore_ascii_case(name))
+}
+
+/// What is wrong with calling `name` with `args` arguments, or `None` if it
+/// is a builtin called correctly. RND may leave out its argument.
+pub fn check_call(name: &str, args: usize) -> Option<String> {
+    let Some(builtin) = lookup(name) else {
+        return Some(format!("Unknown function {}", name));
+    };
+    let arity = if builtin.signature.contains('(') { 1 } else { 0 };
+    if args == arity || (builtin.name == "RND" && args == 0) {
+        return None;
+    }
+    Some(format!("{} takes {} argument{}", builtin.name, arity, if arity == 1 { "" } else { "s" }))
+}
+
+/// Errors for calls to functions that do not exist or that get the wrong
+/// number of arguments, so no target has to handle them.
+pub fn check(statements: &[Statement]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for stmt in statements {
+        for expr in fold::expressions(&stmt.node) {
+            check_expression(expr, stmt, &mut diagnostics);
+        }
+        if let StatementNode::For { body, .. } = &stmt.node {
+            diagnostics.extend(check(body));
+        }
+    }
+    diagnostics
+}
+
+fn check_expression(expr: &Expression, stmt: &Statement, diagnostics: &mut Vec<Diagnostic>) {
+    match expr {
+        Expression::BinaryOp { left, right, .. } => {
+            check_expression(left, stmt, diagnostics);
+            check_expression(right, stmt, diagnostics);
+        }
+        Expression::FunctionCall { name, args } => {
+            if let Some(message) = check_call(name, args.len()) {
+                diagnostics.push(Diagnostic::error(message, stmt.span));
+            }
+            for arg in args {
+                check_expression(arg, stmt, diagnostics);
+            }
+        }
+        Expression::Number(_) | Expression::Float(..) | Expression::Variable(_) => {}
+    }
+}
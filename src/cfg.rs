@@ -0,0 +1,345 @@
+//! Control-flow graph of a parsed program.
+//!
+//! The program is cut into basic blocks: straight runs of steps that are
+//! only entered at the top and only leave at the bottom. Two extra blocks,
+//! [`ENTRY`] and [`EXIT`], stand for the start and the end of the run.
+//!
+//! A FOR loop contributes two steps, each in a block of its own: the FOR
+//! itself (which assigns the variable and may skip the loop) and its NEXT
+//! (which steps the variable and either loops back or leaves). GOSUB jumps
+//! to its target, and every RETURN has an edge back to each statement
+//! following a GOSUB.
+
+use crate::formatter::Formatter;
+use crate::parser::{Statement, StatementNode};
+use std::collections::HashMap;
+
+/// Index of the block every run starts in.
+pub const ENTRY: usize = 0;
+/// Index of the block every run ends in.
+pub const EXIT: usize = 1;
+
+/// One unit of execution inside a block.
+#[derive(Debug, Clone, Copy)]
+pub enum Step<'a> {
+    /// A statement. For a FOR loop this is the loop header, and for an IF
+    /// the test; a THEN part other than a line number is a step of its own.
+    Statement(&'a Statement),
+    /// The NEXT that closes the given FOR loop.
+    Next(&'a Statement),
+}
+
+impl<'a> Step<'a> {
+    pub fn statement(&self) -> &'a Statement {
+        match self {
+            Step::Statement(stmt) | Step::Next(stmt) => stmt,
+        }
+    }
+
+    /// The line number the step is written on, if any.
+    pub fn label(&self) -> Option<i64> {
+        match self {
+            Step::Statement(stmt) => stmt.label,
+            Step::Next(stmt) => match stmt.node {
+                StatementNode::For { next_label, .. } => next_label,
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Why control moves along an edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// On to the following statement.
+    Fallthrough,
+    Goto,
+    /// IF condition true.
+    Then,
+    /// IF condition false.
+    Else,
+    /// NEXT going round again.
+    Loop,
+    /// FOR whose range is empty, skipping past its NEXT.
+    LoopExit,
+    Gosub,
+    Return,
+    End,
+}
+
+impl EdgeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EdgeKind::Fallthrough => "fallthrough",
+            EdgeKind::Goto => "goto",
+            EdgeKind::Then => "then",
+            EdgeKind::Else => "else",
+            EdgeKind::Loop => "loop",
+            EdgeKind::LoopExit => "loop exit",
+            EdgeKind::Gosub => "gosub",
+            EdgeKind::Return => "return",
+            EdgeKind::End => "end",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BasicBlock<'a> {
+    pub steps: Vec<Step<'a>>,
+    pub successors: Vec<Edge>,
+    pub predecessors: Vec<usize>,
+}
+
+/// The blocks of a program. Statement blocks follow [`ENTRY`] and [`EXIT`]
+/// in source order.
+#[derive(Debug, Clone)]
+pub struct Cfg<'a> {
+    pub blocks: Vec<BasicBlock<'a>>,
+}
+
+/// Steps in source order with the edges leaving each one. An edge to
+/// `steps.len()` falls off the end of the program.
+struct StepGraph<'a> {
+    steps: Vec<Step<'a>>,
+    edges: Vec<Vec<Edge>>,
+    labels: HashMap<i64, usize>,
+    /// Jumps to resolve once every label is known.
+    jumps: Vec<(usize, i64, EdgeKind)>,
+    /// Steps that follow a GOSUB.
+    return_points: Vec<usize>,
+    returns: Vec<usize>,
+}
+
+impl<'a> StepGraph<'a> {
+    fn push(&mut self, step: Step<'a>) -> usize {
+        self.steps.push(step);
+        self.edges.push(Vec::new());
+        self.steps.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, target: usize, kind: EdgeKind) {
+        self.edges[from].push(Edge { target, kind });
+    }
+
+    fn add_block(&mut self, statements: &'a [Statement]) {
+        for stmt in statements {
+            self.add_statement(stmt);
+        }
+    }
+
+    fn add_statement(&mut self, stmt: &'a Statement) {
+        let index = self.push(Step::Statement(stmt));
+        if let Some(label) = stmt.label {
+            self.labels.entry(label).or_insert(index);
+        }
+
+        match &stmt.node {
+            StatementNode::End => {}
+            StatementNode::Goto(target) => self.jumps.push((index, *target, EdgeKind::Goto)),
+            StatementNode::Gosub(target) => {
+                self.jumps.push((index, *target, EdgeKind::Gosub));
+                self.return_points.push(index + 1);
+            }
+            StatementNode::Return => self.returns.push(index),
+            StatementNode::If { then_part, .. } => match then_part.node {
+                // THEN <line> is a plain conditional jump
+                StatementNode::Goto(target) => {
+                    self.jumps.push((index, target, EdgeKind::Then));
+                    self.edge(index, index + 1, EdgeKind::Else);
+                }
+                _ => {
+                    self.add_statement(then_part);
+                    // A THEN part that falls through lands on the same step
+                    let after = self.steps.len();
+                    self.edge(index, index + 1, EdgeKind::Then);
+                    self.edge(index, after, EdgeKind::Else);
+                }
+            },
+            StatementNode::For { body, next_label, .. } => {
+                self.add_block(body);
+                let next = self.push(Step::Next(stmt));
+                if let Some(label) = next_label {
+                    self.labels.entry(*label).or_insert(next);
+                }
+                self.edge(index, index + 1, EdgeKind::Fallthrough);
+                self.edge(index, next + 1, EdgeKind::LoopExit);
+                self.edge(next, index + 1, EdgeKind::Loop);
+                self.edge(next, next + 1, EdgeKind::Fallthrough);
+            }
+            StatementNode::Let { .. }
+            | StatementNode::Print { .. }
+            | StatementNode::Input(_)
+            | StatementNode::Randomize(_)
+            | StatementNode::Tron
+            | StatementNode::Troff
+            | StatementNode::Rem(_)
+            | StatementNode::Empty => self.edge(index, index + 1, EdgeKind::Fallthrough),
+        }
+    }
+}
+
+impl<'a> Cfg<'a> {
+    pub fn new(statements: &'a [Statement]) -> Self {
+        let mut graph = StepGraph {
+            steps: Vec::new(),
+            edges: Vec::new(),
+            labels: HashMap::new(),
+            jumps: Vec::new(),
+            return_points: Vec::new(),
+            returns: Vec::new(),
+        };
+        graph.add_block(statements);
+        // Jumps to lines that do not exist lead nowhere
+        for (from, target, kind) in std::mem::take(&mut graph.jumps) {
+            if let Some(&index) = graph.labels.get(&target) {
+                graph.edge(from, index, kind);
+            }
+        }
+        for from in std::mem::take(&mut graph.returns) {
+            for point in graph.return_points.clone() {
+                graph.edge(from, point, EdgeKind::Return);
+            }
+        }
+        Self::from_steps(graph)
+    }
+
+    /// Cut the steps into basic blocks.
+    fn from_steps(graph: StepGraph<'a>) -> Self {
+        let count = graph.steps.len();
+        let mut leader = vec![false; count + 1];
+        if count > 0 {
+            leader[0] = true;
+        }
+        for (index, step) in graph.steps.iter().enumerate() {
+            // FOR and NEXT get blocks of their own so loops are easy to find
+            if matches!(step, Step::Next(_) | Step::Statement(Statement { node: StatementNode::For { .. }, .. })) {
+                leader[index] = true;
+            }
+        }
+        for (index, edges) in graph.edges.iter().enumerate() {
+            let straight = matches!(edges.as_slice(), [Edge { kind: EdgeKind::Fallthrough, target }] if *target == index + 1);
+            if !straight {
+                leader[index + 1] = true;
+            }
+            for edge in edges {
+                if edge.kind != EdgeKind::Fallthrough || !straight {
+                    leader[edge.target] = true;
+                }
+            }
+        }
+
+        // Map each step to its block; falling off the end goes to EXIT
+        let mut block_of = vec![EXIT; count + 1];
+        let mut blocks = vec![BasicBlock::default(), BasicBlock::default()];
+        for (index, step) in graph.steps.iter().enumerate() {
+            if leader[index] {
+                blocks.push(BasicBlock::default());
+            }
+            block_of[index] = blocks.len() - 1;
+            blocks.last_mut().unwrap().steps.push(*step);
+        }
+
+        let entry_target = if count > 0 { block_of[0] } else { EXIT };
+        blocks[ENTRY].successors.push(Edge { target: entry_target, kind: EdgeKind::Fallthrough });
+        for (index, edges) in graph.edges.iter().enumerate() {
+            let is_last = index + 1 == count || leader[index + 1];
+            if !is_last {
+                continue;
+            }
+            let block = block_of[index];
+            for edge in edges {
+                blocks[block].successors.push(Edge { target: block_of[edge.target], kind: edge.kind });
+            }
+            if matches!(graph.steps[index], Step::Statement(Statement { node: StatementNode::End, .. })) {
+                blocks[block].successors.push(Edge { target: EXIT, kind: EdgeKind::End });
+            }
+        }
+
+        for from in 0..blocks.len() {
+            for edge in blocks[from].successors.clone() {
+                if !blocks[edge.target].predecessors.contains(&from) {
+                    blocks[edge.target].predecessors.push(from);
+                }
+            }
+        }
+        Cfg { blocks }
+    }
+
+    /// Which blocks some run can reach.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut seen = vec![false; self.blocks.len()];
+        let mut stack = vec![ENTRY];
+        while let Some(block) = stack.pop() {
+            if seen[block] {
+                continue;
+            }
+            seen[block] = true;
+            stack.extend(self.blocks[block].successors.iter().map(|edge| edge.target));
+        }
+        seen
+    }
+
+    /// The statement blocks in source order, with their indices.
+    pub fn statement_blocks(&self) -> impl Iterator<Item = (usize, &BasicBlock<'a>)> {
+        self.blocks.iter().enumerate().skip(EXIT + 1)
+    }
+
+    /// Where a RETURN goes back to after a GOSUB ending `block`: the block
+    /// after it, or the exit if it is the last.
+    pub fn return_point(&self, block: usize) -> usize {
+        if block + 1 < self.blocks.len() { block + 1 } else { EXIT }
+    }
+
+    /// Render as a Graphviz `digraph`.
+    pub fn to_dot(&self) -> String {
+        let mut formatter = Formatter::new(Default::default());
+        let mut result = String::from("digraph cfg {\n");
+        result.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        result.push_str(&format!("    b{} [label=\"entry\", shape=oval];\n", ENTRY));
+        result.push_str(&format!("    b{} [label=\"exit\", shape=oval];\n", EXIT));
+        for (index, block) in self.statement_blocks() {
+            let mut text = String::new();
+            for step in &block.steps {
+                let line = match step {
+                    Step::Statement(stmt) => formatter.format_statement_node(&stmt.node),
+                    Step::Next(stmt) => match &stmt.node {
+                        StatementNode::For { var, .. } => format!("NEXT {}", var),
+                        _ => "NEXT".to_string(),
+                    },
+                };
+                let line = match step.label() {
+                    Some(label) => format!("{} {}", label, line).trim_end().to_string(),
+                    None => line,
+                };
+                text.push_str(&dot_escape(&line));
+                text.push_str("\\l");
+            }
+            result.push_str(&format!("    b{} [label=\"{}\"];\n", index, text));
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            for edge in &block.successors {
+                match edge.kind {
+                    EdgeKind::Fallthrough => result.push_str(&format!("    b{} -> b{};\n", index, edge.target)),
+                    kind => result.push_str(&format!(
+                        "    b{} -> b{} [label=\"{}\"];\n",
+                        index,
+                        edge.target,
+                        kind.name()
+                    )),
+                }
+            }
+        }
+        result.push_str("}\n");
+        result
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
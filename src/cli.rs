@@ -0,0 +1,273 @@
+use compiler::dialect::Dialect;
+use compiler::diagnostic::WarningConfig;
+use compiler::driver::BuildOptions;
+use compiler::formatter::FormatOptions;
+use compiler::numbers::Numbers;
+use compiler::renum::RenumOptions;
+use compiler::runtime::Runtime;
+use compiler::Target;
+use std::env;
+use std::io::{self, IsTerminal};
+
+pub const USAGE: &str = "\
+Usage: compiler <command> [options] <input>
+
+Commands:
+  build    Compile a program to an executable (or see --emit)
+  run      Compile a program and run it
+  check    Report errors and warnings without generating code
+  fmt      Print a program in canonical form
+  renum    Renumber a program: renum <input> [new[,old[,inc]]]
+  debug    Run a program under a debugger reading commands from stdin
+
+Options:
+  -o <file>            Output file (default: stdout, or the input name for exe)
+  --emit <kind>        What build produces: exe (default), c, llvm-ir, tokens,
+                       ast, tokens-json, ast-json or cfg-dot; llvm-ir links
+                       with basic_rt.c, written next to it with -o
+  --target <lang>      c (default), rust, wasm, x86_64-asm or js; for those
+                       but c, build writes the program instead of an
+                       executable
+  --keep-c             Keep the generated C next to the executable
+  --opt-level <level>  Optimisation level for the C compiler (default 2)
+  --no-opt             Skip constant folding and dead code elimination
+  --runtime <kind>     inline (default) copies the C runtime into the output;
+                       external includes basic_rt.h and writes basic_rt.c
+                       next to it
+  --seed <n>           Seed RND with n; RANDOMIZE TIMER and RANDOMIZE then
+                       use n too, so runs repeat
+  --numbers <kind>     double (default) or mbf, to round every result to a
+                       GW-BASIC single as the original did
+  --checks             Report division by zero, overflow and illegal function
+                       calls at runtime, naming the BASIC line
+  --trace              Print [line] as each line runs, as after TRON, and
+                       ignore TROFF
+  -g                   Build with debug info (implies --line-directives)
+  --line-directives    Emit #line directives pointing back at the BASIC source,
+                       or !dbg line locations with --emit llvm-ir
+  --dialect <name>     gwbasic (default) or ansi
+  -W <flag>            error, no-error, <warning> or no-<warning>
+  --color <when>       auto (default), always or never
+  --indent-for         fmt: indent FOR bodies
+  --implicit-let       fmt: write assignments without LET
+  -h, --help           Show this message
+
+Executables are built with $CC, or cc if it is not set.
+Use - as <input> to read the program from stdin.
+Exit status is 0 on success, 1 if the program has errors and 2 on bad usage.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Build,
+    Run,
+    Check,
+    Fmt,
+    Renum,
+    Debug,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Exe,
+    Tokens,
+    Ast,
+    TokensJson,
+    AstJson,
+    C,
+    /// The control-flow graph in Graphviz format
+    CfgDot,
+    /// Textual LLVM IR, calling the C runtime
+    LlvmIr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether diagnostics written to stderr should be coloured.
+    pub fn enabled(&self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        }
+    }
+}
+
+/// Everything the command line asked for.
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    pub command: Command,
+    /// Input path, or `-` for stdin.
+    pub input: String,
+    pub output: Option<String>,
+    pub emit: Emit,
+    pub target: Target,
+    pub build: BuildOptions,
+    pub line_directives: bool,
+    pub no_opt: bool,
+    pub checks: bool,
+    pub trace: bool,
+    pub seed: Option<i64>,
+    pub numbers: Numbers,
+    pub dialect: Dialect,
+    pub warnings: WarningConfig,
+    pub color: ColorChoice,
+    pub format: FormatOptions,
+    pub renum: RenumOptions,
+}
+
+/// Outcome of reading the command line.
+pub enum Parsed {
+    Options(CliOptions),
+    Help,
+}
+
+impl CliOptions {
+    pub fn parse(args: &[String]) -> Result<Parsed, String> {
+        let mut iter = args.iter();
+        let command = match iter.next().map(|s| s.as_str()) {
+            Some("build") => Command::Build,
+            Some("run") => Command::Run,
+            Some("check") => Command::Check,
+            Some("fmt") => Command::Fmt,
+            Some("renum") => Command::Renum,
+            Some("debug") => Command::Debug,
+            Some("-h" | "--help" | "help") => return Ok(Parsed::Help),
+            Some(other) => return Err(format!("unknown command '{}'", other)),
+            None => return Err("no command given".to_string()),
+        };
+
+        let mut options = CliOptions {
+            command,
+            input: String::new(),
+            output: None,
+            emit: Emit::Exe,
+            target: Target::default(),
+            build: BuildOptions::default(),
+            line_directives: false,
+            no_opt: false,
+            checks: false,
+            trace: false,
+            seed: None,
+            numbers: Numbers::default(),
+            dialect: Dialect::default(),
+            warnings: WarningConfig::default(),
+            color: ColorChoice::Auto,
+            format: FormatOptions::default(),
+            renum: RenumOptions::default(),
+        };
+        let mut positional = Vec::new();
+
+        while let Some(arg) = iter.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |name: &str| -> Result<String, String> {
+                match inline_value.clone() {
+                    Some(value) => Ok(value),
+                    None => iter.next().cloned().ok_or(format!("{} needs a value", name)),
+                }
+            };
+
+            match flag {
+                "-h" | "--help" => return Ok(Parsed::Help),
+                "-o" => options.output = Some(value("-o")?),
+                "--emit" => {
+                    options.emit = match value("--emit")?.as_str() {
+                        "exe" => Emit::Exe,
+                        "tokens" => Emit::Tokens,
+                        "ast" => Emit::Ast,
+                        "tokens-json" => Emit::TokensJson,
+                        "ast-json" => Emit::AstJson,
+                        "c" => Emit::C,
+                        "cfg-dot" => Emit::CfgDot,
+                        "llvm-ir" => Emit::LlvmIr,
+                        other => return Err(format!("unknown --emit kind '{}'", other)),
+                    }
+                }
+                "--target" => {
+                    let name = value("--target")?;
+                    options.target = Target::from_name(&name).ok_or(format!("unknown target '{}'", name))?;
+                }
+                "--keep-c" => options.build.keep_c = true,
+                "-g" => {
+                    options.build.debug_info = true;
+                    options.line_directives = true;
+                }
+                "--line-directives" => options.line_directives = true,
+                "--opt-level" => {
+                    let level = value("--opt-level")?;
+                    if !matches!(level.as_str(), "0" | "1" | "2" | "3" | "s" | "z" | "g") {
+                        return Err(format!("unknown --opt-level '{}'", level));
+                    }
+                    options.build.opt_level = level;
+                }
+                "--runtime" => {
+                    let name = value("--runtime")?;
+                    options.build.runtime = Runtime::from_name(&name).ok_or(format!("unknown runtime '{}'", name))?;
+                }
+                "--no-opt" => options.no_opt = true,
+                "--checks" => options.checks = true,
+                "--trace" => options.trace = true,
+                "--seed" => {
+                    let seed = value("--seed")?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("--seed needs an integer, not '{}'", seed))?);
+                }
+                "--numbers" => {
+                    let name = value("--numbers")?;
+                    options.numbers = Numbers::from_name(&name).ok_or(format!("unknown number format '{}'", name))?;
+                }
+                "--dialect" => {
+                    let name = value("--dialect")?;
+                    options.dialect = Dialect::from_name(&name).ok_or(format!("unknown dialect '{}'", name))?;
+                }
+                "-W" => options.warnings.set(&value("-W")?)?,
+                "--color" => {
+                    options.color = match value("--color")?.as_str() {
+                        "auto" => ColorChoice::Auto,
+                        "always" => ColorChoice::Always,
+                        "never" => ColorChoice::Never,
+                        other => return Err(format!("unknown --color value '{}'", other)),
+                    }
+                }
+                "--indent-for" => options.format.indent_for = true,
+                "--implicit-let" => options.format.explicit_let = false,
+                _ if flag.starts_with("-W") => options.warnings.set(&flag[2..])?,
+                _ if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option '{}'", flag)),
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        options.input = positional.next().ok_or("no input file given")?;
+        if command == Command::Renum && let Some(renum_args) = positional.next() {
+            options.renum = RenumOptions::parse(&renum_args)?;
+        }
+        if let Some(extra) = positional.next() {
+            return Err(format!("unexpected argument '{}'", extra));
+        }
+        if command == Command::Debug && options.input == "-" {
+            return Err("debug reads its commands from stdin, so needs an input file".to_string());
+        }
+        if options.target != Target::C {
+            if options.checks || options.trace || options.numbers != Numbers::Double {
+                return Err("--checks, --trace and --numbers mbf need --target c".to_string());
+            }
+            if options.command == Command::Run {
+                return Err("run needs --target c".to_string());
+            }
+            if matches!(options.emit, Emit::C | Emit::LlvmIr) {
+                return Err("--emit c and --emit llvm-ir need --target c".to_string());
+            }
+        }
+
+        Ok(Parsed::Options(options))
+    }
+}
@@ -1,17 +1,128 @@
+use crate::cfg::{Cfg, Step};
+use crate::dce;
+use crate::diagnostic::Diagnostic;
+use crate::dialect::Dialect;
+use crate::runtime::{self, Runtime};
+use crate::infer::Types;
+use crate::numbers::Numbers;
 use crate::parser::{BinOp, Expression, Statement, StatementNode, PrintItem};
 use crate::lexer::Token;
+use crate::structure::{self, Structure, Structured, Target};
 use std::collections::BTreeSet;
 
+/// How deeply GOSUB calls may nest, for every target.
+pub const GOSUB_STACK_SIZE: usize = 256;
+
+/// Options controlling the generated C.
+#[derive(Debug, Clone, Default)]
+pub struct CodegenOptions {
+    /// Emit `#line N "file"` before each statement so C diagnostics and
+    /// debuggers refer to this BASIC source file.
+    pub line_directives: Option<String>,
+    /// The file the generated C is compiled from, which `#line` directives
+    /// return to for the code that does not come from a BASIC statement:
+    /// the runtime, the end of `main` and the GOSUB return dispatch.
+    /// Without one there is nothing to name, and they do not return.
+    pub c_file: Option<String>,
+    /// Report arithmetic errors at runtime instead of producing inf or NaN.
+    pub checks: bool,
+    /// Decides which runtime errors stop the program.
+    pub dialect: Dialect,
+    pub runtime: Runtime,
+    /// Seed RND with this instead of the clock or the user, for runs that
+    /// can be repeated.
+    pub seed: Option<i64>,
+    /// Round every result to an MBF single, as GW-BASIC did, or not.
+    pub numbers: Numbers,
+    /// Print every line number as it runs, as after TRON, whatever the
+    /// program's own TRON and TROFF say.
+    pub trace: bool,
+}
+
+/// Whether the program prints line numbers as it runs: with `--trace`, or
+/// if it has a TRON.
+pub fn traces(statements: &[Statement], options: &CodegenOptions) -> bool {
+    options.trace || statements.iter().any(|stmt| has_tron(&stmt.node))
+}
+
+fn has_tron(node: &StatementNode) -> bool {
+    match node {
+        StatementNode::Tron => true,
+        StatementNode::If { then_part, .. } => has_tron(&then_part.node),
+        StatementNode::For { body, .. } => body.iter().any(|stmt| has_tron(&stmt.node)),
+        _ => false,
+    }
+}
+
+/// Errors for each TRON and TROFF, for the targets that have no way to
+/// print line numbers as they run; only C and LLVM IR do.
+pub fn check_tracing(statements: &[Statement]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for stmt in statements {
+        match &stmt.node {
+            StatementNode::Tron => diagnostics.push(Diagnostic::error("TRON needs --target c", stmt.span)),
+            StatementNode::Troff => diagnostics.push(Diagnostic::error("TROFF needs --target c", stmt.span)),
+            StatementNode::If { then_part, .. } => diagnostics.extend(check_tracing(std::slice::from_ref(then_part))),
+            StatementNode::For { body, .. } => diagnostics.extend(check_tracing(body)),
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+/// `s` as a C string literal. Octal escapes stop after three digits, so a
+/// digit after one is safe, and `\?` keeps `??` from starting a trigraph.
+fn c_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    let mut previous = None;
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '?' if previous == Some('?') => out.push_str("\\?"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u32)),
+            c => out.push(c),
+        }
+        previous = Some(ch);
+    }
+    out.push('"');
+    out
+}
+
+/// `text` as a C block comment. A `//` comment would run on into the next
+/// line if it ended in `\` or the trigraph `??/`.
+fn c_comment(text: &str) -> String {
+    format!("/* {} */", text.replace("*/", "* /").replace("/*", "/ *"))
+}
+
 pub struct CodeGenerator {
     indent_level: usize,
     variables: BTreeSet<String>,
+    options: CodegenOptions,
+    /// Number of GOSUB statements generated so far; each gets a return label.
+    gosub_count: usize,
+    uses_return: bool,
+    types: Types,
+    /// Whether lines call `rt_trace` as they start; see [`traces`].
+    traces: bool,
+    /// Every line number in the program, so a jump to another is an error.
+    lines: BTreeSet<i64>,
 }
 
 impl CodeGenerator {
-    pub fn new() -> Self {
+    pub fn new(options: CodegenOptions) -> Self {
         CodeGenerator {
             indent_level: 1,
             variables: BTreeSet::new(),
+            options,
+            gosub_count: 0,
+            uses_return: false,
+            types: Types::default(),
+            traces: false,
+            lines: BTreeSet::new(),
         }
     }
 
@@ -19,29 +130,124 @@ impl CodeGenerator {
         "    ".repeat(self.indent_level)
     }
 
+    /// Whether `expr` can be computed in `int32_t` arithmetic: it is built
+    /// from integer variables and integer constants with `+`, `-` and `*`,
+    /// and no intermediate result leaves the range.
+    fn is_integer_expr(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Number(_) | Expression::Float(..) => self.types.fits(&self.types.value(expr)),
+            Expression::Variable(name) => self.types.is_integer(name),
+            Expression::BinaryOp { left, operator: BinOp::Add | BinOp::Subtract | BinOp::Multiply, right } => {
+                self.types.fits(&self.types.value(expr)) && self.is_integer_expr(left) && self.is_integer_expr(right)
+            }
+            Expression::BinaryOp { .. } | Expression::FunctionCall { .. } => false,
+        }
+    }
+
+    /// C for `expr` in `int32_t` where possible, and whether it is.
+    fn generate_typed(&self, expr: &Expression) -> (String, bool) {
+        if !self.is_integer_expr(expr) {
+            return (self.generate_expr(expr), false);
+        }
+        let code = match expr {
+            Expression::Number(n) => n.to_string(),
+            Expression::Float(f, _) => (*f as i64).to_string(),
+            Expression::Variable(name) => name.clone(),
+            Expression::BinaryOp { left, operator, right } => {
+                let (left_string, _) = self.generate_typed(left);
+                let (right_string, _) = self.generate_typed(right);
+                let op = match operator {
+                    BinOp::Add => "+",
+                    BinOp::Subtract => "-",
+                    _ => "*",
+                };
+                format!("({} {} {})", left_string, op, right_string)
+            }
+            _ => self.generate_expr(expr),
+        };
+        (code, true)
+    }
+
+    /// C for `expr` as an assignment to `var`.
+    fn generate_value_for(&self, var: &str, expr: &Expression) -> String {
+        if !self.types.is_integer(var) {
+            return self.generate_expr(expr);
+        }
+        match self.generate_typed(expr) {
+            (code, true) => code,
+            (code, false) => format!("(int32_t){}", code),
+        }
+    }
+
+    /// C for one operand of a double operator: integer variables are used
+    /// as they are, constants are written as doubles.
+    fn generate_operand(&self, expr: &Expression) -> (String, bool) {
+        match expr {
+            Expression::Number(_) | Expression::Float(..) => (self.generate_expr(expr), false),
+            _ => self.generate_typed(expr),
+        }
+    }
+
+    /// `code` rounded to the program's number format.
+    fn round(&self, code: String) -> String {
+        match self.options.numbers {
+            Numbers::Double => code,
+            Numbers::Mbf => format!("rt_mbf({})", code),
+        }
+    }
+
+    /// C for a constant. One that is too large for the number format is
+    /// left for the runtime to report.
+    fn generate_constant(&self, value: f64) -> String {
+        match self.options.numbers.round(value) {
+            // Debug formatting keeps a decimal point, so C never sees an int
+            Some(rounded) => format!("{:?}", rounded),
+            None => self.round(format!("{:?}", value)),
+        }
+    }
+
+    /// C for `expr` computed in double precision.
     fn generate_expr(&self, expr: &Expression) -> String {
         match expr {
-            Expression::Number(n) => format!("{}.0", n),
-            Expression::Float(f) => f.to_string(),
+            Expression::Number(n) => self.generate_constant(*n as f64),
+            Expression::Float(f, _) => self.generate_constant(*f),
             Expression::Variable(name) => name.clone(),
             Expression::BinaryOp { left, operator, right } => {
-                let left_string = self.generate_expr(left);
-                let right_string = self.generate_expr(right);
-                match operator {
+                let (mut left_string, left_integer) = self.generate_operand(left);
+                let (right_string, right_integer) = self.generate_operand(right);
+                // Two int32_t operands would otherwise use int arithmetic
+                if left_integer && right_integer {
+                    left_string = format!("(double){}", left_string);
+                }
+                if self.options.checks {
+                    let helper = match operator {
+                        BinOp::Add => "rt_add",
+                        BinOp::Subtract => "rt_sub",
+                        BinOp::Multiply => "rt_mul",
+                        BinOp::Divide => "rt_div",
+                        BinOp::Power => "rt_pow",
+                    };
+                    return self.round(format!("{}({}, {})", helper, left_string, right_string));
+                }
+                let code = match operator {
                     BinOp::Add => format!("({} + {})", left_string, right_string),
                     BinOp::Subtract => format!("({} - {})", left_string, right_string),
                     BinOp::Multiply => format!("({} * {})", left_string, right_string),
                     BinOp::Divide => format!("({} / {})", left_string, right_string),
                     BinOp::Power => format!("pow({}, {})", left_string, right_string),
-                }
+                };
+                self.round(code)
             }
             Expression::FunctionCall { name, args } => {
                 let args_string: Vec<String> = args.iter().map(|a| self.generate_expr(a)).collect();
                 match name.to_uppercase().as_str() {
                     "INT" => format!("floor({})", args_string[0]),
-                    "RND" => "((double)rand() / (double)RAND_MAX)".to_string(),
-                    "SQR" => format!("sqrt({})", args_string[0]),
-                    "EXP" => format!("exp({})", args_string[0]),
+                    "RND" => format!("rt_rnd({})", args_string.first().map_or("1.0", String::as_str)),
+                    "TIMER" => "rt_timer()".to_string(),
+                    "SQR" if self.options.checks => self.round(format!("rt_sqr({})", args_string[0])),
+                    "EXP" if self.options.checks => self.round(format!("rt_exp({})", args_string[0])),
+                    "SQR" => self.round(format!("sqrt({})", args_string[0])),
+                    "EXP" => self.round(format!("exp({})", args_string[0])),
                     "ABS" => format!("fabs({})", args_string[0]),
                     _ => format!("{}({})", name, args_string.join(", ")),
                 }
@@ -74,10 +280,12 @@ impl CodeGenerator {
         }
     }
 
-    fn generate_statement_node(&mut self, node: &StatementNode) -> String {
-        match node {
+    /// C for a statement that runs straight through. Jumps, IF tests and
+    /// loops are written from the program's structure instead.
+    fn generate_statement(&mut self, stmt: &Statement) -> String {
+        match &stmt.node {
             StatementNode::Let { var, value } => {
-                let value_string = self.generate_expr(value);
+                let value_string = self.generate_value_for(var, value);
                 format!("{}{} = {};\n", self.indent(), var, value_string)
             }
             StatementNode::Print { items, newline } => {
@@ -85,107 +293,402 @@ impl CodeGenerator {
                 for item in items {
                     match item {
                         PrintItem::String(s) => {
-                            result.push_str(&format!("{}printf(\"%s\", \"{}\");\n", self.indent(), s));
+                            result.push_str(&format!("{}rt_print_string({});\n", self.indent(), c_string(s)));
                         }
                         PrintItem::Expr(expr) => {
-                            let expr_string = self.generate_expr(expr);
-                            // We use %g for general float printing
-                            result.push_str(&format!("{}printf(\"%g \", {});\n", self.indent(), expr_string));
+                            // Keep int32_t arithmetic exact, then print as a double
+                            let expr_string = match self.generate_typed(expr) {
+                                (code, true) => format!("(double){}", code),
+                                (code, false) => code,
+                            };
+                            let print = match self.options.numbers {
+                                Numbers::Double => "rt_print_number",
+                                Numbers::Mbf => "rt_print_single",
+                            };
+                            result.push_str(&format!("{}{}({});\n", self.indent(), print, expr_string));
                         }
+                        PrintItem::Comma | PrintItem::Semicolon => {}
                     }
                 }
                 if *newline {
-                    result.push_str(&format!("{}printf(\"\\n\");\n", self.indent()));
+                    result.push_str(&format!("{}rt_print_newline();\n", self.indent()));
                 }
                 result
             }
-            StatementNode::For { var, start, end, step, body } => {
-                let start_string = self.generate_expr(start);
-                let end_string = self.generate_expr(end);
-                let step_string = step.as_ref().map(|s| self.generate_expr(s)).unwrap_or("1.0".to_string());
-                
-                let mut result = format!(
-                    "{}for ({} = {}; {} <= {}; {} += {}) {{\n",
+            StatementNode::Gosub(line) => {
+                let index = self.gosub_count;
+                self.gosub_count += 1;
+                let jump = if self.lines.contains(line) {
+                    format!("goto line{};", line)
+                } else {
+                    format!("{};", self.undefined_line(stmt.label))
+                };
+                format!(
+                    "{}if (gosub_sp == {}) rt_error(\"Out of memory\", 1);\n{}gosub_stack[gosub_sp++] = {}; {}\nreturn{}: ;\n",
                     self.indent(),
-                    var,
-                    start_string,
-                    var,
-                    end_string,
-                    var,
-                    step_string
-                );
-
-                self.indent_level += 1;
-                for stmt in body {
-                    result.push_str(&self.generate_statements_internal(stmt));
-                }
-                self.indent_level -= 1;
+                    GOSUB_STACK_SIZE,
+                    self.indent(),
+                    index,
+                    jump,
+                    index
+                )
+            }
+            StatementNode::Return => {
+                self.uses_return = true;
+                format!("{}goto gosub_return;\n", self.indent())
+            }
+            StatementNode::Randomize(None) => format!("{}rt_randomize_prompt();\n", self.indent()),
+            StatementNode::Randomize(Some(Expression::FunctionCall { name, args })) if name == "TIMER" && args.is_empty() => {
+                format!("{}rt_randomize_timer();\n", self.indent())
+            }
+            StatementNode::Randomize(Some(seed)) => {
+                format!("{}rt_randomize({});\n", self.indent(), self.generate_expr(seed))
+            }
+            StatementNode::Input(var) => match self.options.numbers {
+                Numbers::Double => format!("{}rt_input(&{});\n", self.indent(), var),
+                Numbers::Mbf => format!("{}rt_input(&{});\n{}{} = rt_mbf({});\n", self.indent(), var, self.indent(), var, var),
+            },
+            StatementNode::Rem(comment) if !comment.is_empty() => format!("{}{}\n", self.indent(), c_comment(comment)),
+            // --trace has tracing on from the start to the end
+            StatementNode::Tron if !self.options.trace => format!("{}rt_tracing = 1;\n", self.indent()),
+            StatementNode::Troff if self.traces && !self.options.trace => format!("{}rt_tracing = 0;\n", self.indent()),
+            StatementNode::For { .. }
+            | StatementNode::If { .. }
+            | StatementNode::Goto(_)
+            | StatementNode::Rem(_)
+            | StatementNode::Empty
+            | StatementNode::Tron
+            | StatementNode::Troff
+            | StatementNode::End => String::new(),
+        }
+    }
 
-                result.push_str(&format!("{}}}\n", self.indent()));
-                result
+    /// With `--checks`, record that line `label` is running so runtime
+    /// errors can name it.
+    fn track_line(&self, label: Option<i64>) -> String {
+        match label {
+            Some(line) if self.options.checks => format!("{}rt_line = {};\n", self.indent(), line),
+            _ => String::new(),
+        }
+    }
+
+    /// When tracing, print line `label` as it starts.
+    fn trace_line(&self, label: Option<i64>) -> String {
+        match label {
+            Some(line) if self.traces => format!("{}rt_trace({});\n", self.indent(), line),
+            _ => String::new(),
+        }
+    }
+
+    fn line_directive(&self, stmt: &Statement) -> String {
+        match &self.options.line_directives {
+            Some(file) => format!("#line {} {}\n", stmt.span.line, c_string(file)),
+            None => String::new(),
+        }
+    }
+
+    /// A `#line` pointing back at the generated C for the code after
+    /// `result`, once `#line` directives name the BASIC source.
+    fn generated_line_directive(&self, result: &str) -> String {
+        let (Some(_), Some(file)) = (&self.options.line_directives, &self.options.c_file) else {
+            return String::new();
+        };
+        // The directive takes a line itself; it names the one after it
+        format!("#line {} {}\n", result.matches('\n').count() + 2, c_string(file))
+    }
+
+    /// Mark the first line of `code` with the BASIC line it came from.
+    fn mark_line(code: String, label: Option<i64>) -> String {
+        match (label, code.find('\n')) {
+            (Some(label), Some(end)) if !code.trim_start().starts_with("/*") => {
+                format!("{}  // {}{}", &code[..end], label, &code[end..])
             }
-            StatementNode::If { left, op, right, then_part } => {
-                let left_string = self.generate_expr(left);
-                let right_string = self.generate_expr(right);
-                let op_string = match op {
-                    Token::Equal         => "=",
-                    Token::NotEqual       => "!=",
-                    Token::LessThan       => "<",
-                    Token::LessOrEqual    => "<=",
-                    Token::GreaterThan    => ">",
-                    Token::GreaterOrEqual => ">=",
-                    _ => "==",
-                };
-                let mut result = format!("{}if ({} {} {}) {{\n", self.indent(), left_string, op_string, right_string);
-                self.indent_level += 1;
-                result.push_str(&self.generate_statements_internal(then_part));
-                self.indent_level -= 1;
-                result.push_str(&format!("{}}}\n", self.indent()));
-                result
+            _ => code,
+        }
+    }
+
+    fn label_name(cfg: &Cfg, block: usize) -> String {
+        match cfg.blocks[block].steps.first().and_then(|step| step.label()) {
+            Some(line) => format!("line{}", line),
+            None => format!("block{}", block),
+        }
+    }
+
+    /// The condition of the IF that ends block `cond`.
+    fn generate_condition(&self, cfg: &Cfg, cond: usize, negated: bool) -> String {
+        let Some(StatementNode::If { left, op, right, .. }) = cfg.blocks[cond].steps.last().map(|s| &s.statement().node)
+        else {
+            return "1".to_string();
+        };
+        // Compare in int32_t only when both sides are integers
+        let (left_string, right_string) = match (self.generate_typed(left), self.generate_typed(right)) {
+            ((left, true), (right, true)) => (left, right),
+            _ => (self.generate_expr(left), self.generate_expr(right)),
+        };
+        let op_string = match (op, negated) {
+            (Token::Equal, false) | (Token::NotEqual, true) => "==",
+            (Token::NotEqual, false) | (Token::Equal, true) => "!=",
+            (Token::LessThan, _) => "<",
+            (Token::LessOrEqual, _) => "<=",
+            (Token::GreaterThan, _) => ">",
+            (Token::GreaterOrEqual, _) => ">=",
+            _ => "==",
+        };
+        let condition = format!("{} {} {}", left_string, op_string, right_string);
+        // Only = and <> can be flipped; the others differ on NaN
+        let condition = if negated && !matches!(op, Token::Equal | Token::NotEqual) {
+            format!("!({})", condition)
+        } else {
+            condition
+        };
+        // Loop conditions are tested again and again, so set the line as
+        // part of the test
+        let label = Self::condition_statement(cfg, cond).label;
+        let condition = match label {
+            Some(line) if self.options.checks => format!("rt_line = {}, {}", line, condition),
+            _ => condition,
+        };
+        match label {
+            Some(line) if self.traces => format!("rt_trace({}), {}", line, condition),
+            _ => condition,
+        }
+    }
+
+    /// The IF statement ending block `cond`, for its line directive and mark.
+    fn condition_statement<'a>(cfg: &Cfg<'a>, cond: usize) -> &'a Statement {
+        cfg.blocks[cond].steps.last().expect("IF block has steps").statement()
+    }
+
+    fn generate_label(&self, cfg: &Cfg, structure: &Structure, block: usize, bare: bool) -> String {
+        if !structure.labelled.contains(&block) {
+            return String::new();
+        }
+        // A label must be followed by a statement
+        let terminator = if bare { " ;" } else { "" };
+        format!("{}:{}\n", Self::label_name(cfg, block), terminator)
+    }
+
+    fn generate_block(&mut self, cfg: &Cfg, structure: &Structure, block: usize) -> String {
+        let mut code = String::new();
+        for step in &cfg.blocks[block].steps {
+            let Step::Statement(stmt) = step else {
+                continue;
+            };
+            let mut statement = self.generate_statement(stmt);
+            if !statement.is_empty() {
+                statement = self.track_line(stmt.label) + &statement;
             }
-            StatementNode::Goto(line) => {
-                format!("{}goto line{};\n", self.indent(), line)
+            // The IF ending a block is traced in its condition
+            if !matches!(stmt.node, StatementNode::If { .. }) {
+                statement = self.trace_line(stmt.label) + &statement;
             }
-            StatementNode::Input(var) => {
-                format!("{}printf(\"? \"); scanf(\"%lf\", &{});\n", self.indent(), var)
+            if !statement.is_empty() {
+                code.push_str(&self.line_directive(stmt));
+                code.push_str(&Self::mark_line(statement, stmt.label));
             }
-            StatementNode::Rem => "".to_string(),
-            StatementNode::End => format!("{}return 0;\n", self.indent()),
         }
+        self.generate_label(cfg, structure, block, code.is_empty()) + &code
     }
 
-    fn generate_statements_internal(&mut self, stmt: &Statement) -> String {
+    fn generate_jump(&self, cfg: &Cfg, target: Target) -> String {
+        match target {
+            Target::Exit => "return 0;".to_string(),
+            Target::Block(block) => format!("goto {};", Self::label_name(cfg, block)),
+            Target::Missing(from) => format!("{};", self.undefined_line(from)),
+        }
+    }
+
+    /// The error for a jump from line `from` to a line that does not exist.
+    fn undefined_line(&self, from: Option<i64>) -> String {
+        match from {
+            Some(line) => format!("rt_line = {}, rt_error(\"Undefined line number\", 1)", line),
+            None => "rt_error(\"Undefined line number\", 1)".to_string(),
+        }
+    }
+
+    fn generate_body(&mut self, cfg: &Cfg, structure: &Structure, body: &[Structured]) -> String {
+        self.indent_level += 1;
+        let result = self.generate_structured(cfg, structure, body);
+        self.indent_level -= 1;
+        result
+    }
+
+    fn generate_structured(&mut self, cfg: &Cfg, structure: &Structure, items: &[Structured]) -> String {
         let mut result = String::new();
-        if let Some(label) = stmt.label {
-            result.push_str(&format!("line{}:\n", label));
+        for item in items {
+            match item {
+                Structured::Block(block) => result.push_str(&self.generate_block(cfg, structure, *block)),
+                Structured::For { header, next, body } => {
+                    let stmt = cfg.blocks[*header].steps[0].statement();
+                    let StatementNode::For { var, start, end, step, .. } = &stmt.node else {
+                        continue;
+                    };
+                    let start_string = self.generate_value_for(var, start);
+                    // The bound is compared, not stored, so a fraction stays
+                    let end_string =
+                        if self.types.is_integer(var) { self.generate_typed(end).0 } else { self.generate_expr(end) };
+                    let step_string = match step {
+                        Some(step) => self.generate_value_for(var, step),
+                        None if self.types.is_integer(var) => "1".to_string(),
+                        None => "1.0".to_string(),
+                    };
+                    result.push_str(&self.generate_label(cfg, structure, *header, false));
+                    result.push_str(&self.line_directive(stmt));
+                    result.push_str(&self.trace_line(stmt.label));
+                    result.push_str(&self.track_line(stmt.label));
+                    let increment = if self.types.is_integer(var) || self.options.numbers == Numbers::Double {
+                        format!("{} += {}", var, step_string)
+                    } else {
+                        format!("{} = {}", var, self.round(format!("{} + {}", var, step_string)))
+                    };
+                    // The NEXT line runs before each step
+                    let increment = match cfg.blocks[*next].steps[0].label() {
+                        Some(line) if self.traces => format!("rt_trace({}), {}", line, increment),
+                        _ => increment,
+                    };
+                    let header_line = format!(
+                        "{}for ({} = {}; {} <= {}; {}) {{\n",
+                        self.indent(),
+                        var,
+                        start_string,
+                        var,
+                        end_string,
+                        increment
+                    );
+                    result.push_str(&Self::mark_line(header_line, stmt.label));
+                    result.push_str(&self.generate_body(cfg, structure, body));
+                    // Jumping to the NEXT line continues the loop
+                    result.push_str(&self.generate_label(cfg, structure, *next, true));
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::If { cond, negated, then_part, else_part } => {
+                    let stmt = Self::condition_statement(cfg, *cond);
+                    let condition = self.generate_condition(cfg, *cond, *negated);
+                    result.push_str(&self.line_directive(stmt));
+                    match (then_part.as_slice(), else_part.is_empty()) {
+                        // A lone jump goes on the same line
+                        ([jump], true) if jump.is_jump() => {
+                            let jump = self.generate_structured(cfg, structure, then_part);
+                            let line = format!("{}if ({}) {}\n", self.indent(), condition, jump.trim());
+                            result.push_str(&Self::mark_line(line, stmt.label));
+                        }
+                        _ => {
+                            let line = format!("{}if ({}) {{\n", self.indent(), condition);
+                            result.push_str(&Self::mark_line(line, stmt.label));
+                            result.push_str(&self.generate_body(cfg, structure, then_part));
+                            if !else_part.is_empty() {
+                                result.push_str(&format!("{}}} else {{\n", self.indent()));
+                                result.push_str(&self.generate_body(cfg, structure, else_part));
+                            }
+                            result.push_str(&format!("{}}}\n", self.indent()));
+                        }
+                    }
+                }
+                Structured::Loop { body, .. } => {
+                    result.push_str(&format!("{}while (1) {{\n", self.indent()));
+                    result.push_str(&self.generate_body(cfg, structure, body));
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::While { cond, negated, body } => {
+                    let stmt = Self::condition_statement(cfg, *cond);
+                    result.push_str(&self.generate_label(cfg, structure, *cond, false));
+                    result.push_str(&self.line_directive(stmt));
+                    let condition = self.generate_condition(cfg, *cond, *negated);
+                    let line = format!("{}while ({}) {{\n", self.indent(), condition);
+                    result.push_str(&Self::mark_line(line, stmt.label));
+                    result.push_str(&self.generate_body(cfg, structure, body));
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::DoWhile { body, cond, negated } => {
+                    let stmt = Self::condition_statement(cfg, *cond);
+                    result.push_str(&format!("{}do {{\n", self.indent()));
+                    result.push_str(&self.generate_body(cfg, structure, body));
+                    result.push_str(&self.line_directive(stmt));
+                    let condition = self.generate_condition(cfg, *cond, *negated);
+                    let line = format!("{}}} while ({});\n", self.indent(), condition);
+                    result.push_str(&Self::mark_line(line, stmt.label));
+                }
+                Structured::Break => result.push_str(&format!("{}break;\n", self.indent())),
+                Structured::Continue => result.push_str(&format!("{}continue;\n", self.indent())),
+                Structured::Goto(target) => {
+                    result.push_str(&format!("{}{}\n", self.indent(), self.generate_jump(cfg, *target)));
+                }
+            }
         }
-        result.push_str(&self.generate_statement_node(&stmt.node));
+        result
+    }
+
+    /// The jump table RETURN goes through to get back to its GOSUB.
+    fn generate_return_dispatch(&self) -> String {
+        let mut result = String::from("gosub_return:\n");
+        result.push_str("    if (gosub_sp == 0) {\n");
+        result.push_str("        rt_error(\"RETURN without GOSUB\", 1);\n");
+        result.push_str("    }\n");
+        result.push_str("    switch (gosub_stack[--gosub_sp]) {\n");
+        for index in 0..self.gosub_count {
+            result.push_str(&format!("    case {}: goto return{};\n", index, index));
+        }
+        result.push_str("    }\n");
+        result.push_str("    return 0;\n");
         result
     }
 
     pub fn generate(&mut self, statements: &[Statement]) -> String {
         self.collect_variables(statements);
+        // A variable nothing assigns is still read, as 0
+        let mut reads = Vec::new();
+        dce::collect_reads(statements, &mut reads);
+        self.variables.extend(reads);
+        self.types = Types::infer(statements, self.options.numbers);
+        self.traces = traces(statements, &self.options);
 
-        let mut result = String::new();
-        result.push_str("#include <stdio.h>\n");
-        result.push_str("#include <stdlib.h>\n");
-        result.push_str("#include <math.h>\n");
-        result.push_str("#include <time.h>\n\n");
+        let cfg = Cfg::new(statements);
+        self.lines = cfg.blocks.iter().flat_map(|block| &block.steps).filter_map(|step| step.label()).collect();
+        let structure = structure::structure(&cfg);
+        let body = self.generate_structured(&cfg, &structure, &structure.body);
+
+        let mut result = self.generated_line_directive("");
+        match self.options.runtime {
+            Runtime::Inline => result.push_str(&runtime::inline_source()),
+            Runtime::External => result.push_str(&format!("#include \"{}\"\n", runtime::HEADER_NAME)),
+        }
+        result.push('\n');
         result.push_str("int main() {\n");
-        result.push_str("    srand(time(NULL));\n");
+        let continue_after_errors = self.options.dialect.continues_after_arithmetic_error();
+        result.push_str(&format!("    rt_init({});\n", continue_after_errors as i32));
+        if let Some(seed) = self.options.seed {
+            result.push_str(&format!("    rt_fix_seed({});\n", seed));
+        }
+        if self.options.trace {
+            result.push_str("    rt_tracing = 1;\n");
+        }
 
+        // Variables start out as 0, as in BASIC
+        let (integers, doubles): (Vec<&String>, Vec<&String>) =
+            self.variables.iter().partition(|var| self.types.is_integer(var));
+        if !integers.is_empty() {
+            let declarations: Vec<String> = integers.iter().map(|var| format!("{} = 0", var)).collect();
+            result.push_str(&format!("    int32_t {};\n", declarations.join(", ")));
+        }
+        if !doubles.is_empty() {
+            let declarations: Vec<String> = doubles.iter().map(|var| format!("{} = 0.0", var)).collect();
+            result.push_str(&format!("    double {};\n", declarations.join(", ")));
+        }
         if !self.variables.is_empty() {
-            result.push_str("    double ");
-            let vars: Vec<String> = self.variables.iter().cloned().collect();
-            result.push_str(&vars.join(", "));
-            result.push_str(";\n\n");
+            result.push('\n');
         }
-
-        for stmt in statements {
-            result.push_str(&self.generate_statements_internal(stmt));
+        if self.gosub_count > 0 || self.uses_return {
+            result.push_str(&format!("    int gosub_stack[{}];\n", GOSUB_STACK_SIZE));
+            result.push_str("    int gosub_sp = 0;\n\n");
         }
 
+        result.push_str(&body);
+
+        result.push_str(&self.generated_line_directive(&result));
         result.push_str("\n    return 0;\n");
+        if self.uses_return {
+            result.push_str(&self.generate_return_dispatch());
+        }
         result.push_str("}\n");
 
         result
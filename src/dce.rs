@@ -0,0 +1,200 @@
+//! Dead code elimination.
+//!
+//! Before code generation the program loses:
+//!
+//! - statements no path from the start reaches,
+//! - assignments to variables that are never read, unless the value calls
+//!   a function with side effects such as RND (or, when arithmetic reports
+//!   errors at runtime, could raise one),
+//! - blank lines and empty REMs that no GOTO, GOSUB or THEN refers to,
+//!   unless the program traces lines, when they print their numbers.
+//!
+//! An assignment dropped from a line something jumps to leaves a blank
+//! line behind so the jump still has somewhere to go.
+
+use crate::cfg::Cfg;
+use crate::lint::expression_reads;
+use crate::parser::{Expression, PrintItem, Statement, StatementNode};
+use std::collections::BTreeSet;
+
+/// Remove the dead code from a program. With `keep_lines`, blank lines
+/// stay.
+pub fn eliminate(statements: &mut Vec<Statement>, raises_errors: bool, keep_lines: bool) {
+    remove_unreachable(statements);
+    while remove_dead_stores(statements, raises_errors) {}
+    if !keep_lines {
+        let targets = jump_targets(statements);
+        remove_blank_lines(statements, &targets);
+    }
+}
+
+/// Whether each statement stays, following the nesting of FOR bodies.
+struct Keep {
+    keep: bool,
+    body: Vec<Keep>,
+}
+
+fn remove_unreachable(statements: &mut Vec<Statement>) {
+    let keep = {
+        let cfg = Cfg::new(statements);
+        let reachable = cfg.reachable();
+        // A FOR is live if its header or NEXT runs; both count as the FOR
+        let live: BTreeSet<*const Statement> = cfg
+            .statement_blocks()
+            .filter(|(index, _)| reachable[*index])
+            .flat_map(|(_, block)| block.steps.iter().map(|step| step.statement() as *const Statement))
+            .collect();
+        keep_live(statements, &live)
+    };
+    apply(statements, keep);
+}
+
+fn keep_live(statements: &[Statement], live: &BTreeSet<*const Statement>) -> Vec<Keep> {
+    statements
+        .iter()
+        .map(|stmt| {
+            let body = match &stmt.node {
+                StatementNode::For { body, .. } => keep_live(body, live),
+                _ => Vec::new(),
+            };
+            // A loop jumped into from outside keeps its header
+            let keep = live.contains(&(stmt as *const Statement)) || body.iter().any(|k| k.keep);
+            Keep { keep, body }
+        })
+        .collect()
+}
+
+fn apply(statements: &mut Vec<Statement>, keep: Vec<Keep>) {
+    let mut keep = keep.into_iter();
+    statements.retain_mut(|stmt| {
+        let Keep { keep, body } = keep.next().expect("one entry per statement");
+        if let StatementNode::For { body: statements, .. } = &mut stmt.node {
+            apply(statements, body);
+        }
+        keep
+    });
+}
+
+/// Whether evaluating `expr` does nothing but produce its value. With
+/// `raises_errors`, arithmetic that may raise a runtime error does more.
+fn is_pure(expr: &Expression, raises_errors: bool) -> bool {
+    match expr {
+        Expression::Number(_) | Expression::Float(..) | Expression::Variable(_) => true,
+        Expression::BinaryOp { left, right, .. } => !raises_errors && is_pure(left, raises_errors) && is_pure(right, raises_errors),
+        Expression::FunctionCall { name, args } => {
+            let pure = match name.to_uppercase().as_str() {
+                "INT" | "ABS" => true,
+                "SQR" | "EXP" => !raises_errors,
+                _ => false,
+            };
+            pure && args.iter().all(|arg| is_pure(arg, raises_errors))
+        }
+    }
+}
+
+/// Every variable `statements` read, in order.
+pub(crate) fn collect_reads(statements: &[Statement], reads: &mut Vec<String>) {
+    for stmt in statements {
+        node_reads(&stmt.node, reads);
+    }
+}
+
+fn node_reads(node: &StatementNode, reads: &mut Vec<String>) {
+    match node {
+        StatementNode::Let { value, .. } | StatementNode::Randomize(Some(value)) => expression_reads(value, reads),
+        StatementNode::Print { items, .. } => {
+            for item in items {
+                if let PrintItem::Expr(expr) = item {
+                    expression_reads(expr, reads);
+                }
+            }
+        }
+        StatementNode::If { left, right, then_part, .. } => {
+            expression_reads(left, reads);
+            expression_reads(right, reads);
+            node_reads(&then_part.node, reads);
+        }
+        StatementNode::For { var, start, end, step, body, .. } => {
+            // NEXT reads the loop variable
+            reads.push(var.clone());
+            expression_reads(start, reads);
+            expression_reads(end, reads);
+            if let Some(step) = step {
+                expression_reads(step, reads);
+            }
+            collect_reads(body, reads);
+        }
+        _ => {}
+    }
+}
+
+/// Blank out assignments to variables nothing reads. Returns whether any
+/// were found.
+fn remove_dead_stores(statements: &mut [Statement], raises_errors: bool) -> bool {
+    let mut reads = Vec::new();
+    collect_reads(statements, &mut reads);
+    let read: BTreeSet<String> = reads.into_iter().collect();
+    blank_stores(statements, &read, raises_errors)
+}
+
+fn blank_stores(statements: &mut [Statement], read: &BTreeSet<String>, raises_errors: bool) -> bool {
+    let mut changed = false;
+    for stmt in statements {
+        changed |= blank_store(&mut stmt.node, read, raises_errors);
+    }
+    changed
+}
+
+fn blank_store(node: &mut StatementNode, read: &BTreeSet<String>, raises_errors: bool) -> bool {
+    match node {
+        StatementNode::Let { var, value } if !read.contains(var) && is_pure(value, raises_errors) => {
+            *node = StatementNode::Empty;
+            true
+        }
+        StatementNode::If { left, right, then_part, .. } => {
+            let changed = blank_store(&mut then_part.node, read, raises_errors);
+            // An IF left with nothing to do goes too
+            if matches!(then_part.node, StatementNode::Empty) && is_pure(left, raises_errors) && is_pure(right, raises_errors) {
+                *node = StatementNode::Empty;
+            }
+            changed
+        }
+        StatementNode::For { body, .. } => blank_stores(body, read, raises_errors),
+        _ => false,
+    }
+}
+
+/// The lines some GOTO, GOSUB or THEN refers to.
+fn jump_targets(statements: &[Statement]) -> BTreeSet<i64> {
+    let mut targets = BTreeSet::new();
+    for stmt in statements {
+        node_targets(&stmt.node, &mut targets);
+    }
+    targets
+}
+
+fn node_targets(node: &StatementNode, targets: &mut BTreeSet<i64>) {
+    match node {
+        StatementNode::Goto(line) | StatementNode::Gosub(line) => {
+            targets.insert(*line);
+        }
+        StatementNode::If { then_part, .. } => node_targets(&then_part.node, targets),
+        StatementNode::For { body, .. } => targets.extend(jump_targets(body)),
+        _ => {}
+    }
+}
+
+fn remove_blank_lines(statements: &mut Vec<Statement>, targets: &BTreeSet<i64>) {
+    statements.retain_mut(|stmt| {
+        let referenced = stmt.label.is_some_and(|label| targets.contains(&label));
+        match &mut stmt.node {
+            StatementNode::Empty => referenced,
+            StatementNode::Rem(comment) if comment.trim().is_empty() => referenced,
+            StatementNode::For { body, .. } => {
+                remove_blank_lines(body, targets);
+                true
+            }
+            _ => true,
+        }
+    });
+}
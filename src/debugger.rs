@@ -0,0 +1,456 @@
+//! An interactive debugger for BASIC programs, `compiler debug prog.bas`.
+//!
+//! The program runs in the [interpreter](crate::interp) and stops at
+//! breakpoints set on line numbers, or after each statement when stepping.
+//! Commands are read from any reader and answers written to any writer,
+//! normally stdin and stdout; the program's own INPUT reads from the same
+//! reader, so a session can be scripted.
+//!
+//! `renum` renumbers the program as `RENUM` does and starts it again, with
+//! its breakpoints moved to the new line numbers.
+
+use crate::builtins;
+use crate::codegen::CodegenOptions;
+use crate::diagnostic::Diagnostic;
+use crate::interp::{Interpreter, Io, RuntimeError};
+use crate::lexer::Lexer;
+use crate::parser::{Parser, Statement, StatementNode};
+use crate::renum::{self, RenumOptions};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+Commands:
+  run, r              Start the program again from its first line
+  continue, c         Run until a breakpoint or the end
+  step, s             Run one statement, stopping inside a GOSUB
+  next, n             Run one statement, running a GOSUB through
+  break, b <line>     Stop before line <line>; without a line, list them
+  delete, d [<line>]  Remove the breakpoint on <line>, or all of them
+  print, p, ? <items> Print expressions, as PRINT does
+  let <var> = <expr>  Set a variable
+  vars                Show every variable
+  stack, bt           Show the GOSUB and FOR stacks
+  list, l             Show the lines around the next one to run
+  tron, troff         Turn line number tracing on or off
+  renum [n][,o][,i]   Renumber as RENUM does and start again
+  help, h             Show this message
+  quit, q             Leave the debugger";
+
+/// How many lines `list` shows either side of the next one.
+const LIST_CONTEXT: usize = 3;
+
+/// Commands and the program share one reader and one writer.
+struct Console<R, W> {
+    input: R,
+    output: W,
+    /// Whether the last thing written ended a line, so messages from the
+    /// debugger start on one of their own.
+    at_line_start: bool,
+}
+
+impl<R: BufRead, W: Write> Console<R, W> {
+    fn read_line(&mut self) -> Option<String> {
+        let _ = self.output.flush();
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                // What was typed ended with a newline the terminal showed
+                self.at_line_start = true;
+                Some(line)
+            }
+        }
+    }
+
+    /// Write a message from the debugger on a line of its own.
+    fn message(&mut self, text: &str) {
+        if !self.at_line_start {
+            let _ = writeln!(self.output);
+        }
+        let _ = writeln!(self.output, "{}", text);
+        self.at_line_start = true;
+    }
+}
+
+impl<R: BufRead, W: Write> Io for Console<R, W> {
+    fn print(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let _ = write!(self.output, "{}", text);
+        self.at_line_start = text.ends_with('\n');
+    }
+
+    fn input(&mut self) -> Option<String> {
+        self.read_line()
+    }
+
+    fn warn(&mut self, error: &RuntimeError) {
+        self.message(&error.to_string());
+    }
+}
+
+/// What the prompt does after a command.
+enum Then {
+    Prompt,
+    Renumber(RenumOptions),
+    Quit,
+}
+
+/// Why a run stopped.
+enum Stop {
+    Breakpoint,
+    Step,
+    Ended,
+    Failed(RuntimeError),
+}
+
+struct Debugger<'a, R, W> {
+    statements: &'a [Statement],
+    options: CodegenOptions,
+    source_lines: Vec<&'a str>,
+    /// Where each numbered line is in the source, by its number.
+    line_numbers: HashMap<i64, usize>,
+    interpreter: Interpreter<'a>,
+    breakpoints: BTreeSet<i64>,
+    console: Console<R, W>,
+}
+
+impl<'a, R: BufRead, W: Write> Debugger<'a, R, W> {
+    fn new(
+        source: &'a str,
+        statements: &'a [Statement],
+        options: CodegenOptions,
+        breakpoints: BTreeSet<i64>,
+        console: Console<R, W>,
+    ) -> Self {
+        let source_lines: Vec<&str> = source.lines().collect();
+        let mut line_numbers = HashMap::new();
+        for (index, text) in source_lines.iter().enumerate() {
+            let digits: String = text.trim_start().chars().take_while(char::is_ascii_digit).collect();
+            if let Ok(number) = digits.parse() {
+                line_numbers.entry(number).or_insert(index);
+            }
+        }
+        Debugger {
+            statements,
+            options: options.clone(),
+            source_lines,
+            line_numbers,
+            interpreter: Interpreter::new(statements, options),
+            breakpoints,
+            console,
+        }
+    }
+
+    /// The index into the source of the next line to run.
+    fn source_line(&self) -> Option<usize> {
+        let step = self.interpreter.current()?;
+        match step.label() {
+            Some(line) => self.line_numbers.get(&line).copied(),
+            None => step.statement().span.line.checked_sub(1),
+        }
+    }
+
+    fn show_location(&mut self) {
+        if let Some(index) = self.source_line() {
+            let text = format!("  {}", self.source_lines[index].trim());
+            self.console.message(&text);
+        }
+    }
+
+    fn list(&mut self) {
+        let Some(current) = self.source_line() else {
+            return self.console.message("The program is not running");
+        };
+        let first = current.saturating_sub(LIST_CONTEXT);
+        let last = (current + LIST_CONTEXT).min(self.source_lines.len() - 1);
+        for index in first..=last {
+            let marker = if index == current { "=>" } else { "  " };
+            let text = format!("{} {}", marker, self.source_lines[index].trim_end());
+            self.console.message(&text);
+        }
+    }
+
+    /// Run one step, or say why not.
+    fn step_once(&mut self) -> Option<Stop> {
+        if self.interpreter.finished() {
+            return Some(Stop::Ended);
+        }
+        if let Err(error) = self.interpreter.step(&mut self.console) {
+            return Some(Stop::Failed(error));
+        }
+        self.interpreter.finished().then_some(Stop::Ended)
+    }
+
+    /// Whether the next step starts a line with a breakpoint.
+    fn at_breakpoint(&self) -> bool {
+        self.interpreter
+            .current()
+            .and_then(|step| step.label())
+            .is_some_and(|line| self.breakpoints.contains(&line))
+    }
+
+    /// Run until a breakpoint, or while `keep_going` says to.
+    fn run_until(&mut self, keep_going: impl Fn(&Interpreter) -> bool) -> Stop {
+        // The step stopped at runs even if it has a breakpoint
+        loop {
+            if let Some(stop) = self.step_once() {
+                return stop;
+            }
+            if self.at_breakpoint() {
+                return Stop::Breakpoint;
+            }
+            if !keep_going(&self.interpreter) {
+                return Stop::Step;
+            }
+        }
+    }
+
+    fn report(&mut self, stop: Stop) {
+        match stop {
+            Stop::Breakpoint => {
+                let line = self.interpreter.current_line().unwrap_or_default();
+                self.console.message(&format!("Breakpoint at line {}", line));
+                self.show_location();
+            }
+            Stop::Step => self.show_location(),
+            Stop::Ended => self.console.message("The program has ended; run starts it again"),
+            Stop::Failed(error) => self.console.message(&error.to_string()),
+        }
+    }
+
+    fn restart(&mut self) {
+        let trace = self.interpreter.trace;
+        self.interpreter = Interpreter::new(self.statements, self.options.clone());
+        self.interpreter.trace = trace;
+    }
+
+    /// Run a statement typed at the prompt, `text` being the whole line.
+    fn execute(&mut self, text: &str, allowed: fn(&StatementNode) -> bool) {
+        let statement = match parse(text) {
+            Ok(statements) => statements.into_iter().next(),
+            Err(diagnostics) => return self.errors(&diagnostics),
+        };
+        let Some(statement) = statement.filter(|stmt| allowed(&stmt.node)) else {
+            return self.console.message(&format!("error: cannot run '{}' here", text));
+        };
+        if let Err(error) = self.interpreter.execute(&statement.node, &mut self.console) {
+            self.console.message(&format!("error: {}", error.message));
+        }
+    }
+
+    fn errors(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics.iter().filter(|d| d.is_error()) {
+            self.console.message(&format!("error: {}", diagnostic.message));
+        }
+    }
+
+    fn show_variables(&mut self) {
+        if self.interpreter.variables().is_empty() {
+            return self.console.message("No variables are set");
+        }
+        let lines: Vec<String> = self
+            .interpreter
+            .variables()
+            .iter()
+            .map(|(name, value)| format!("  {} = {}", name, self.options.numbers.format(*value).trim()))
+            .collect();
+        for line in lines {
+            self.console.message(&line);
+        }
+    }
+
+    fn show_stacks(&mut self) {
+        let mut lines = Vec::new();
+        lines.push("GOSUB stack:".to_string());
+        if self.interpreter.gosub_stack().is_empty() {
+            lines.push("  (empty)".to_string());
+        }
+        for frame in self.interpreter.gosub_stack().iter().rev() {
+            let line = frame.line.map_or("an unnumbered line".to_string(), |line| line.to_string());
+            lines.push(format!("  GOSUB at {}", line));
+        }
+        lines.push("FOR stack:".to_string());
+        if self.interpreter.for_stack().is_empty() {
+            lines.push("  (empty)".to_string());
+        }
+        for frame in self.interpreter.for_stack().iter().rev() {
+            let StatementNode::For { var, .. } = &frame.statement.node else {
+                continue;
+            };
+            let value = self.interpreter.variables().get(var).copied().unwrap_or(0.0);
+            let line = frame.statement.label.map_or(String::new(), |line| format!(" at {}", line));
+            let value = self.options.numbers.format(value);
+            lines.push(format!("  FOR {}{}, {} = {}", var, line, var, value.trim()));
+        }
+        for line in lines {
+            self.console.message(&line);
+        }
+    }
+
+    /// Carry out one command.
+    fn command(&mut self, line: &str) -> Then {
+        let line = line.trim();
+        let (word, rest) = match line.find(char::is_whitespace) {
+            Some(end) => (&line[..end], line[end..].trim()),
+            None => (line, ""),
+        };
+        // `?X` needs no space, as in GW-BASIC
+        let (word, rest) = match word.strip_prefix('?') {
+            Some(items) if !items.is_empty() => ("?", line[1..].trim()),
+            _ => (word, rest),
+        };
+        match word.to_lowercase().as_str() {
+            "" => {}
+            "run" | "r" => {
+                self.restart();
+                let stop = if self.at_breakpoint() { Stop::Breakpoint } else { self.run_until(|_| true) };
+                self.report(stop);
+            }
+            "continue" | "c" => {
+                let stop = self.run_until(|_| true);
+                self.report(stop);
+            }
+            "step" | "s" => {
+                let stop = self.step_once().unwrap_or(Stop::Step);
+                self.report(stop);
+            }
+            "next" | "n" => {
+                let depth = self.interpreter.gosub_stack().len();
+                let stop = self.run_until(|interpreter| interpreter.gosub_stack().len() > depth);
+                self.report(stop);
+            }
+            "break" | "b" if rest.is_empty() => {
+                let list: Vec<String> = self.breakpoints.iter().map(i64::to_string).collect();
+                let message = if list.is_empty() { "No breakpoints".to_string() } else { format!("Breakpoints at {}", list.join(", ")) };
+                self.console.message(&message);
+            }
+            "break" | "b" => match rest.parse() {
+                Ok(line) if self.interpreter.has_line(line) => {
+                    self.breakpoints.insert(line);
+                    self.console.message(&format!("Breakpoint at line {}", line));
+                }
+                Ok(line) => self.console.message(&format!("error: there is no line {}", line)),
+                Err(_) => self.console.message(&format!("error: '{}' is not a line number", rest)),
+            },
+            "delete" | "d" if rest.is_empty() => self.breakpoints.clear(),
+            "delete" | "d" => match rest.parse() {
+                Ok(line) if self.breakpoints.remove(&line) => {}
+                _ => self.console.message(&format!("error: no breakpoint at '{}'", rest)),
+            },
+            "print" | "p" | "?" => self.execute(&format!("PRINT {}", rest), |node| matches!(node, StatementNode::Print { .. })),
+            "let" => self.execute(line, |node| matches!(node, StatementNode::Let { .. })),
+            "vars" => self.show_variables(),
+            "stack" | "bt" => self.show_stacks(),
+            "list" | "l" => self.list(),
+            "tron" => self.interpreter.trace = true,
+            "troff" => self.interpreter.trace = false,
+            "renum" => match RenumOptions::parse(rest) {
+                Ok(options) => return Then::Renumber(options),
+                Err(message) => self.console.message(&format!("error: {}", message)),
+            },
+            "help" | "h" => self.console.message(HELP),
+            "quit" | "q" => return Then::Quit,
+            _ => self.console.message(&format!("error: unknown command '{}'; help lists them", word)),
+        }
+        Then::Prompt
+    }
+
+    /// Read and carry out commands until one leaves the prompt, which the
+    /// end of the input does as `quit` would.
+    fn prompt(&mut self) -> Then {
+        loop {
+            if !self.console.at_line_start {
+                let _ = writeln!(self.console.output);
+            }
+            let _ = write!(self.console.output, "(debug) ");
+            self.console.at_line_start = false;
+            let Some(line) = self.console.read_line() else {
+                return Then::Quit;
+            };
+            match self.command(&line) {
+                Then::Prompt => {}
+                then => return then,
+            }
+        }
+    }
+
+    /// `source` renumbered and parsed again, with the breakpoints moved to
+    /// the new line numbers, or `None` after saying why it cannot be.
+    fn renumber(&mut self, source: &str, options: &RenumOptions) -> Option<(String, Vec<Statement>)> {
+        let renumbered = match renum::renumber(source, options) {
+            Ok(renumbered) => renumbered,
+            Err(message) => {
+                self.console.message(&format!("error: {}", message));
+                return None;
+            }
+        };
+        for warning in &renumbered.warnings {
+            self.console.message(warning);
+        }
+        let statements = match parse(&renumbered.source) {
+            Ok(statements) => statements,
+            Err(diagnostics) => {
+                self.errors(&diagnostics);
+                return None;
+            }
+        };
+        self.breakpoints = self.breakpoints.iter().map(|line| renumbered.mapping.get(line).copied().unwrap_or(*line)).collect();
+        Some((renumbered.source, statements))
+    }
+}
+
+/// Parse text typed at the prompt or renumbered, as the whole program is.
+fn parse(text: &str) -> Result<Vec<Statement>, Vec<Diagnostic>> {
+    Lexer::new(text)
+        .tokenize_spanned()
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .and_then(|statements| {
+            let errors = builtins::check(&statements);
+            if errors.is_empty() { Ok(statements) } else { Err(errors) }
+        })
+}
+
+/// Debug `statements`, parsed from `source`, until the commands run out or
+/// one is `quit`.
+pub fn debug(
+    source: &str,
+    statements: &[Statement],
+    options: CodegenOptions,
+    input: impl BufRead,
+    output: impl Write,
+) -> io::Result<()> {
+    let mut console = Console { input, output, at_line_start: true };
+    let mut breakpoints = BTreeSet::new();
+    // The program once `renum` has renumbered it
+    let mut renumbered: Option<(String, Vec<Statement>)> = None;
+    console.message("Stopped at the start; help lists the commands");
+    loop {
+        let (source, statements) = match &renumbered {
+            Some((source, statements)) => (source.as_str(), statements.as_slice()),
+            None => (source, statements),
+        };
+        let mut debugger = Debugger::new(source, statements, options.clone(), breakpoints, console);
+        debugger.show_location();
+        let program = loop {
+            match debugger.prompt() {
+                Then::Renumber(renum) => {
+                    if let Some(program) = debugger.renumber(source, &renum) {
+                        break Some(program);
+                    }
+                }
+                _ => break None,
+            }
+        };
+        (breakpoints, console) = (debugger.breakpoints, debugger.console);
+        match program {
+            Some(program) => {
+                renumbered = Some(program);
+                console.message("Renumbered; the program starts again");
+            }
+            None => return console.output.flush(),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::lexer::Span;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning tied to a place in the source.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Stable name of a warning, used by `-W` flags (e.g. `implicit-let`).
+    /// Errors have no code since they cannot be turned off.
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: Some(code),
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Render as `file:line:col: severity: message [-Wcode]`, optionally with
+    /// ANSI colours.
+    pub fn render(&self, file: &str, color: bool) -> String {
+        let (label, paint) = match self.severity {
+            Severity::Error => ("error", "\x1b[1;31m"),
+            Severity::Warning => ("warning", "\x1b[1;33m"),
+        };
+        let label = if color {
+            format!("{}{}\x1b[0m", paint, label)
+        } else {
+            label.to_string()
+        };
+        let mut result = format!("{}:{}:{}: {}: {}", file, self.span.line, self.span.column, label, self.message);
+        if let Some(code) = self.code {
+            result.push_str(&format!(" [-W{}]", code));
+        }
+        result
+    }
+}
+
+/// The code of every warning, as `-W` names them.
+pub const WARNING_CODES: &[&str] = &[
+    "division-by-zero",
+    "duplicate-line",
+    "end-not-last",
+    "for-variable-modified",
+    "line-order",
+    "missing-line-number",
+    "missing-return",
+    "uninitialized",
+    "unreachable-code",
+    "unused-variable",
+    "variable-name",
+];
+
+/// Which warnings are reported and whether they fail the build, as set by
+/// `-W` flags.
+#[derive(Debug, Clone, Default)]
+pub struct WarningConfig {
+    pub warnings_as_errors: bool,
+    disabled: BTreeSet<String>,
+}
+
+impl WarningConfig {
+    /// Apply one `-W` argument: `error`, `no-error`, `no-<code>` or `<code>`.
+    /// A code that names no warning is an error, so a misspelling is not
+    /// silently ignored.
+    pub fn set(&mut self, flag: &str) -> Result<(), String> {
+        match flag {
+            "error" => self.warnings_as_errors = true,
+            "no-error" => self.warnings_as_errors = false,
+            _ => {
+                let code = flag.strip_prefix("no-").unwrap_or(flag);
+                if !WARNING_CODES.contains(&code) {
+                    return Err(format!("unknown warning '{}'", code));
+                }
+                if code == flag {
+                    self.disabled.remove(code);
+                } else {
+                    self.disabled.insert(code.to_string());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_enabled(&self, code: &str) -> bool {
+        !self.disabled.contains(code)
+    }
+
+    /// Drop disabled warnings and promote the rest to errors under `-W error`.
+    pub fn apply(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .filter(|d| d.code.is_none_or(|code| self.is_enabled(code)))
+            .map(|mut d| {
+                if self.warnings_as_errors {
+                    d.severity = Severity::Error;
+                }
+                d
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,84 @@
+use crate::diagnostic::Diagnostic;
+use crate::parser::{Statement, StatementNode};
+
+/// The BASIC dialect a program is written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dialect {
+    /// Microsoft GW-BASIC, the default.
+    #[default]
+    GwBasic,
+    /// ANSI/ECMA-55 Minimal BASIC.
+    Ansi,
+}
+
+impl Dialect {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "gwbasic" | "gw-basic" => Some(Dialect::GwBasic),
+            "ansi" | "ecma55" | "ecma-55" => Some(Dialect::Ansi),
+            _ => None,
+        }
+    }
+
+    /// Whether a program goes on after a division by zero or an overflow.
+    /// GW-BASIC prints the error and carries on with the largest number;
+    /// Minimal BASIC only recommends that, so stop instead.
+    pub fn continues_after_arithmetic_error(&self) -> bool {
+        *self == Dialect::GwBasic
+    }
+
+    /// Warn about constructs the dialect does not allow.
+    pub fn check(&self, statements: &[Statement]) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        if *self == Dialect::Ansi {
+            Self::check_ansi(statements, &mut diagnostics);
+            if let Some(last) = statements.last()
+                && !matches!(last.node, StatementNode::End)
+            {
+                diagnostics.push(Diagnostic::warning(
+                    "end-not-last",
+                    "Minimal BASIC requires END as the last line",
+                    last.span,
+                ));
+            }
+        }
+        diagnostics
+    }
+
+    fn check_ansi(statements: &[Statement], diagnostics: &mut Vec<Diagnostic>) {
+        for stmt in statements {
+            if stmt.label.is_none() {
+                diagnostics.push(Diagnostic::warning(
+                    "missing-line-number",
+                    "Minimal BASIC requires a line number on every line",
+                    stmt.span,
+                ));
+            }
+
+            let assigned = match &stmt.node {
+                StatementNode::Let { var, .. } | StatementNode::Input(var) => Some(var),
+                StatementNode::For { var, body, .. } => {
+                    Self::check_ansi(body, diagnostics);
+                    Some(var)
+                }
+                _ => None,
+            };
+            if let Some(var) = assigned
+                && !Self::is_ansi_variable(var)
+            {
+                diagnostics.push(Diagnostic::warning(
+                    "variable-name",
+                    format!("Minimal BASIC variable names are a letter and an optional digit, not {}", var),
+                    stmt.span,
+                ));
+            }
+        }
+    }
+
+    fn is_ansi_variable(name: &str) -> bool {
+        let mut chars = name.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+            && chars.next().is_none_or(|c| c.is_ascii_digit())
+            && chars.next().is_none()
+    }
+}
@@ -0,0 +1,179 @@
+use crate::runtime::{self, Runtime};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How the C compiler is invoked.
+#[derive(Debug, Clone)]
+pub struct BuildOptions {
+    /// Passed through as `-O<level>`.
+    pub opt_level: String,
+    /// Keep the generated C next to the executable, compiling it there
+    /// instead of in a scratch directory.
+    pub keep_c: bool,
+    /// Pass `-g` to the C compiler.
+    pub debug_info: bool,
+    /// Whether the generated C needs `basic_rt.c` built alongside it.
+    pub runtime: Runtime,
+}
+
+impl Default for BuildOptions {
+    fn default() -> Self {
+        BuildOptions {
+            opt_level: "2".to_string(),
+            keep_c: false,
+            debug_info: false,
+            runtime: Runtime::default(),
+        }
+    }
+}
+
+/// The C compiler to drive, as the program and any arguments to put first:
+/// `$CC` split on whitespace as make does (so `CC="ccache gcc"` works),
+/// falling back to `cc`.
+fn c_compiler() -> Vec<String> {
+    let words: Vec<String> = env::var("CC").unwrap_or_default().split_whitespace().map(str::to_string).collect();
+    if words.is_empty() { vec!["cc".to_string()] } else { words }
+}
+
+/// A fresh scratch directory, removed again when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Result<Self, String> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        let dir = env::temp_dir().join(format!("basic-{}-{}", std::process::id(), nanos));
+        fs::create_dir_all(&dir).map_err(|err| format!("Error creating '{}': {}", dir.display(), err))?;
+        Ok(TempDir(dir))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The BASIC line whose code contains line `c_line` (1-based) of the
+/// generated C, found from the nearest preceding `lineN:` label or
+/// `// N` line marker.
+fn basic_line_for(c_code: &str, c_line: usize) -> Option<i64> {
+    c_code
+        .lines()
+        .take(c_line)
+        .filter_map(|line| {
+            // Either a `lineN:` label or code ending in a `// N` marker
+            let label = line.strip_prefix("line").and_then(|rest| rest.split(':').next()?.parse().ok());
+            let marker = || {
+                if line.trim_start().starts_with("//") {
+                    return None;
+                }
+                line.rsplit_once("  // ")?.1.parse().ok()
+            };
+            label.or_else(marker)
+        })
+        .last()
+}
+
+/// Rewrite the C compiler's `file.c:LINE:COL: kind: message` lines so they
+/// point at BASIC line numbers instead. Other lines, such as notes without
+/// a position and linker errors, are kept as they are.
+fn map_c_diagnostics(stderr: &str, c_file: &Path, c_code: &str, source_name: &str) -> String {
+    let c_path = c_file.to_string_lossy();
+    let mut mapped = String::new();
+    for line in stderr.lines() {
+        match map_c_diagnostic(line, &c_path, c_code, source_name) {
+            Some(line) => mapped.push_str(&line),
+            None => mapped.push_str(line),
+        }
+        mapped.push('\n');
+    }
+    mapped
+}
+
+/// One line of the C compiler's output pointing into the generated C, as
+/// it reads pointing at the BASIC line instead.
+fn map_c_diagnostic(line: &str, c_path: &str, c_code: &str, source_name: &str) -> Option<String> {
+    let rest = line.strip_prefix(c_path)?.strip_prefix(':')?;
+    let mut parts = rest.splitn(3, ':');
+    let (Some(c_line), Some(_column), Some(message)) = (parts.next(), parts.next(), parts.next()) else {
+        return None;
+    };
+    let c_line = c_line.parse::<usize>().ok()?;
+    Some(match basic_line_for(c_code, c_line) {
+        Some(basic_line) => format!("{}: line {}:{} (generated C line {})", source_name, basic_line, message, c_line),
+        None => format!("{}:{} (generated C line {})", source_name, message, c_line),
+    })
+}
+
+/// Write `basic_rt.h` and `basic_rt.c` into `dir`, for C generated with
+/// [`Runtime::External`].
+pub fn write_runtime(dir: &Path) -> Result<(), String> {
+    for (name, text) in [(runtime::HEADER_NAME, runtime::HEADER), (runtime::SOURCE_NAME, runtime::SOURCE)] {
+        let path = dir.join(name);
+        fs::write(&path, text).map_err(|err| format!("Error writing '{}': {}", path.display(), err))?;
+    }
+    Ok(())
+}
+
+/// Write generated C to `c_file` and compile it there into the executable
+/// `exe` with the system C compiler. `c_file` is the file the C's own
+/// `#line` directives name.
+pub fn build_executable(
+    c_code: &str,
+    c_file: &Path,
+    exe: &Path,
+    source_name: &str,
+    options: &BuildOptions,
+) -> Result<(), String> {
+    fs::write(c_file, c_code).map_err(|err| format!("Error writing '{}': {}", c_file.display(), err))?;
+    let dir = c_file.parent().unwrap_or(Path::new("."));
+    if options.runtime == Runtime::External {
+        write_runtime(dir)?;
+    }
+
+    let words = c_compiler();
+    let cc = words.join(" ");
+    let mut command = Command::new(&words[0]);
+    command.args(&words[1..]).arg("-std=c99").arg(format!("-O{}", options.opt_level));
+    if options.debug_info {
+        command.arg("-g");
+    }
+    command.arg(c_file);
+    if options.runtime == Runtime::External {
+        command.arg(dir.join(runtime::SOURCE_NAME));
+    }
+    let output = command
+        .arg("-o")
+        .arg(exe)
+        .arg("-lm")
+        .output()
+        .map_err(|err| format!("Error running '{}': {}", cc, err))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.is_empty() {
+        eprint!("{}", map_c_diagnostics(&stderr, c_file, c_code, source_name));
+    }
+    if !output.status.success() {
+        return Err(format!("'{}' failed to compile the generated C", cc));
+    }
+    Ok(())
+}
+
+/// Compile generated C at `c_file`, normally in a [`TempDir`], run it next
+/// to it with the terminal's stdin/stdout, and return its exit code.
+pub fn run_c(c_code: &str, c_file: &Path, source_name: &str, options: &BuildOptions) -> Result<i32, String> {
+    let exe = c_file.with_extension("");
+    build_executable(c_code, c_file, &exe, source_name, options)?;
+
+    let status = Command::new(&exe)
+        .status()
+        .map_err(|err| format!("Error running '{}': {}", exe.display(), err))?;
+    Ok(status.code().unwrap_or(1))
+}
@@ -0,0 +1,519 @@
+//! The generator shared by the targets written as source in a language
+//! with labelled loops: [Rust](crate::rustgen) and
+//! [JavaScript](crate::jsgen). What differs between them is a [`Syntax`].
+//!
+//! A program whose control flow [`structure`](crate::structure) can
+//! express without labels becomes native `while` and endless loops. Any
+//! other program, such as one that uses GOSUB or jumps into a loop, runs as
+//! a state machine, a `match` or `switch` on `pc` in an endless loop, with
+//! one arm per CFG block.
+//!
+//! Every variable is a double. Runtime checks and MBF arithmetic are only
+//! available in C.
+
+use crate::cfg::{Cfg, EdgeKind, Step, ENTRY, EXIT};
+use crate::codegen::{CodegenOptions, GOSUB_STACK_SIZE};
+use crate::lexer::Token;
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+use crate::structure::{self, Structured, Target};
+use std::collections::BTreeSet;
+use std::marker::PhantomData;
+
+/// How a target language writes what the generator needs.
+pub trait Syntax {
+    /// The runtime every generated program starts with.
+    const RUNTIME: &'static str;
+    /// Names the generated code uses itself, and the language's keywords,
+    /// which BASIC variables are renamed away from.
+    const RESERVED: &'static [&'static str];
+    /// What `floor`, `sqrt`, `exp` and `abs` are called on.
+    const MATH: &'static str;
+    /// The function `x ^ y` calls with `x, y`.
+    const POWER: &'static str;
+    const TIMER: &'static str;
+    const EQUAL: &'static str;
+    const NOT_EQUAL: &'static str;
+    /// What comes before a loop label's name.
+    const LABEL: &'static str;
+    /// An endless loop, before its `{`.
+    const LOOP: &'static str;
+    /// The statement that ends the program early.
+    const EXIT: &'static str;
+    /// The last statement of a program that runs to its end, if it needs one.
+    const FINISH: &'static str;
+    /// The opening line of the function the program becomes.
+    const PROGRAM: &'static str;
+    /// The state machine's `pc`, declared but not yet given a value.
+    const PC: &'static str;
+    /// The head of the state machine's `match` or `switch`.
+    const DISPATCH: &'static str;
+    /// The lines that end the program when `pc` matches no block.
+    const DEFAULT: &'static str;
+    /// The statement ending an arm, if it needs one.
+    const END_CASE: Option<&'static str>;
+
+    /// The label on the state machine's arm for block `index`.
+    fn case(index: usize) -> String;
+    /// The condition of an `if` or `while`, as it follows the keyword.
+    fn test(condition: &str) -> String;
+    /// A call to the runtime's method `name`, as Rust names it.
+    fn method(name: &str, args: &str) -> String;
+    /// A call to a runtime method that may stop the program.
+    fn checked(call: String) -> String;
+    /// A call to a runtime method that may stop the program, or wait for
+    /// input.
+    fn awaited(call: String) -> String;
+    /// A BASIC line number, or none, as the runtime's errors take it.
+    fn line(line: Option<i64>) -> String;
+    /// The statement that stops the program with `error`.
+    fn raise(error: String) -> String;
+    fn declare(var: &str) -> String;
+    /// A constant the runtime uses.
+    fn constant(name: &str, value: usize) -> String;
+
+    /// Lines printing `value`, each starting with `indent`.
+    fn print_number(indent: &str, value: &str) -> String {
+        format!("{}{};\n", indent, Self::method("print_number", value))
+    }
+}
+
+/// A loop being generated, for `break` and `continue` inside it.
+struct Loop {
+    label: usize,
+    /// FOR and do-while loops have code to run before going round again,
+    /// so `continue` leaves a labelled block around the body instead.
+    continue_leaves_body: bool,
+    continued: bool,
+    /// Whether a `break` or `continue` names the loop.
+    named: bool,
+}
+
+pub struct Emitter<S: Syntax> {
+    indent_level: usize,
+    options: CodegenOptions,
+    variables: BTreeSet<String>,
+    loops: Vec<Loop>,
+    loop_count: usize,
+    syntax: PhantomData<S>,
+}
+
+impl<S: Syntax> Emitter<S> {
+    pub fn new(options: CodegenOptions) -> Self {
+        Emitter {
+            indent_level: 1,
+            options,
+            variables: BTreeSet::new(),
+            loops: Vec::new(),
+            loop_count: 0,
+            syntax: PhantomData,
+        }
+    }
+
+    fn indent(&self) -> String {
+        "    ".repeat(self.indent_level)
+    }
+
+    fn variable(name: &str) -> String {
+        if S::RESERVED.contains(&name) { format!("{}_", name) } else { name.to_string() }
+    }
+
+    fn collect_variables(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            self.collect_variables_node(&stmt.node);
+        }
+    }
+
+    fn collect_variables_node(&mut self, node: &StatementNode) {
+        match node {
+            StatementNode::Let { var, .. } | StatementNode::Input(var) => {
+                self.variables.insert(var.clone());
+            }
+            StatementNode::For { var, body, .. } => {
+                self.variables.insert(var.clone());
+                self.collect_variables(body);
+            }
+            StatementNode::If { then_part, .. } => self.collect_variables_node(&then_part.node),
+            _ => {}
+        }
+    }
+
+    fn generate_expr(&self, expr: &Expression) -> String {
+        match expr {
+            Expression::Number(n) => format!("{:?}", *n as f64),
+            Expression::Float(f, _) => format!("{:?}", f),
+            // A variable nothing assigns is never declared, and always 0
+            Expression::Variable(name) if !self.variables.contains(name) => "0.0".to_string(),
+            Expression::Variable(name) => Self::variable(name),
+            Expression::BinaryOp { left, operator, right } => {
+                let left = self.generate_expr(left);
+                let right = self.generate_expr(right);
+                match operator {
+                    BinOp::Add => format!("({} + {})", left, right),
+                    BinOp::Subtract => format!("({} - {})", left, right),
+                    BinOp::Multiply => format!("({} * {})", left, right),
+                    BinOp::Divide => format!("({} / {})", left, right),
+                    BinOp::Power => format!("{}({}, {})", S::POWER, left, right),
+                }
+            }
+            Expression::FunctionCall { name, args } => {
+                let args: Vec<String> = args.iter().map(|arg| self.generate_expr(arg)).collect();
+                match name.to_uppercase().as_str() {
+                    "INT" => format!("{}floor({})", S::MATH, args[0]),
+                    "RND" => S::method("rnd", args.first().map_or("1.0", String::as_str)),
+                    "TIMER" => S::TIMER.to_string(),
+                    "SQR" => format!("{}sqrt({})", S::MATH, args[0]),
+                    "EXP" => format!("{}exp({})", S::MATH, args[0]),
+                    "ABS" => format!("{}abs({})", S::MATH, args[0]),
+                    _ => format!("{}({})", name, args.join(", ")),
+                }
+            }
+        }
+    }
+
+    fn generate_condition(&self, left: &Expression, op: &Token, right: &Expression, negated: bool) -> String {
+        let op_string = match (op, negated) {
+            (Token::Equal, false) | (Token::NotEqual, true) => S::EQUAL,
+            (Token::NotEqual, false) | (Token::Equal, true) => S::NOT_EQUAL,
+            (Token::LessThan, _) => "<",
+            (Token::LessOrEqual, _) => "<=",
+            (Token::GreaterThan, _) => ">",
+            (Token::GreaterOrEqual, _) => ">=",
+            _ => S::EQUAL,
+        };
+        let condition = format!("{} {} {}", self.generate_expr(left), op_string, self.generate_expr(right));
+        // Only = and <> can be flipped; the others differ on NaN
+        if negated && !matches!(op, Token::Equal | Token::NotEqual) {
+            format!("!({})", condition)
+        } else {
+            condition
+        }
+    }
+
+    /// The condition of the IF that ends block `cond`.
+    fn block_condition(&self, cfg: &Cfg, cond: usize, negated: bool) -> String {
+        match cfg.blocks[cond].steps.last().map(|step| &step.statement().node) {
+            Some(StatementNode::If { left, op, right, .. }) => self.generate_condition(left, op, right, negated),
+            _ => "true".to_string(),
+        }
+    }
+
+    /// Code for a statement that runs straight through.
+    fn generate_statement_node(&self, node: &StatementNode) -> String {
+        let indent = self.indent();
+        match node {
+            StatementNode::Let { var, value } => {
+                format!("{}{} = {};\n", indent, Self::variable(var), self.generate_expr(value))
+            }
+            StatementNode::Print { items, newline } => {
+                let mut result = String::new();
+                for item in items {
+                    match item {
+                        PrintItem::String(s) => {
+                            result.push_str(&format!("{}{};\n", indent, S::method("print_string", &format!("{:?}", s))))
+                        }
+                        PrintItem::Expr(expr) => result.push_str(&S::print_number(&indent, &self.generate_expr(expr))),
+                        PrintItem::Comma | PrintItem::Semicolon => {}
+                    }
+                }
+                if *newline {
+                    result.push_str(&format!("{}{};\n", indent, S::method("print_newline", "")));
+                }
+                result
+            }
+            StatementNode::Input(var) => {
+                format!("{}{} = {};\n", indent, Self::variable(var), S::awaited(S::method("input", "")))
+            }
+            StatementNode::Randomize(None) => format!("{}{};\n", indent, S::awaited(S::method("randomize_prompt", ""))),
+            StatementNode::Randomize(Some(Expression::FunctionCall { name, args })) if name == "TIMER" && args.is_empty() => {
+                format!("{}{};\n", indent, S::method("randomize_timer", ""))
+            }
+            StatementNode::Randomize(Some(seed)) => {
+                format!("{}{};\n", indent, S::method("randomize", &self.generate_expr(seed)))
+            }
+            StatementNode::Rem(comment) if !comment.is_empty() => format!("{}// {}\n", indent, comment),
+            _ => String::new(),
+        }
+    }
+
+    /// Mark the first line of `code` with the BASIC line it came from.
+    fn mark_line(code: String, label: Option<i64>) -> String {
+        match (label, code.find('\n')) {
+            (Some(label), Some(end)) if !code.trim_start().starts_with("//") => {
+                format!("{}  // {}{}", &code[..end], label, &code[end..])
+            }
+            _ => code,
+        }
+    }
+
+    fn generate_block(&self, cfg: &Cfg, block: usize) -> String {
+        let mut code = String::new();
+        for step in &cfg.blocks[block].steps {
+            if let Step::Statement(stmt) = step {
+                code.push_str(&Self::mark_line(self.generate_statement_node(&stmt.node), stmt.label));
+            }
+        }
+        code
+    }
+
+    fn generate_body(&mut self, cfg: &Cfg, body: &[Structured]) -> String {
+        self.indent_level += 1;
+        let result = self.generate_structured(cfg, body);
+        self.indent_level -= 1;
+        result
+    }
+
+    /// A loop's body, with the labelled block `continue` leaves if it is
+    /// needed, and the label to put on the loop.
+    fn generate_loop_body(&mut self, cfg: &Cfg, body: &[Structured], continue_leaves_body: bool) -> (String, String) {
+        self.loop_count += 1;
+        let label = self.loop_count;
+        self.loops.push(Loop { label, continue_leaves_body, continued: false, named: false });
+        let code = self.generate_body(cfg, body);
+        let this = self.loops.pop().expect("loop pushed above");
+        let name = if this.named { format!("{}loop{}: ", S::LABEL, label) } else { String::new() };
+        if !this.continued {
+            return (name, code);
+        }
+        let inner = self.indent() + "    ";
+        let mut result = format!("{}{}next{}: {{\n", inner, S::LABEL, label);
+        for line in code.lines() {
+            result.push_str(&format!("    {}\n", line));
+        }
+        result.push_str(&format!("{}}}\n", inner));
+        (name, result)
+    }
+
+    fn generate_structured(&mut self, cfg: &Cfg, items: &[Structured]) -> String {
+        let mut result = String::new();
+        for item in items {
+            match item {
+                Structured::Block(block) => result.push_str(&self.generate_block(cfg, *block)),
+                Structured::For { header, body, .. } => {
+                    let stmt = cfg.blocks[*header].steps[0].statement();
+                    let StatementNode::For { var, start, end, step, .. } = &stmt.node else {
+                        continue;
+                    };
+                    let var = Self::variable(var);
+                    let step = step.as_ref().map_or("1.0".to_string(), |step| self.generate_expr(step));
+                    let start_line = format!("{}{} = {};\n", self.indent(), var, self.generate_expr(start));
+                    result.push_str(&Self::mark_line(start_line, stmt.label));
+                    let (name, body) = self.generate_loop_body(cfg, body, true);
+                    let test = S::test(&format!("{} <= {}", var, self.generate_expr(end)));
+                    result.push_str(&format!("{}{}while {} {{\n", self.indent(), name, test));
+                    result.push_str(&body);
+                    result.push_str(&format!("{}    {} += {};\n", self.indent(), var, step));
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::If { cond, negated, then_part, else_part } => {
+                    let stmt = cfg.blocks[*cond].steps.last().expect("IF block has steps").statement();
+                    let condition = self.block_condition(cfg, *cond, *negated);
+                    let line = format!("{}if {} {{\n", self.indent(), S::test(&condition));
+                    result.push_str(&Self::mark_line(line, stmt.label));
+                    result.push_str(&self.generate_body(cfg, then_part));
+                    if !else_part.is_empty() {
+                        result.push_str(&format!("{}}} else {{\n", self.indent()));
+                        result.push_str(&self.generate_body(cfg, else_part));
+                    }
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::Loop { body, .. } => {
+                    let (name, body) = self.generate_loop_body(cfg, body, false);
+                    result.push_str(&format!("{}{}{} {{\n", self.indent(), name, S::LOOP));
+                    result.push_str(&body);
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::While { cond, negated, body } => {
+                    let stmt = cfg.blocks[*cond].steps.last().expect("IF block has steps").statement();
+                    let condition = self.block_condition(cfg, *cond, *negated);
+                    let (name, body) = self.generate_loop_body(cfg, body, false);
+                    let line = format!("{}{}while {} {{\n", self.indent(), name, S::test(&condition));
+                    result.push_str(&Self::mark_line(line, stmt.label));
+                    result.push_str(&body);
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::DoWhile { body, cond, negated } => {
+                    let stmt = cfg.blocks[*cond].steps.last().expect("IF block has steps").statement();
+                    // The test is negated once more: it decides to stop
+                    let condition = self.block_condition(cfg, *cond, !*negated);
+                    let (name, body) = self.generate_loop_body(cfg, body, true);
+                    result.push_str(&format!("{}{}{} {{\n", self.indent(), name, S::LOOP));
+                    result.push_str(&body);
+                    let line = format!("{}    if {} {{\n", self.indent(), S::test(&condition));
+                    result.push_str(&Self::mark_line(line, stmt.label));
+                    result.push_str(&format!("{}        break;\n{}    }}\n", self.indent(), self.indent()));
+                    result.push_str(&format!("{}}}\n", self.indent()));
+                }
+                Structured::Break => {
+                    let indent = self.indent();
+                    let innermost = self.loops.last_mut().expect("break inside a loop");
+                    innermost.named = true;
+                    result.push_str(&format!("{}break {}loop{};\n", indent, S::LABEL, innermost.label));
+                }
+                Structured::Continue => {
+                    let innermost = self.loops.last_mut().expect("continue inside a loop");
+                    innermost.continued |= innermost.continue_leaves_body;
+                    innermost.named |= !innermost.continue_leaves_body;
+                    let line = if innermost.continue_leaves_body {
+                        format!("break {}next{};", S::LABEL, innermost.label)
+                    } else {
+                        format!("continue {}loop{};", S::LABEL, innermost.label)
+                    };
+                    result.push_str(&format!("{}{}\n", self.indent(), line));
+                }
+                Structured::Goto(_) => result.push_str(&format!("{}{}\n", self.indent(), S::EXIT)),
+            }
+        }
+        result
+    }
+
+    /// Whether the program has a GOSUB or RETURN, which need the state
+    /// machine even when there is no line to go to.
+    fn has_subroutines(cfg: &Cfg) -> bool {
+        cfg.blocks
+            .iter()
+            .flat_map(|block| &block.steps)
+            .any(|step| matches!(step.statement().node, StatementNode::Gosub(_) | StatementNode::Return))
+    }
+
+    /// Whether every jump in `items` is one structured code can make.
+    fn is_structured(items: &[Structured]) -> bool {
+        items.iter().all(|item| match item {
+            Structured::Goto(target) => *target == Target::Exit,
+            Structured::For { body, .. } | Structured::Loop { body, .. } | Structured::While { body, .. } => {
+                Self::is_structured(body)
+            }
+            Structured::DoWhile { body, .. } => Self::is_structured(body),
+            Structured::If { then_part, else_part, .. } => {
+                Self::is_structured(then_part) && Self::is_structured(else_part)
+            }
+            _ => true,
+        })
+    }
+
+    /// The `pc` value control goes to along the edge of `kind` out of
+    /// `block`, or `None` if it leads to a line that does not exist.
+    fn successor(cfg: &Cfg, block: usize, kind: EdgeKind) -> Option<usize> {
+        cfg.blocks[block].successors.iter().find(|edge| edge.kind == kind).map(|edge| edge.target)
+    }
+
+    /// The statement that sets `pc` from the end of `block`.
+    fn generate_transfer(&self, cfg: &Cfg, block: usize) -> String {
+        let indent = self.indent();
+        let goto = |target: Option<usize>, line: Option<i64>| match target {
+            Some(target) => format!("pc = {};", target),
+            None => S::raise(S::method("error", &format!("\"Undefined line number\", {}", S::line(line)))),
+        };
+        let Some(last) = cfg.blocks[block].steps.last() else {
+            return String::new();
+        };
+        let stmt = last.statement();
+        let line = match (last, &stmt.node) {
+            (Step::Next(_), StatementNode::For { var, end, step, .. }) => {
+                let var = Self::variable(var);
+                let step = step.as_ref().map_or("1.0".to_string(), |step| self.generate_expr(step));
+                let again = goto(Self::successor(cfg, block, EdgeKind::Loop), stmt.label);
+                let done = goto(Self::successor(cfg, block, EdgeKind::Fallthrough), stmt.label);
+                let next = format!("{}{} += {};\n", indent, var, step);
+                let test = S::test(&format!("{} <= {}", var, self.generate_expr(end)));
+                let test = format!("{}if {} {{ {} }} else {{ {} }}\n", indent, test, again, done);
+                return Self::mark_line(next, last.label()) + &test;
+            }
+            (_, StatementNode::For { var, start, end, .. }) => {
+                let var = Self::variable(var);
+                let body = goto(Self::successor(cfg, block, EdgeKind::Fallthrough), stmt.label);
+                let skip = goto(Self::successor(cfg, block, EdgeKind::LoopExit), stmt.label);
+                let start = format!("{}{} = {};\n", indent, var, self.generate_expr(start));
+                let test = S::test(&format!("{} <= {}", var, self.generate_expr(end)));
+                let test = format!("{}if {} {{ {} }} else {{ {} }}\n", indent, test, body, skip);
+                return Self::mark_line(start, stmt.label) + &test;
+            }
+            (_, StatementNode::If { left, op, right, .. }) => {
+                let then = goto(Self::successor(cfg, block, EdgeKind::Then), stmt.label);
+                let otherwise = goto(Self::successor(cfg, block, EdgeKind::Else), stmt.label);
+                let condition = self.generate_condition(left, op, right, false);
+                format!("if {} {{ {} }} else {{ {} }}", S::test(&condition), then, otherwise)
+            }
+            (_, StatementNode::Goto(_)) => goto(Self::successor(cfg, block, EdgeKind::Goto), stmt.label),
+            (_, StatementNode::Gosub(_)) => {
+                let push = S::method("gosub", &format!("{}, {}", cfg.return_point(block), S::line(stmt.label)));
+                format!("{}; {}", S::checked(push), goto(Self::successor(cfg, block, EdgeKind::Gosub), stmt.label))
+            }
+            (_, StatementNode::Return) => {
+                format!("pc = {};", S::checked(S::method("gosub_return", &S::line(stmt.label))))
+            }
+            (_, StatementNode::End) => format!("pc = {};", EXIT),
+            _ => goto(Self::successor(cfg, block, EdgeKind::Fallthrough), None),
+        };
+        // Jumps and tests get their own line number only if nothing else
+        // on the line has it
+        let marked = if self.generate_statement_node(&stmt.node).is_empty() { stmt.label } else { None };
+        Self::mark_line(format!("{}{}\n", indent, line), marked)
+    }
+
+    /// The program as a `match` or `switch` on `pc` in an endless loop.
+    fn generate_state_machine(&mut self, cfg: &Cfg) -> String {
+        let first = cfg.blocks[ENTRY].successors.first().map_or(EXIT, |edge| edge.target);
+        let mut result = format!("{}{} = {};\n", self.indent(), S::PC, first);
+        result.push_str(&format!("{}{} {{\n", self.indent(), S::LOOP));
+        result.push_str(&format!("{}    {} {{\n", self.indent(), S::DISPATCH));
+        self.indent_level += 3;
+        for (index, block) in cfg.statement_blocks() {
+            let lines: Vec<i64> = block.steps.iter().filter_map(|step| step.label()).collect();
+            let comment = match (lines.first(), lines.last()) {
+                (Some(first), Some(last)) if first != last => format!(" // {}-{}", first, last),
+                (Some(first), _) => format!(" // {}", first),
+                _ => String::new(),
+            };
+            let arm_indent = "    ".repeat(self.indent_level - 1);
+            result.push_str(&format!("{}{} {{{}\n", arm_indent, S::case(index), comment));
+            result.push_str(&self.generate_block(cfg, index));
+            result.push_str(&self.generate_transfer(cfg, index));
+            if let Some(end) = S::END_CASE {
+                result.push_str(&format!("{}{}\n", self.indent(), end));
+            }
+            result.push_str(&format!("{}}}\n", arm_indent));
+        }
+        self.indent_level -= 3;
+        for line in S::DEFAULT.lines() {
+            result.push_str(&format!("{}        {}\n", self.indent(), line));
+        }
+        result.push_str(&format!("{}    }}\n", self.indent()));
+        result.push_str(&format!("{}}}\n", self.indent()));
+        result
+    }
+
+    pub fn generate(&mut self, statements: &[Statement]) -> String {
+        self.collect_variables(statements);
+        let cfg = Cfg::new(statements);
+        let structure = structure::structure(&cfg);
+
+        let body = if structure.labelled.is_empty() && !Self::has_subroutines(&cfg) && Self::is_structured(&structure.body) {
+            let mut body = self.generate_structured(&cfg, &structure.body);
+            if !S::FINISH.is_empty() {
+                body.push_str(&format!("{}{}\n", self.indent(), S::FINISH));
+            }
+            body
+        } else {
+            self.generate_state_machine(&cfg)
+        };
+
+        let mut result = String::from(S::RUNTIME);
+        result.push('\n');
+        result.push_str(&S::constant("GOSUB_STACK_SIZE", GOSUB_STACK_SIZE));
+        result.push_str("\n\n");
+        result.push_str(S::PROGRAM);
+        result.push('\n');
+        if let Some(seed) = self.options.seed {
+            result.push_str(&format!("    {};\n", S::method("fix_seed", &format!("{:?}", seed as f64))));
+        }
+        for var in &self.variables {
+            result.push_str(&format!("    {}\n", S::declare(&Self::variable(var))));
+        }
+        if !self.variables.is_empty() {
+            result.push('\n');
+        }
+        result.push_str(&body);
+        result.push_str("}\n");
+        result
+    }
+}
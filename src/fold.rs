@@ -0,0 +1,173 @@
+//! Constant folding and algebraic simplification of expressions.
+//!
+//! Operators and pure builtins whose operands are all constants are
+//! evaluated at compile time, in the same precision the generated code
+//! uses (rounding to MBF singles when the program computes in those), so
+//! folding never changes what a program prints. A result that is not a
+//! finite number (`1/0`, `SQR(-1)`) or overflows is left for the runtime.
+//!
+//! Identities are then removed: `X*1`, `1*X`, `X+0`, `0+X`, `X-0`, `X/1`
+//! and `X^1` become `X`, and `X^2` on a variable becomes `X*X`.
+//!
+//! A division whose divisor folds to zero is reported with the
+//! `division-by-zero` warning.
+
+use crate::diagnostic::Diagnostic;
+use crate::numbers::Numbers;
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+
+/// Fold every expression in the program.
+pub fn fold(statements: &mut [Statement], numbers: Numbers) {
+    for stmt in statements {
+        fold_statement(&mut stmt.node, numbers);
+    }
+}
+
+fn fold_statement(node: &mut StatementNode, numbers: Numbers) {
+    match node {
+        StatementNode::Let { value, .. } | StatementNode::Randomize(Some(value)) => *value = fold_expression(value, numbers),
+        StatementNode::Print { items, .. } => {
+            for item in items {
+                if let PrintItem::Expr(expr) = item {
+                    *expr = fold_expression(expr, numbers);
+                }
+            }
+        }
+        StatementNode::If { left, right, then_part, .. } => {
+            *left = fold_expression(left, numbers);
+            *right = fold_expression(right, numbers);
+            fold_statement(&mut then_part.node, numbers);
+        }
+        StatementNode::For { start, end, step, body, .. } => {
+            *start = fold_expression(start, numbers);
+            *end = fold_expression(end, numbers);
+            if let Some(step) = step {
+                *step = fold_expression(step, numbers);
+            }
+            fold(body, numbers);
+        }
+        _ => {}
+    }
+}
+
+/// Warn about divisions by a constant zero.
+pub fn check(statements: &[Statement]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    for stmt in statements {
+        let mut divides_by_zero = false;
+        for expr in expressions(&stmt.node) {
+            fold_checked(expr, Numbers::Double, &mut divides_by_zero);
+        }
+        if divides_by_zero {
+            diagnostics.push(Diagnostic::warning("division-by-zero", "Division by zero", stmt.span));
+        }
+        if let StatementNode::For { body, .. } = &stmt.node {
+            diagnostics.extend(check(body));
+        }
+    }
+    diagnostics
+}
+
+/// The expressions of a statement, not counting the bodies of FOR loops.
+pub(crate) fn expressions(node: &StatementNode) -> Vec<&Expression> {
+    match node {
+        StatementNode::Let { value, .. } | StatementNode::Randomize(Some(value)) => vec![value],
+        StatementNode::Print { items, .. } => items
+            .iter()
+            .filter_map(|item| match item {
+                PrintItem::Expr(expr) => Some(expr),
+                _ => None,
+            })
+            .collect(),
+        StatementNode::If { left, right, then_part, .. } => {
+            let mut result = vec![left, right];
+            result.extend(expressions(&then_part.node));
+            result
+        }
+        StatementNode::For { start, end, step, .. } => [Some(start), Some(end), step.as_ref()].into_iter().flatten().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The folded and simplified form of `expr`.
+pub fn fold_expression(expr: &Expression, numbers: Numbers) -> Expression {
+    fold_checked(expr, numbers, &mut false)
+}
+
+/// The value of a constant expression.
+pub fn constant(expr: &Expression) -> Option<f64> {
+    match expr {
+        Expression::Number(n) => Some(*n as f64),
+        Expression::Float(f, _) => Some(*f),
+        _ => None,
+    }
+}
+
+fn number(value: f64) -> Expression {
+    Expression::Float(value, format!("{:?}", value))
+}
+
+/// A pure builtin applied to `x`, or `None` for anything else.
+fn evaluate_builtin(name: &str, x: f64) -> Option<f64> {
+    match name.to_uppercase().as_str() {
+        "INT" => Some(x.floor()),
+        "SQR" => Some(x.sqrt()),
+        "EXP" => Some(x.exp()),
+        "ABS" => Some(x.abs()),
+        _ => None,
+    }
+}
+
+fn fold_checked(expr: &Expression, numbers: Numbers, divides_by_zero: &mut bool) -> Expression {
+    match expr {
+        Expression::Number(_) | Expression::Float(..) | Expression::Variable(_) => expr.clone(),
+        Expression::FunctionCall { name, args } => {
+            let args: Vec<Expression> = args.iter().map(|arg| fold_checked(arg, numbers, divides_by_zero)).collect();
+            if let [arg] = args.as_slice()
+                && let Some(x) = constant(arg)
+                && let Some(x) = numbers.round(x)
+                && let Some(value) = evaluate_builtin(name, x).and_then(|value| numbers.round(value))
+            {
+                return number(value);
+            }
+            Expression::FunctionCall { name: name.clone(), args }
+        }
+        Expression::BinaryOp { left, operator, right } => {
+            let left = fold_checked(left, numbers, divides_by_zero);
+            let right = fold_checked(right, numbers, divides_by_zero);
+            let (l, r) = (constant(&left), constant(&right));
+            if r == Some(0.0) && matches!(operator, BinOp::Divide) {
+                *divides_by_zero = true;
+            }
+            // GW-BASIC reports 0 raised to a negative power the same way
+            if l == Some(0.0) && matches!(operator, BinOp::Power) && r.is_some_and(|r| r < 0.0) {
+                *divides_by_zero = true;
+            }
+
+            if let (Some(l), Some(r)) = (l.and_then(|l| numbers.round(l)), r.and_then(|r| numbers.round(r))) {
+                let value = match operator {
+                    BinOp::Add => l + r,
+                    BinOp::Subtract => l - r,
+                    BinOp::Multiply => l * r,
+                    BinOp::Divide => l / r,
+                    BinOp::Power => l.powf(r),
+                };
+                if let Some(value) = numbers.round(value) {
+                    return number(value);
+                }
+            }
+
+            match (operator, l, r) {
+                (BinOp::Add, Some(0.0), _) | (BinOp::Multiply, Some(1.0), _) => right,
+                (BinOp::Add | BinOp::Subtract, _, Some(0.0))
+                | (BinOp::Multiply | BinOp::Divide | BinOp::Power, _, Some(1.0)) => left,
+                (BinOp::Power, _, Some(2.0)) if matches!(left, Expression::Variable(_)) => Expression::BinaryOp {
+                    left: Box::new(left.clone()),
+                    operator: BinOp::Multiply,
+                    right: Box::new(left),
+                },
+                _ => Expression::BinaryOp { left: Box::new(left), operator: operator.clone(), right: Box::new(right) },
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::parser::{BinOp, Expression, Statement, StatementNode, PrintItem};
+use crate::lexer::Token;
+
+/// Options controlling the canonical layout.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Indent the body of FOR loops.
+    pub indent_for: bool,
+    /// Write assignments as `LET X = 1` rather than `X = 1`.
+    pub explicit_let: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent_for: false,
+            explicit_let: true,
+        }
+    }
+}
+
+/// Prints a parsed program back as canonical BASIC source.
+pub struct Formatter {
+    indent_level: usize,
+    options: FormatOptions,
+}
+
+impl Formatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Formatter {
+            indent_level: 0,
+            options,
+        }
+    }
+
+    fn indent(&self) -> String {
+        if self.options.indent_for {
+            "  ".repeat(self.indent_level)
+        } else {
+            String::new()
+        }
+    }
+
+    fn precedence(operator: &BinOp) -> u8 {
+        match operator {
+            BinOp::Add | BinOp::Subtract => 1,
+            BinOp::Multiply | BinOp::Divide => 2,
+            BinOp::Power => 3,
+        }
+    }
+
+    /// Format an expression, adding only the parentheses its structure needs.
+    fn format_expr(&self, expr: &Expression) -> String {
+        match expr {
+            Expression::Number(n) => n.to_string(),
+            Expression::Float(_, text) => text.clone(),
+            Expression::Variable(name) => name.clone(),
+            Expression::BinaryOp { left, operator, right } => {
+                let precedence = Self::precedence(operator);
+                // Nested ^ is always parenthesised since dialects disagree on
+                // its associativity; the other operators group to the left
+                let (left_min, right_min) = match operator {
+                    BinOp::Power => (precedence + 1, precedence + 1),
+                    _ => (precedence, precedence + 1),
+                };
+                let left_string = self.format_operand(left, left_min);
+                let right_string = self.format_operand(right, right_min);
+                let op_string = match operator {
+                    BinOp::Add => "+",
+                    BinOp::Subtract => "-",
+                    BinOp::Multiply => "*",
+                    BinOp::Divide => "/",
+                    BinOp::Power => "^",
+                };
+                format!("{} {} {}", left_string, op_string, right_string)
+            }
+            Expression::FunctionCall { name, args } if args.is_empty() => name.to_uppercase(),
+            Expression::FunctionCall { name, args } => {
+                let args_string: Vec<String> = args.iter().map(|a| self.format_expr(a)).collect();
+                format!("{}({})", name.to_uppercase(), args_string.join(", "))
+            }
+        }
+    }
+
+    fn format_operand(&self, expr: &Expression, min_precedence: u8) -> String {
+        let formatted = self.format_expr(expr);
+        match expr {
+            Expression::BinaryOp { operator, .. } if Self::precedence(operator) < min_precedence => {
+                format!("({})", formatted)
+            }
+            _ => formatted,
+        }
+    }
+
+    /// Format one statement as a single line, without its line number. A
+    /// FOR loop gives just its header.
+    pub fn format_statement_node(&mut self, node: &StatementNode) -> String {
+        match node {
+            StatementNode::Let { var, value } => {
+                let keyword = if self.options.explicit_let { "LET " } else { "" };
+                format!("{}{} = {}", keyword, var, self.format_expr(value))
+            }
+            StatementNode::Print { items, .. } => {
+                let mut result = String::from("PRINT");
+                let mut after_value = false;
+                for item in items {
+                    let text = match item {
+                        PrintItem::Comma | PrintItem::Semicolon => {
+                            result.push(if matches!(item, PrintItem::Comma) { ',' } else { ';' });
+                            after_value = false;
+                            continue;
+                        }
+                        PrintItem::String(s) => format!("\"{}\"", s),
+                        PrintItem::Expr(expr) => self.format_expr(expr),
+                    };
+                    // Adjacent items print as if separated by ;
+                    if after_value {
+                        result.push(';');
+                    }
+                    result.push(' ');
+                    result.push_str(&text);
+                    after_value = true;
+                }
+                result
+            }
+            StatementNode::For { var, start, end, step, .. } => {
+                let mut header = format!(
+                    "FOR {} = {} TO {}",
+                    var,
+                    self.format_expr(start),
+                    self.format_expr(end)
+                );
+                if let Some(step) = step {
+                    header.push_str(&format!(" STEP {}", self.format_expr(step)));
+                }
+                header
+            }
+            StatementNode::If { left, op, right, then_part } => {
+                let op_string = match op {
+                    Token::Equal => "=",
+                    Token::NotEqual => "<>",
+                    Token::LessThan => "<",
+                    Token::LessOrEqual => "<=",
+                    Token::GreaterThan => ">",
+                    Token::GreaterOrEqual => ">=",
+                    _ => "=",
+                };
+                let then_string = match &then_part.node {
+                    StatementNode::Goto(line) => line.to_string(),
+                    node => self.format_statement_node(node),
+                };
+                format!(
+                    "IF {} {} {} THEN {}",
+                    self.format_expr(left),
+                    op_string,
+                    self.format_expr(right),
+                    then_string
+                )
+            }
+            StatementNode::Goto(line) => format!("GOTO {}", line),
+            StatementNode::Gosub(line) => format!("GOSUB {}", line),
+            StatementNode::Return => "RETURN".to_string(),
+            StatementNode::Randomize(None) => "RANDOMIZE".to_string(),
+            StatementNode::Randomize(Some(seed)) => format!("RANDOMIZE {}", self.format_expr(seed)),
+            StatementNode::Input(var) => format!("INPUT {}", var),
+            StatementNode::Tron => "TRON".to_string(),
+            StatementNode::Troff => "TROFF".to_string(),
+            StatementNode::Rem(comment) if comment.is_empty() => "REM".to_string(),
+            StatementNode::Rem(comment) => format!("REM {}", comment),
+            StatementNode::End => "END".to_string(),
+            StatementNode::Empty => String::new(),
+        }
+    }
+
+    fn format_line(&self, label: Option<i64>, text: &str) -> String {
+        let mut line = String::new();
+        if let Some(label) = label {
+            line.push_str(&label.to_string());
+            if !text.is_empty() {
+                line.push(' ');
+            }
+        }
+        if !text.is_empty() {
+            line.push_str(&self.indent());
+            line.push_str(text);
+        }
+        line.push('\n');
+        line
+    }
+
+    fn format_statement(&mut self, stmt: &Statement) -> String {
+        match &stmt.node {
+            StatementNode::For { var, body, next_label, .. } => {
+                let header = self.format_statement_node(&stmt.node);
+                let mut result = self.format_line(stmt.label, &header);
+
+                self.indent_level += 1;
+                for inner in body {
+                    result.push_str(&self.format_statement(inner));
+                }
+                self.indent_level -= 1;
+
+                result.push_str(&self.format_line(*next_label, &format!("NEXT {}", var)));
+                result
+            }
+            node => {
+                let text = self.format_statement_node(node);
+                self.format_line(stmt.label, &text)
+            }
+        }
+    }
+
+    pub fn format(&mut self, statements: &[Statement]) -> String {
+        let mut result = String::new();
+        for stmt in statements {
+            result.push_str(&self.format_statement(stmt));
+        }
+        result
+    }
+}
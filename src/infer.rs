@@ -0,0 +1,283 @@
+//! Inference of variables that only ever hold small integers.
+//!
+//! Each variable gets an abstract value: the interval its values lie in,
+//! whether they are all integers, and whether one of them may be negative
+//! zero (which prints as `-0` and so must stay a double). The values of
+//! every assignment to a variable are joined until nothing changes; a bound
+//! that keeps growing is widened to infinity so the loop ends. Variables
+//! start out as 0.
+//!
+//! A variable whose values are integers without negative zero and within
+//! the range of `int32_t` can be stored as one: every value it takes is
+//! exactly representable either way, so the program behaves the same.
+//! When numbers are MBF singles, whose mantissa holds 24 bits, the range is
+//! further limited to what a single holds exactly.
+
+use crate::numbers::Numbers;
+use crate::parser::{BinOp, Expression, Statement, StatementNode};
+use std::collections::BTreeMap;
+
+/// Rounds of joining before growing bounds are widened.
+const WIDEN_AFTER: usize = 8;
+
+/// The largest integer up to which every integer is an MBF single.
+const MBF_EXACT: f64 = 16777216.0;
+
+/// What is known about the values of a variable or expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Value {
+    pub low: f64,
+    pub high: f64,
+    pub integral: bool,
+    pub negative_zero: bool,
+}
+
+impl Value {
+    fn constant(value: f64) -> Self {
+        Value {
+            low: value,
+            high: value,
+            integral: value.fract() == 0.0,
+            negative_zero: value == 0.0 && value.is_sign_negative(),
+        }
+    }
+
+    fn unknown() -> Self {
+        Value { low: f64::NEG_INFINITY, high: f64::INFINITY, integral: false, negative_zero: true }
+    }
+
+    fn range(low: f64, high: f64, integral: bool, negative_zero: bool) -> Self {
+        // inf - inf and 0 * inf give NaN; treat them as unbounded
+        let low = if low.is_nan() { f64::NEG_INFINITY } else { low };
+        let high = if high.is_nan() { f64::INFINITY } else { high };
+        Value { low, high, integral, negative_zero }
+    }
+
+    fn join(&self, other: &Value) -> Value {
+        Value {
+            low: self.low.min(other.low),
+            high: self.high.max(other.high),
+            integral: self.integral && other.integral,
+            negative_zero: self.negative_zero || other.negative_zero,
+        }
+    }
+
+    fn contains_zero(&self) -> bool {
+        self.low <= 0.0 && self.high >= 0.0
+    }
+
+    /// Whether every value fits an `int32_t` unchanged.
+    pub fn fits_int32(&self) -> bool {
+        self.integral && !self.negative_zero && self.low >= i32::MIN as f64 && self.high <= i32::MAX as f64
+    }
+}
+
+/// An assignment, seen without regard to where in the program it is.
+enum Assignment<'a> {
+    Value(&'a Expression),
+    Input,
+    For { start: &'a Expression, end: &'a Expression, step: Option<&'a Expression> },
+    /// NEXT, adding the step to whatever value the variable has.
+    Next(Option<&'a Expression>),
+}
+
+/// The inferred values of every variable in a program.
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    values: BTreeMap<String, Value>,
+    numbers: Numbers,
+}
+
+impl Types {
+    pub fn infer(statements: &[Statement], numbers: Numbers) -> Self {
+        let mut assignments = Vec::new();
+        collect_assignments(statements, &mut assignments);
+        // NEXT adds the step to the value the variable has when it gets
+        // there. If only the FOR assigns it, that is a value the loop test
+        // let through, which loop_value covers; any other assignment may
+        // run in between, through the body, a GOSUB or a jump.
+        let nexts: Vec<_> = assignments
+            .iter()
+            .filter_map(|(var, assignment)| match assignment {
+                Assignment::For { step, .. } if assignments.iter().filter(|(other, _)| other == var).count() > 1 => {
+                    Some((*var, Assignment::Next(*step)))
+                }
+                _ => None,
+            })
+            .collect();
+        assignments.extend(nexts);
+
+        let mut types = Types { values: BTreeMap::new(), numbers };
+        for (var, _) in &assignments {
+            types.values.insert(var.to_string(), Value::constant(0.0));
+        }
+        let mut round = 0;
+        loop {
+            let mut changed = false;
+            for (var, assignment) in &assignments {
+                let value = match assignment {
+                    Assignment::Value(expr) => types.value(expr),
+                    Assignment::Input => Value::unknown(),
+                    Assignment::For { start, end, step } => types.loop_value(start, end, *step),
+                    Assignment::Next(step) => types.next_value(var, *step),
+                };
+                let old = types.values[*var];
+                let mut new = old.join(&value);
+                if round >= WIDEN_AFTER {
+                    if new.low < old.low {
+                        new.low = f64::NEG_INFINITY;
+                    }
+                    if new.high > old.high {
+                        new.high = f64::INFINITY;
+                    }
+                }
+                if new != old {
+                    types.values.insert(var.to_string(), new);
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+            round += 1;
+        }
+        types
+    }
+
+    /// Whether `var` can be stored in an `int32_t`.
+    pub fn is_integer(&self, var: &str) -> bool {
+        self.values.get(var).is_some_and(|value| self.fits(value))
+    }
+
+    /// Whether every value can be computed in `int32_t` and still be what
+    /// the program's number format would give.
+    pub fn fits(&self, value: &Value) -> bool {
+        match self.numbers {
+            Numbers::Double => value.fits_int32(),
+            Numbers::Mbf => value.fits_int32() && value.low >= -MBF_EXACT && value.high <= MBF_EXACT,
+        }
+    }
+
+    /// The values a FOR variable takes, including the one it has after the
+    /// loop.
+    fn loop_value(&self, start: &Expression, end: &Expression, step: Option<&Expression>) -> Value {
+        let start = self.value(start);
+        let end = self.value(end);
+        let step = step.map_or(Value::constant(1.0), |step| self.value(step));
+        let integral = start.integral && step.integral;
+        let negative_zero = start.negative_zero;
+        if step.low < 0.0 {
+            // Counting down past an end tested with <= never stops
+            return Value::range(f64::NEG_INFINITY, start.high.max(end.high), integral, negative_zero);
+        }
+        Value::range(start.low, start.high.max(end.high + step.high), integral, negative_zero)
+    }
+
+    /// The value of `var` after NEXT adds the step to any value it has.
+    fn next_value(&self, var: &str, step: Option<&Expression>) -> Value {
+        let value = self.values[var];
+        let step = step.map_or(Value::constant(1.0), |step| self.value(step));
+        Value::range(
+            value.low + step.low,
+            value.high + step.high,
+            value.integral && step.integral,
+            value.negative_zero && step.negative_zero,
+        )
+    }
+
+    /// What is known about the value of `expr`.
+    pub fn value(&self, expr: &Expression) -> Value {
+        match expr {
+            Expression::Number(n) => Value::constant(*n as f64),
+            Expression::Float(f, _) => Value::constant(*f),
+            Expression::Variable(name) => self.values.get(name).copied().unwrap_or(Value::constant(0.0)),
+            Expression::BinaryOp { left, operator, right } => {
+                let (a, b) = (self.value(left), self.value(right));
+                let integral = a.integral && b.integral;
+                match operator {
+                    BinOp::Add => Value::range(
+                        a.low + b.low,
+                        a.high + b.high,
+                        integral,
+                        a.negative_zero && b.negative_zero,
+                    ),
+                    BinOp::Subtract => Value::range(
+                        a.low - b.high,
+                        a.high - b.low,
+                        integral,
+                        a.negative_zero && b.contains_zero(),
+                    ),
+                    BinOp::Multiply => {
+                        let products = [a.low * b.low, a.low * b.high, a.high * b.low, a.high * b.high];
+                        let (low, high) = bounds(&products);
+                        // 0 times a negative number is -0
+                        let negative_zero = (a.contains_zero() && b.low < 0.0)
+                            || (b.contains_zero() && a.low < 0.0)
+                            || a.negative_zero
+                            || b.negative_zero;
+                        Value::range(low, high, integral, negative_zero)
+                    }
+                    BinOp::Divide if b.low > 0.0 || b.high < 0.0 => {
+                        let quotients = [a.low / b.low, a.low / b.high, a.high / b.low, a.high / b.high];
+                        let (low, high) = bounds(&quotients);
+                        Value::range(low, high, false, true)
+                    }
+                    BinOp::Divide => Value::unknown(),
+                    BinOp::Power => match crate::fold::constant(right) {
+                        Some(k) if k.fract() == 0.0 && (0.0..=31.0).contains(&k) => {
+                            let mut powers = vec![a.low.powf(k), a.high.powf(k)];
+                            if a.contains_zero() {
+                                powers.push(0.0);
+                            }
+                            let (low, high) = bounds(&powers);
+                            Value::range(low, high, a.integral, a.negative_zero || a.low < 0.0)
+                        }
+                        _ => Value::unknown(),
+                    },
+                }
+            }
+            Expression::FunctionCall { name, args } => {
+                let x = args.first().map_or(Value::unknown(), |arg| self.value(arg));
+                match name.to_uppercase().as_str() {
+                    "INT" => Value::range(x.low.floor(), x.high.floor(), true, x.negative_zero),
+                    "ABS" => {
+                        let low = if x.contains_zero() { 0.0 } else { x.low.abs().min(x.high.abs()) };
+                        Value::range(low, x.low.abs().max(x.high.abs()), x.integral, false)
+                    }
+                    "RND" => Value::range(0.0, 1.0, false, false),
+                    "SQR" => Value::range(0.0, x.high.max(0.0).sqrt(), false, true),
+                    "EXP" => Value::range(0.0, x.high.exp(), false, false),
+                    _ => Value::unknown(),
+                }
+            }
+        }
+    }
+}
+
+fn bounds(values: &[f64]) -> (f64, f64) {
+    if values.iter().any(|v| v.is_nan()) {
+        return (f64::NEG_INFINITY, f64::INFINITY);
+    }
+    let low = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let high = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    (low, high)
+}
+
+fn collect_assignments<'a>(statements: &'a [Statement], assignments: &mut Vec<(&'a str, Assignment<'a>)>) {
+    for stmt in statements {
+        collect_node(&stmt.node, assignments);
+    }
+}
+
+fn collect_node<'a>(node: &'a StatementNode, assignments: &mut Vec<(&'a str, Assignment<'a>)>) {
+    match node {
+        StatementNode::Let { var, value } => assignments.push((var, Assignment::Value(value))),
+        StatementNode::Input(var) => assignments.push((var, Assignment::Input)),
+        StatementNode::For { var, start, end, step, body, .. } => {
+            assignments.push((var, Assignment::For { start, end, step: step.as_ref() }));
+            collect_assignments(body, assignments);
+        }
+        StatementNode::If { then_part, .. } => collect_node(&then_part.node, assignments),
+        _ => {}
+    }
+}
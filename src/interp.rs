@@ -0,0 +1,495 @@
+//! An interpreter for parsed programs, which the debugger runs.
+//!
+//! It walks the [control-flow graph](crate::cfg) a step at a time, so a
+//! program behaves as its compiled C does: the same printing and input,
+//! the same RND sequence, and the same `--checks` errors and `--numbers mbf`
+//! rounding. Besides the GOSUB stack it keeps a FOR stack the way GW-BASIC
+//! did, but only to show: which way a loop goes is the graph's business.
+
+use crate::cfg::{Cfg, EdgeKind, Step, ENTRY, EXIT};
+use crate::codegen::{CodegenOptions, GOSUB_STACK_SIZE};
+use crate::lexer::Token;
+use crate::numbers::{self, Numbers};
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// GW-BASIC's generator, as in the C runtime.
+const RND_MODULUS: u32 = 1 << 24;
+const RND_SEED: u32 = 0x4fc752;
+const RND_MULTIPLIER: u32 = 214013;
+const RND_INCREMENT: u32 = 2531011;
+
+/// Where a running program prints to and reads from.
+pub trait Io {
+    /// Write `text` as it is.
+    fn print(&mut self, text: &str);
+    /// Read a line, or `None` when input has run out.
+    fn input(&mut self) -> Option<String>;
+    /// Report an error the program carries on after.
+    fn warn(&mut self, error: &RuntimeError);
+}
+
+/// A runtime error, such as one that stopped the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    pub message: String,
+    /// The BASIC line it happened on.
+    pub line: Option<i64>,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{} in {}", self.message, line),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// A GOSUB that has not returned yet.
+#[derive(Debug, Clone, Copy)]
+pub struct GosubFrame {
+    /// The line of the GOSUB.
+    pub line: Option<i64>,
+    /// The block RETURN goes back to.
+    back: usize,
+}
+
+/// A FOR loop that has started and not finished.
+#[derive(Debug, Clone, Copy)]
+pub struct ForFrame<'a> {
+    /// The FOR statement.
+    pub statement: &'a Statement,
+}
+
+pub struct Interpreter<'a> {
+    cfg: Cfg<'a>,
+    options: CodegenOptions,
+    variables: BTreeMap<String, f64>,
+    /// The step to run next: its block and where it is in the block.
+    block: usize,
+    index: usize,
+    gosub_stack: Vec<GosubFrame>,
+    for_stack: Vec<ForFrame<'a>>,
+    seed: u32,
+    /// The last line that started running, which errors name.
+    line: Option<i64>,
+    /// Whether TRON is on, printing each line number as it runs.
+    pub trace: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Ready to run `statements` from the start.
+    pub fn new(statements: &'a [Statement], options: CodegenOptions) -> Self {
+        let mut interpreter = Interpreter {
+            cfg: Cfg::new(statements),
+            trace: options.trace,
+            options,
+            variables: BTreeMap::new(),
+            block: ENTRY,
+            index: 0,
+            gosub_stack: Vec::new(),
+            for_stack: Vec::new(),
+            seed: RND_SEED,
+            line: None,
+        };
+        if let Some(seed) = interpreter.options.seed {
+            interpreter.randomize(seed as f64);
+        }
+        interpreter.go_to(ENTRY);
+        interpreter
+    }
+
+    /// Whether the program has run to its end, or stopped on an error.
+    pub fn finished(&self) -> bool {
+        self.block == EXIT
+    }
+
+    /// The step that runs next.
+    pub fn current(&self) -> Option<Step<'a>> {
+        self.cfg.blocks[self.block].steps.get(self.index).copied()
+    }
+
+    /// The line of the step that runs next.
+    pub fn current_line(&self) -> Option<i64> {
+        self.current().and_then(|step| step.label())
+    }
+
+    /// Whether `line` is a line a step is written on.
+    pub fn has_line(&self, line: i64) -> bool {
+        self.cfg.blocks.iter().flat_map(|block| &block.steps).any(|step| step.label() == Some(line))
+    }
+
+    /// Every variable assigned so far, by name.
+    pub fn variables(&self) -> &BTreeMap<String, f64> {
+        &self.variables
+    }
+
+    /// The GOSUBs waiting for a RETURN, the most recent last.
+    pub fn gosub_stack(&self) -> &[GosubFrame] {
+        &self.gosub_stack
+    }
+
+    /// The FOR loops running, the innermost last.
+    pub fn for_stack(&self) -> &[ForFrame<'a>] {
+        &self.for_stack
+    }
+
+    fn variable(&self, name: &str) -> f64 {
+        self.variables.get(name).copied().unwrap_or(0.0)
+    }
+
+    fn error(&self, message: impl Into<String>) -> RuntimeError {
+        RuntimeError { message: message.into(), line: self.line }
+    }
+
+    /// Carry on from the start of `block`.
+    fn go_to(&mut self, mut block: usize) {
+        // ENTRY has no steps of its own
+        while block != EXIT && self.cfg.blocks[block].steps.is_empty() {
+            block = self.cfg.blocks[block].successors.first().map_or(EXIT, |edge| edge.target);
+        }
+        self.block = block;
+        self.index = 0;
+    }
+
+    fn successor(&self, kind: EdgeKind) -> Result<usize, RuntimeError> {
+        let edge = self.cfg.blocks[self.block].successors.iter().find(|edge| edge.kind == kind);
+        edge.map(|edge| edge.target).ok_or_else(|| self.error("Undefined line number"))
+    }
+
+    /// Stop after an error the dialect does not carry on after, or report
+    /// it and carry on.
+    fn report(&self, message: &str, io: &mut dyn Io) -> Result<(), RuntimeError> {
+        let error = self.error(message);
+        if !self.options.dialect.continues_after_arithmetic_error() {
+            return Err(error);
+        }
+        io.warn(&error);
+        Ok(())
+    }
+
+    fn overflow(&self, x: f64, io: &mut dyn Io) -> Result<f64, RuntimeError> {
+        if x.is_infinite() {
+            self.report("Overflow", io)?;
+            return Ok(f64::MAX.copysign(x));
+        }
+        Ok(x)
+    }
+
+    fn division_by_zero(&self, sign: f64, io: &mut dyn Io) -> Result<f64, RuntimeError> {
+        self.report("Division by zero", io)?;
+        Ok(f64::MAX.copysign(sign))
+    }
+
+    /// `x` rounded to the program's number format.
+    fn round(&self, x: f64, io: &mut dyn Io) -> Result<f64, RuntimeError> {
+        if self.options.numbers == Numbers::Double {
+            return Ok(x);
+        }
+        match numbers::round_mbf(x) {
+            Some(rounded) => Ok(rounded),
+            None => {
+                self.report("Overflow", io)?;
+                Ok(numbers::mbf_max().copysign(x))
+            }
+        }
+    }
+
+    fn arithmetic(&self, operator: &BinOp, a: f64, b: f64, io: &mut dyn Io) -> Result<f64, RuntimeError> {
+        let value = if self.options.checks {
+            match operator {
+                BinOp::Add => self.overflow(a + b, io)?,
+                BinOp::Subtract => self.overflow(a - b, io)?,
+                BinOp::Multiply => self.overflow(a * b, io)?,
+                BinOp::Divide if b == 0.0 => self.division_by_zero(a, io)?,
+                BinOp::Divide => self.overflow(a / b, io)?,
+                BinOp::Power if a == 0.0 && b < 0.0 => self.division_by_zero(1.0, io)?,
+                BinOp::Power if a < 0.0 && b != b.floor() => return Err(self.error("Illegal function call")),
+                BinOp::Power => self.overflow(a.powf(b), io)?,
+            }
+        } else {
+            match operator {
+                BinOp::Add => a + b,
+                BinOp::Subtract => a - b,
+                BinOp::Multiply => a * b,
+                BinOp::Divide => a / b,
+                BinOp::Power => a.powf(b),
+            }
+        };
+        self.round(value, io)
+    }
+
+    /// The value of `expr` now.
+    pub fn evaluate(&mut self, expr: &Expression, io: &mut dyn Io) -> Result<f64, RuntimeError> {
+        match expr {
+            Expression::Number(n) => self.round(*n as f64, io),
+            Expression::Float(f, _) => self.round(*f, io),
+            Expression::Variable(name) => Ok(self.variable(name)),
+            Expression::BinaryOp { left, operator, right } => {
+                let left = self.evaluate(left, io)?;
+                let right = self.evaluate(right, io)?;
+                self.arithmetic(operator, left, right, io)
+            }
+            Expression::FunctionCall { name, args } => {
+                let x = match args.first() {
+                    Some(arg) => self.evaluate(arg, io)?,
+                    // RND on its own is RND(1)
+                    None => 1.0,
+                };
+                match name.to_uppercase().as_str() {
+                    "INT" => Ok(x.floor()),
+                    "ABS" => Ok(x.abs()),
+                    "RND" => Ok(self.rnd(x)),
+                    "TIMER" => Ok(Self::timer()),
+                    "SQR" if self.options.checks && x < 0.0 => Err(self.error("Illegal function call")),
+                    "SQR" => self.round(x.sqrt(), io),
+                    "EXP" if self.options.checks => {
+                        let value = self.overflow(x.exp(), io)?;
+                        self.round(value, io)
+                    }
+                    _ => self.round(x.exp(), io),
+                }
+            }
+        }
+    }
+
+    /// Whether `left op right` holds. As in C, only `<>` holds for NaN.
+    fn compare(left: f64, op: &Token, right: f64) -> bool {
+        match op {
+            Token::NotEqual => left != right,
+            Token::LessThan => left < right,
+            Token::LessOrEqual => left <= right,
+            Token::GreaterThan => left > right,
+            Token::GreaterOrEqual => left >= right,
+            _ => left == right,
+        }
+    }
+
+    /// The line as a number, or `None` if it is not one. A blank line is 0,
+    /// as it is to C's runtime.
+    fn parse_number(line: &str) -> Option<f64> {
+        let text = line.trim();
+        if text.is_empty() {
+            return Some(0.0);
+        }
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let hex = unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X"));
+        let value = match hex {
+            Some(digits) if !digits.is_empty() && digits.chars().all(|ch| ch.is_ascii_hexdigit()) => {
+                digits.chars().fold(0.0, |value, ch| value * 16.0 + ch.to_digit(16).unwrap_or(0) as f64)
+            }
+            // Rust takes a sign of its own, and C's strtod does not take two
+            _ if unsigned.starts_with(['+', '-']) => return None,
+            _ => unsigned.parse().ok()?,
+        };
+        Some(if negative { -value } else { value })
+    }
+
+    /// Prompt until a number is typed, or `None` when input runs out.
+    fn read_number(&mut self, prompt: &str, io: &mut dyn Io) -> Option<f64> {
+        loop {
+            io.print(prompt);
+            let Some(line) = io.input() else {
+                io.print("\n");
+                return None;
+            };
+            match Self::parse_number(&line) {
+                Some(value) => return Some(value),
+                None => io.print("?Redo from start\n"),
+            }
+        }
+    }
+
+    fn next_seed(&mut self) {
+        self.seed = self.seed.wrapping_mul(RND_MULTIPLIER).wrapping_add(RND_INCREMENT) % RND_MODULUS;
+    }
+
+    /// RND(x): the next number in [0, 1) for x > 0, the last one again for
+    /// x = 0, and a fresh sequence seeded from x for x < 0.
+    fn rnd(&mut self, x: f64) -> f64 {
+        if x < 0.0 {
+            let bits = (x as f32).to_bits();
+            self.seed = bits.wrapping_add(bits >> 24) % RND_MODULUS;
+            self.next_seed();
+        } else if x > 0.0 {
+            self.next_seed();
+        }
+        self.seed as f64 / RND_MODULUS as f64
+    }
+
+    /// RANDOMIZE n: the low 16 bits of n replace the top of the seed.
+    fn randomize(&mut self, n: f64) {
+        let whole = if n.is_nan() { 0.0 } else { n.trunc() % 65536.0 };
+        let bits = (whole as i32 as u32) & 0xffff;
+        self.seed = (bits << 8) | (self.seed & 0xff);
+    }
+
+    /// TIMER: seconds since midnight, UTC.
+    fn timer() -> f64 {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        (now.as_secs() % 86400) as f64
+    }
+
+    /// Run a statement that goes straight on to the next, such as one typed
+    /// at the debugger. Returns `false` if input ran out, ending the program.
+    pub fn execute(&mut self, node: &StatementNode, io: &mut dyn Io) -> Result<bool, RuntimeError> {
+        match node {
+            StatementNode::Let { var, value } => {
+                let value = self.evaluate(value, io)?;
+                self.variables.insert(var.clone(), value);
+            }
+            StatementNode::Print { items, newline } => {
+                for item in items {
+                    match item {
+                        PrintItem::String(s) => io.print(s),
+                        PrintItem::Expr(expr) => {
+                            let value = self.evaluate(expr, io)?;
+                            io.print(&self.options.numbers.format(value));
+                        }
+                        PrintItem::Comma | PrintItem::Semicolon => {}
+                    }
+                }
+                if *newline {
+                    io.print("\n");
+                }
+            }
+            StatementNode::Input(var) => {
+                let Some(value) = self.read_number("? ", io) else {
+                    return Ok(false);
+                };
+                let value = self.round(value, io)?;
+                self.variables.insert(var.clone(), value);
+            }
+            StatementNode::Randomize(None) => {
+                let n = match self.options.seed {
+                    Some(seed) => seed as f64,
+                    None => match self.read_number("Random number seed (-32768 to 32767)? ", io) {
+                        Some(n) => n,
+                        None => return Ok(false),
+                    },
+                };
+                self.randomize(n);
+            }
+            StatementNode::Randomize(Some(seed)) => {
+                let n = match (seed, self.options.seed) {
+                    (Expression::FunctionCall { name, args }, Some(fixed)) if name == "TIMER" && args.is_empty() => {
+                        fixed as f64
+                    }
+                    _ => self.evaluate(seed, io)?,
+                };
+                self.randomize(n);
+            }
+            StatementNode::Tron => self.trace = true,
+            // --trace keeps tracing on whatever the program says
+            StatementNode::Troff if !self.options.trace => self.trace = false,
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// Take the FOR loop over `var` off the FOR stack, with any started
+    /// inside it.
+    fn leave_loop(&mut self, var: &str) {
+        let position = self.for_stack.iter().position(|frame| {
+            matches!(&frame.statement.node, StatementNode::For { var: other, .. } if other == var)
+        });
+        if let Some(index) = position {
+            self.for_stack.truncate(index);
+        }
+    }
+
+    /// Run the next step. The program is finished after an error, which is
+    /// returned.
+    pub fn step(&mut self, io: &mut dyn Io) -> Result<(), RuntimeError> {
+        let result = self.run_step(io);
+        if result.is_err() {
+            self.block = EXIT;
+        }
+        result
+    }
+
+    fn run_step(&mut self, io: &mut dyn Io) -> Result<(), RuntimeError> {
+        let Some(step) = self.current() else {
+            return Ok(());
+        };
+        if let Some(line) = step.label() {
+            self.line = Some(line);
+            if self.trace {
+                io.print(&format!("[{}]", line));
+            }
+        }
+        let stmt = step.statement();
+        let last = self.index + 1 == self.cfg.blocks[self.block].steps.len();
+        let next = match (step, &stmt.node) {
+            (Step::Next(_), StatementNode::For { var, end, step, .. }) => {
+                let increment = match step {
+                    Some(step) => self.evaluate(step, io)?,
+                    None => 1.0,
+                };
+                let counter = self.variable(var) + increment;
+                let counter = self.round(counter, io)?;
+                self.variables.insert(var.clone(), counter);
+                let end = self.evaluate(end, io)?;
+                if counter <= end {
+                    self.successor(EdgeKind::Loop)?
+                } else {
+                    self.leave_loop(var);
+                    self.successor(EdgeKind::Fallthrough)?
+                }
+            }
+            (_, StatementNode::For { var, start, end, .. }) => {
+                let start = self.evaluate(start, io)?;
+                self.variables.insert(var.clone(), start);
+                // A loop over the same variable ends, with any inside it,
+                // as it did on GW-BASIC's stack
+                self.leave_loop(var);
+                let end = self.evaluate(end, io)?;
+                if start <= end {
+                    self.for_stack.push(ForFrame { statement: stmt });
+                    self.successor(EdgeKind::Fallthrough)?
+                } else {
+                    self.successor(EdgeKind::LoopExit)?
+                }
+            }
+            (_, StatementNode::If { left, op, right, .. }) => {
+                let left = self.evaluate(left, io)?;
+                let right = self.evaluate(right, io)?;
+                let kind = if Self::compare(left, op, right) { EdgeKind::Then } else { EdgeKind::Else };
+                self.successor(kind)?
+            }
+            (_, StatementNode::Goto(_)) => self.successor(EdgeKind::Goto)?,
+            (_, StatementNode::Gosub(_)) => {
+                if self.gosub_stack.len() == GOSUB_STACK_SIZE {
+                    return Err(self.error("Out of memory"));
+                }
+                let target = self.successor(EdgeKind::Gosub)?;
+                let back = self.cfg.return_point(self.block);
+                self.gosub_stack.push(GosubFrame { line: stmt.label, back });
+                target
+            }
+            (_, StatementNode::Return) => match self.gosub_stack.pop() {
+                Some(frame) => frame.back,
+                None => return Err(self.error("RETURN without GOSUB")),
+            },
+            (_, StatementNode::End) => EXIT,
+            (_, node) => {
+                if !self.execute(node, io)? {
+                    self.block = EXIT;
+                    return Ok(());
+                }
+                if !last {
+                    self.index += 1;
+                    return Ok(());
+                }
+                self.successor(EdgeKind::Fallthrough)?
+            }
+        };
+        self.go_to(next);
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+//! JavaScript generation, for `--target js`.
+//!
+//! The output is an ES module: the runtime from `runtime/basic_rt.js`,
+//! then the BASIC program as one async function. It exports
+//! `run({print, input})`, which prints through `print(text)` and awaits
+//! `input()` for each line typed, so a web page can run the program in a
+//! text area:
+//!
+//! ```text
+//! import { run } from "./prog.js";
+//! await run({ print: (text) => out.append(text), input: () => nextLine() });
+//! ```
+//!
+//! The program is written by the [`Emitter`] shared with Rust, as native
+//! loops or as `for (;;) { switch (pc) { ... } }`.
+//!
+//! Printing and input follow the C runtime exactly, but `^` is the
+//! engine's `Math.pow`, which can be a last place out from C's `pow`.
+
+use crate::emitter::{Emitter, Syntax};
+
+/// The runtime every generated module starts with.
+pub const RUNTIME: &str = include_str!("runtime/basic_rt.js");
+
+/// JavaScript's spelling of the generated code.
+pub struct Js;
+
+impl Syntax for Js {
+    const RUNTIME: &'static str = RUNTIME;
+    const RESERVED: &'static [&'static str] = &[
+        "BasicError", "End", "Math", "Runtime", "arguments", "await", "break", "case", "catch", "class", "const",
+        "continue", "debugger", "default", "delete", "do", "else", "enum", "eval", "export", "extends", "false",
+        "finally", "for", "function", "if", "implements", "import", "in", "instanceof", "interface", "let", "new",
+        "null", "package", "pc", "pow", "private", "protected", "public", "return", "rt", "static", "super", "switch",
+        "this", "throw", "true", "try", "typeof", "var", "void", "while", "with", "yield",
+    ];
+    const MATH: &'static str = "Math.";
+    const POWER: &'static str = "pow";
+    const TIMER: &'static str = "Runtime.timer()";
+    const EQUAL: &'static str = "===";
+    const NOT_EQUAL: &'static str = "!==";
+    const LABEL: &'static str = "";
+    const LOOP: &'static str = "for (;;)";
+    const EXIT: &'static str = "return;";
+    const FINISH: &'static str = "";
+    const PROGRAM: &'static str = "async function program(rt) {";
+    const PC: &'static str = "let pc";
+    const DISPATCH: &'static str = "switch (pc)";
+    const DEFAULT: &'static str = "default:\n    return;";
+    const END_CASE: Option<&'static str> = Some("break;");
+
+    fn case(index: usize) -> String {
+        format!("case {}:", index)
+    }
+
+    fn test(condition: &str) -> String {
+        format!("({})", condition)
+    }
+
+    /// The runtime's methods are Rust's names in camel case.
+    fn method(name: &str, args: &str) -> String {
+        let mut camel = String::new();
+        let mut words = name.split('_');
+        camel.extend(words.next());
+        for word in words {
+            let mut chars = word.chars();
+            camel.extend(chars.next().map(|ch| ch.to_ascii_uppercase()));
+            camel.extend(chars);
+        }
+        format!("rt.{}({})", camel, args)
+    }
+
+    fn checked(call: String) -> String {
+        call
+    }
+
+    fn awaited(call: String) -> String {
+        format!("await {}", call)
+    }
+
+    fn line(line: Option<i64>) -> String {
+        line.map_or("null".to_string(), |line| line.to_string())
+    }
+
+    fn raise(error: String) -> String {
+        format!("throw {};", error)
+    }
+
+    fn declare(var: &str) -> String {
+        format!("let {} = 0.0;", var)
+    }
+
+    fn constant(name: &str, value: usize) -> String {
+        format!("const {} = {};", name, value)
+    }
+}
+
+pub type JsGenerator = Emitter<Js>;
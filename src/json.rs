@@ -0,0 +1,346 @@
+use std::fmt;
+
+/// A JSON document, built in memory and written with `Display` (compact) or
+/// `pretty` (indented).
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Members keep their insertion order.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Build an object from `(key, value)` pairs.
+    pub fn object<const N: usize>(members: [(&str, JsonValue); N]) -> Self {
+        JsonValue::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn string(s: impl Into<String>) -> Self {
+        JsonValue::String(s.into())
+    }
+
+    /// Indented output, two spaces per level.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out, Some(0));
+        out
+    }
+
+    fn write(&self, out: &mut String, indent: Option<usize>) {
+        let newline = |out: &mut String, level: usize| {
+            if indent.is_some() {
+                out.push('\n');
+                out.push_str(&"  ".repeat(level));
+            }
+        };
+        let level = indent.unwrap_or(0);
+        let inner = indent.map(|i| i + 1);
+
+        match self {
+            JsonValue::Null => out.push_str("null"),
+            JsonValue::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            JsonValue::Number(n) => write_number(out, *n),
+            JsonValue::String(s) => write_string(out, s),
+            JsonValue::Array(items) => {
+                if items.is_empty() {
+                    out.push_str("[]");
+                    return;
+                }
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, level + 1);
+                    item.write(out, inner);
+                }
+                newline(out, level);
+                out.push(']');
+            }
+            JsonValue::Object(members) => {
+                if members.is_empty() {
+                    out.push_str("{}");
+                    return;
+                }
+                out.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, level + 1);
+                    write_string(out, key);
+                    out.push(':');
+                    if indent.is_some() {
+                        out.push(' ');
+                    }
+                    value.write(out, inner);
+                }
+                newline(out, level);
+                out.push('}');
+            }
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, None);
+        f.write_str(&out)
+    }
+}
+
+impl From<bool> for JsonValue {
+    fn from(b: bool) -> Self {
+        JsonValue::Bool(b)
+    }
+}
+
+impl From<i64> for JsonValue {
+    fn from(n: i64) -> Self {
+        JsonValue::Number(n as f64)
+    }
+}
+
+impl From<usize> for JsonValue {
+    fn from(n: usize) -> Self {
+        JsonValue::Number(n as f64)
+    }
+}
+
+impl From<f64> for JsonValue {
+    fn from(n: f64) -> Self {
+        JsonValue::Number(n)
+    }
+}
+
+impl<T: Into<JsonValue>> From<Option<T>> for JsonValue {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(JsonValue::Null, Into::into)
+    }
+}
+
+fn write_number(out: &mut String, n: f64) {
+    if !n.is_finite() {
+        // JSON has no infinities or NaN
+        out.push_str("null");
+    } else if n.fract() == 0.0 && n.abs() < 9007199254740992.0 {
+        out.push_str(&(n as i64).to_string());
+    } else {
+        out.push_str(&n.to_string());
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+impl JsonValue {
+    /// Parse a complete JSON document.
+    pub fn parse(text: &str) -> Result<JsonValue, String> {
+        let mut parser = JsonParser { chars: text.chars().collect(), position: 0 };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return Err(format!("trailing characters at {}", parser.position));
+        }
+        Ok(value)
+    }
+
+    /// Member `key` of an object, or `Null`.
+    pub fn get(&self, key: &str) -> &JsonValue {
+        match self {
+            JsonValue::Object(members) => members
+                .iter()
+                .find(|(k, _)| k == key)
+                .map_or(&JsonValue::Null, |(_, v)| v),
+            _ => &JsonValue::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Number(n) if n.fract() == 0.0 => Some(*n as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == JsonValue::Null
+    }
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl JsonParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() != Some(expected) {
+            return Err(format!("expected '{}' at {}", expected, self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn expect_word(&mut self, word: &str, value: JsonValue) -> Result<JsonValue, String> {
+        for ch in word.chars() {
+            self.expect(ch)?;
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.expect_word("null", JsonValue::Null),
+            Some('t') => self.expect_word("true", JsonValue::Bool(true)),
+            Some('f') => self.expect_word("false", JsonValue::Bool(false)),
+            Some('"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some('[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.position += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.parse_value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.position += 1,
+                        Some(']') => {
+                            self.position += 1;
+                            return Ok(JsonValue::Array(items));
+                        }
+                        _ => return Err(format!("expected ',' or ']' at {}", self.position)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.position += 1;
+                    return Ok(JsonValue::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.parse_string()?;
+                    self.skip_whitespace();
+                    self.expect(':')?;
+                    members.push((key, self.parse_value()?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(',') => self.position += 1,
+                        Some('}') => {
+                            self.position += 1;
+                            return Ok(JsonValue::Object(members));
+                        }
+                        _ => return Err(format!("expected ',' or '}}' at {}", self.position)),
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.position;
+                while matches!(self.peek(), Some(c) if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                    self.position += 1;
+                }
+                let text: String = self.chars[start..self.position].iter().collect();
+                text.parse().map(JsonValue::Number).map_err(|_| format!("bad number '{}'", text))
+            }
+            _ => Err(format!("unexpected character at {}", self.position)),
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.get(self.position..self.position + 4).unwrap_or_default().iter().collect();
+        self.position += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("bad \\u escape '{}'", digits))
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let ch = self.peek().ok_or("unterminated string")?;
+            self.position += 1;
+            match ch {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escape = self.peek().ok_or("unterminated string")?;
+                    self.position += 1;
+                    match escape {
+                        'n' => s.push('\n'),
+                        'r' => s.push('\r'),
+                        't' => s.push('\t'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => {
+                            let mut code = self.parse_hex4()?;
+                            // Surrogate pair
+                            if (0xD800..0xDC00).contains(&code) && self.peek() == Some('\\') {
+                                self.position += 2;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            s.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        other => s.push(other),
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+    }
+}
@@ -1,10 +1,13 @@
+use crate::diagnostic::Diagnostic;
+
 /// Tokens for the BASIC language.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Identifier(String),
     String(String),
     Number(i64),
-    Float(f64),
+    /// A floating-point literal together with its text as written (e.g. `.1`).
+    Float(f64, String),
     // Operators
     OperatorAdd,
     OperatorSubtract,
@@ -76,7 +79,7 @@ pub enum Token {
     //Files,         // FILES statement             (6-78)
     For,           // FOR ... NEXT statement      (6-81)
     //Get,           // GET statement               (6-85/6-86)
-    //Gosub,         // GOSUB ... Return statement  (6-88)
+    Gosub,         // GOSUB ... Return statement  (6-88)
     Goto,          // GOTO statement              (6-90)
     If,            // IF ... THEN ... ELSE        (6-92)
     Input,         // INPUT statement             (6-96)
@@ -130,14 +133,14 @@ pub enum Token {
     //PrintNUsing,   // PRINT# USING statement      (6-192)
     //Pset,          // PSET statement              (6-194)
     //Put,           // PUT statement               (6-196/197)
-    //Randomize,     // RANDOMIZE statement         (6-199)
+    Randomize,     // RANDOMIZE statement         (6-199)
     //Read,          // READ statement              (6-201)
-    Rem,           // REM statement               (6-203)
+    Rem(String),   // REM statement               (6-203)
     //Renum,         // RENUM statement             (6-204)
     //Reset,         // RESET command               (6-205)
     //Restore,       // RESTORE statement           (6-206)
     //Resume,        // RESUME statement            (6-207)
-    //Return,        // GOSUB ... RETURN statement  (6-88)
+    Return,        // GOSUB ... RETURN statement  (6-88)
     //RmDir,         // RMDIR statement             (6-210)
     //Rset,          // RSET statement              (6-130)
     //Run,           // RUN command                 (6-212)
@@ -154,13 +157,13 @@ pub enum Token {
     //System,        // SYSTEM command              (6-235)
     Then,          // IF ... THEN ... ELSE        (6-92)
     //TimeS,         // TIME$ variable              (6-239)
-    //Timer,         // TIMER variable              (6-240)
+    Timer,         // TIMER variable              (6-240)
     //TimerOff,      // TIMER OFF statement         (6-159)
     //TimerOn,       // TIMER ON statement          (6-159)
     //TimerStop,     // TIMER STOP statement        (6-159)
     To,            // FOR ... NEXT statement      (6-81)
-    //Troff,         // TROFF statement             (6-241)
-    //Tron,          // TRON statement              (6-241)
+    Troff,         // TROFF statement             (6-241)
+    Tron,          // TRON statement              (6-241)
     //View,          // VIEW statement              (6-247)
     //ViewPrint,     // VIEW PRINT statement        (6-248)
     //Wait,          // WAIT statement              (6-249)
@@ -236,9 +239,28 @@ pub enum Token {
     //VarPtrSFunction,      // VARPTR$() Function    (6-245)
 }
 
+/// Keywords the lexer recognises.
+pub const KEYWORDS: &[&str] = &[
+    "ELSE", "END", "FOR", "GOSUB", "GOTO", "IF", "INPUT", "LET", "NEXT", "PRINT", "RANDOMIZE", "REM", "RETURN",
+    "STEP", "THEN", "TIMER", "TO", "TROFF", "TRON",
+];
+
+/// Where a token sits in the source: character offsets plus the 1-based
+/// line and column of its first character.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
+    line: usize,
+    column: usize,
+    errors: Vec<Diagnostic>,
 }
 
 impl Lexer {
@@ -246,6 +268,9 @@ impl Lexer {
         Lexer {
             input: input.chars().collect(),
             position: 0,
+            line: 1,
+            column: 1,
+            errors: Vec::new(),
         }
     }
 
@@ -259,6 +284,12 @@ impl Lexer {
     }
 
     fn advance(&mut self) {
+        if self.current_char() == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         self.position += 1;
     }
 
@@ -394,7 +425,7 @@ impl Lexer {
                 if ch == '.' {
                     // Start of a float like .1
                     self.advance();
-                    let mut num_string = String::from("0.");
+                    let mut num_string = String::from(".");
                     while let Some(digit) = self.current_char() {
                         if digit.is_ascii_digit() {
                             num_string.push(digit);
@@ -403,7 +434,8 @@ impl Lexer {
                             break;
                         }
                     }
-                    Token::Float(num_string.parse().unwrap_or(0.0))
+                    let value = format!("0{}", num_string).parse().unwrap_or(0.0);
+                    Token::Float(value, num_string)
                 } else {
                     let mut num_string = String::new();
                     let mut is_float = false;
@@ -420,7 +452,7 @@ impl Lexer {
                         }
                     }
                     if is_float {
-                        Token::Float(num_string.parse().unwrap_or(0.0))
+                        Token::Float(num_string.parse().unwrap_or(0.0), num_string)
                     } else {
                         Token::Number(num_string.parse().unwrap_or(0))
                     }
@@ -433,42 +465,66 @@ impl Lexer {
                     "ELSE" => Token::Else,
                     "END" => Token::End,
                     "FOR" => Token::For,
+                    "GOSUB" => Token::Gosub,
                     "GOTO" => Token::Goto,
                     "IF" => Token::If,
                     "INPUT" => Token::Input,
                     "LET" => Token::Let,
                     "NEXT" => Token::Next,
                     "PRINT" => Token::Print,
+                    "RANDOMIZE" => Token::Randomize,
+                    "RETURN" => Token::Return,
                     "THEN" => Token::Then,
+                    "TIMER" => Token::Timer,
                     "TO" => Token::To,
+                    "TROFF" => Token::Troff,
+                    "TRON" => Token::Tron,
                     "REM" => {
-                        // Skip until newline
+                        // The rest of the line is the comment
+                        let mut comment = String::new();
                         while let Some(c) = self.current_char() {
                             if c == '\n' { break; }
+                            comment.push(c);
                             self.advance();
                         }
-                        Token::Rem
+                        Token::Rem(comment.trim().to_string())
                     }
                     "STEP" => Token::Step,
                     _ => Token::Identifier(identifier),
                 }
             }
             Some(ch) => {
-                panic!("Unrecognized character: {}", ch);
+                let span = Span { start: self.position, end: self.position + 1, line: self.line, column: self.column };
+                self.errors.push(Diagnostic::error(format!("Unrecognized character: {}", ch), span));
+                self.advance();
+                self.next_token()
             }
         }
     }
 
-    pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tokens: Vec<Token> = Vec::new();
+    pub fn tokenize(&mut self) -> Result<Vec<Token>, Vec<Diagnostic>> {
+        let tokens = self.tokenize_spanned()?;
+        Ok(tokens.into_iter().map(|(token, _)| token).collect())
+    }
+
+    /// Like `tokenize`, but pairs every token with the span it was read from.
+    pub fn tokenize_spanned(&mut self) -> Result<Vec<(Token, Span)>, Vec<Diagnostic>> {
+        let mut tokens: Vec<(Token, Span)> = Vec::new();
         loop {
+            self.skip_whitespace();
+            let (start, line, column) = (self.position, self.line, self.column);
             let token = self.next_token();
+            let span = Span { start, end: self.position, line, column };
             if token == Token::Eof {
-                tokens.push(token);
+                tokens.push((token, span));
                 break;
             }
-            tokens.push(token);
+            tokens.push((token, span));
+        }
+        if self.errors.is_empty() {
+            Ok(tokens)
+        } else {
+            Err(std::mem::take(&mut self.errors))
         }
-        tokens
     }
 }
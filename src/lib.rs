@@ -0,0 +1,158 @@
+//! A compiler from line-numbered BASIC to C.
+//!
+//! The usual entry point is [`compile`]; the stages it strings together
+//! ([`Lexer`], [`Parser`], [`CodeGenerator`]) are public as well for tools that
+//! need the tokens or the AST.
+//!
+//! ```no_run
+//! let output = compiler::compile("10 PRINT 1+1\n20 END\n", &compiler::Options::default())
+//!     .expect("program has errors");
+//! println!("{}", String::from_utf8_lossy(&output.code));
+//! ```
+
+pub mod lexer;
+pub mod parser;
+pub mod codegen;
+pub mod renum;
+pub mod formatter;
+pub mod diagnostic;
+pub mod dialect;
+pub mod driver;
+pub mod json;
+pub mod serialize;
+pub mod builtins;
+pub mod lsp;
+pub mod lint;
+pub mod cfg;
+pub mod structure;
+pub mod fold;
+pub mod infer;
+pub mod dce;
+pub mod runtime;
+pub mod numbers;
+pub mod rustgen;
+pub mod wasm;
+pub mod asm;
+pub mod llvm;
+pub mod jsgen;
+pub mod interp;
+pub mod debugger;
+pub mod emitter;
+
+pub use asm::AsmGenerator;
+pub use codegen::{CodeGenerator, CodegenOptions};
+pub use diagnostic::{Diagnostic, Severity, WarningConfig};
+pub use dialect::Dialect;
+pub use jsgen::JsGenerator;
+pub use lexer::{Lexer, Span, Token};
+pub use llvm::LlvmGenerator;
+pub use numbers::Numbers;
+pub use parser::{BinOp, Expression, Parser, PrintItem, Statement, StatementNode};
+pub use rustgen::RustGenerator;
+pub use wasm::WasmGenerator;
+
+/// The language [`compile`] generates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Target {
+    #[default]
+    C,
+    /// A standalone Rust program with a `run(io)` entry point.
+    Rust,
+    /// A binary WebAssembly module exporting `run`, with printing and
+    /// input imported from the host.
+    Wasm,
+    /// A standalone x86-64 Linux program for the GNU assembler, with no C
+    /// library.
+    X86_64Asm,
+    /// An ES module exporting an async `run({print, input})`.
+    Js,
+    /// Textual LLVM IR calling the C runtime, for `--emit llvm-ir`.
+    LlvmIr,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "c" => Some(Target::C),
+            "rust" => Some(Target::Rust),
+            "wasm" => Some(Target::Wasm),
+            "x86_64-asm" => Some(Target::X86_64Asm),
+            "js" => Some(Target::Js),
+            _ => None,
+        }
+    }
+}
+
+/// Settings for [`parse`] and [`compile`].
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub dialect: Dialect,
+    pub target: Target,
+    pub warnings: WarningConfig,
+    pub codegen: CodegenOptions,
+    /// Skip constant folding and dead code elimination.
+    pub no_opt: bool,
+}
+
+/// A program that parsed without errors, with the warnings found on the way.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// The result of a successful compilation.
+#[derive(Debug, Clone)]
+pub struct Output {
+    /// The generated source, in the language of [`Options::target`], or
+    /// the binary module for [`Target::Wasm`].
+    pub code: Vec<u8>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Lex, parse and check `source`.
+///
+/// On failure every diagnostic is returned, warnings included, in source
+/// order.
+pub fn parse(source: &str, options: &Options) -> Result<Program, Vec<Diagnostic>> {
+    let tokens = Lexer::new(source).tokenize_spanned()?;
+    let statements = Parser::new(tokens).parse()?;
+
+    let mut diagnostics = options.dialect.check(&statements);
+    diagnostics.extend(builtins::check(&statements));
+    if !matches!(options.target, Target::C | Target::LlvmIr) {
+        diagnostics.extend(codegen::check_tracing(&statements));
+    }
+    diagnostics.extend(lint::check(&statements));
+    diagnostics.extend(fold::check(&statements));
+    let mut diagnostics = options.warnings.apply(diagnostics);
+    diagnostics.sort_by_key(|d| d.span.start);
+    if diagnostics.iter().any(|d| d.is_error()) {
+        return Err(diagnostics);
+    }
+    Ok(Program { statements, warnings: diagnostics })
+}
+
+/// Compile BASIC `source` to C (or another [`Target`]) in one call.
+pub fn compile(source: &str, options: &Options) -> Result<Output, Vec<Diagnostic>> {
+    let mut program = parse(source, options)?;
+    if !options.no_opt {
+        let numbers = options.codegen.numbers;
+        fold::fold(&mut program.statements, numbers);
+        // MBF arithmetic reports overflow whether or not checks are on
+        let raises_errors = options.codegen.checks || numbers == Numbers::Mbf;
+        // Traced lines print their numbers even when they do nothing else
+        let traces = codegen::traces(&program.statements, &options.codegen);
+        dce::eliminate(&mut program.statements, raises_errors, traces);
+    }
+    let codegen = CodegenOptions { dialect: options.dialect, ..options.codegen.clone() };
+    let code = match options.target {
+        Target::C => CodeGenerator::new(codegen).generate(&program.statements).into_bytes(),
+        Target::Rust => RustGenerator::new(codegen).generate(&program.statements).into_bytes(),
+        Target::Wasm => WasmGenerator::new(codegen).generate(&program.statements),
+        Target::X86_64Asm => AsmGenerator::new(codegen).generate(&program.statements).into_bytes(),
+        Target::Js => JsGenerator::new(codegen).generate(&program.statements).into_bytes(),
+        Target::LlvmIr => LlvmGenerator::new(codegen).generate(&program.statements).into_bytes(),
+    };
+    Ok(Output { code, warnings: program.warnings })
+}
@@ -0,0 +1,322 @@
+//! Warnings for mistakes that are legal BASIC but rarely intended.
+//!
+//! Every warning has a stable code that `-W no-<code>` turns off:
+//!
+//! | Code                    | Reports                                            |
+//! |-------------------------|----------------------------------------------------|
+//! | `uninitialized`         | a variable read before any path assigns it         |
+//! | `unreachable-code`      | statements no path from the start can reach        |
+//! | `unused-variable`       | a variable that is assigned but never read         |
+//! | `line-order`            | a line number lower than the one before it         |
+//! | `duplicate-line`        | a line number used twice                           |
+//! | `for-variable-modified` | an assignment to a FOR variable inside its loop    |
+//! | `missing-return`        | a GOSUB whose subroutine never reaches RETURN      |
+
+use crate::cfg::{Cfg, EdgeKind, Step};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{Expression, PrintItem, Statement, StatementNode};
+use std::collections::BTreeSet;
+
+/// Run every lint over the program.
+pub fn check(statements: &[Statement]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check_line_numbers(statements, &mut diagnostics);
+    check_for_variables(statements, &mut diagnostics);
+
+    let cfg = Cfg::new(statements);
+    check_unreachable(&cfg, &mut diagnostics);
+    check_uninitialized(&cfg, &mut diagnostics);
+    check_unused(&cfg, &mut diagnostics);
+    check_returns(&cfg, &mut diagnostics);
+    diagnostics
+}
+
+/// Every line label in source order, including those on NEXT lines.
+fn collect_labels(statements: &[Statement], labels: &mut Vec<(i64, Span)>) {
+    for stmt in statements {
+        if let Some(label) = stmt.label {
+            labels.push((label, stmt.span));
+        }
+        if let StatementNode::For { body, next_label, .. } = &stmt.node {
+            collect_labels(body, labels);
+            if let Some(label) = next_label {
+                // The NEXT line has no span of its own; point at the FOR
+                labels.push((*label, stmt.span));
+            }
+        }
+    }
+}
+
+fn check_line_numbers(statements: &[Statement], diagnostics: &mut Vec<Diagnostic>) {
+    let mut labels = Vec::new();
+    collect_labels(statements, &mut labels);
+
+    let mut seen = BTreeSet::new();
+    let mut previous: Option<i64> = None;
+    for (label, span) in labels {
+        if !seen.insert(label) {
+            diagnostics.push(Diagnostic::warning(
+                "duplicate-line",
+                format!("Line {} is defined more than once", label),
+                span,
+            ));
+        } else if let Some(previous) = previous
+            && label < previous
+        {
+            diagnostics.push(Diagnostic::warning(
+                "line-order",
+                format!("Line {} comes after line {}", label, previous),
+                span,
+            ));
+        }
+        previous = Some(previous.map_or(label, |p| p.max(label)));
+    }
+}
+
+fn check_for_variables(statements: &[Statement], diagnostics: &mut Vec<Diagnostic>) {
+    for stmt in statements {
+        if let StatementNode::For { var, body, .. } = &stmt.node {
+            for inner in body {
+                check_assignments_to(var, inner, diagnostics);
+            }
+            check_for_variables(body, diagnostics);
+        }
+    }
+}
+
+/// Warn about every assignment to the loop variable `var` within `stmt`.
+fn check_assignments_to(var: &str, stmt: &Statement, diagnostics: &mut Vec<Diagnostic>) {
+    match &stmt.node {
+        StatementNode::Let { var: assigned, .. } | StatementNode::Input(assigned) if assigned == var => {
+            diagnostics.push(Diagnostic::warning(
+                "for-variable-modified",
+                format!("FOR variable {} is modified inside its loop", var),
+                stmt.span,
+            ));
+        }
+        StatementNode::For { var: inner, body, .. } => {
+            if inner == var {
+                diagnostics.push(Diagnostic::warning(
+                    "for-variable-modified",
+                    format!("Nested FOR reuses the loop variable {}", var),
+                    stmt.span,
+                ));
+            }
+            for inner in body {
+                check_assignments_to(var, inner, diagnostics);
+            }
+        }
+        StatementNode::If { then_part, .. } => check_assignments_to(var, then_part, diagnostics),
+        _ => {}
+    }
+}
+
+/// Every variable `expr` reads, in order.
+pub(crate) fn expression_reads(expr: &Expression, reads: &mut Vec<String>) {
+    match expr {
+        Expression::Variable(name) => reads.push(name.clone()),
+        Expression::BinaryOp { left, right, .. } => {
+            expression_reads(left, reads);
+            expression_reads(right, reads);
+        }
+        Expression::FunctionCall { args, .. } => {
+            for arg in args {
+                expression_reads(arg, reads);
+            }
+        }
+        Expression::Number(_) | Expression::Float(..) => {}
+    }
+}
+
+/// The variables a step reads, in order, and the one it assigns.
+fn step_uses(step: &Step) -> (Vec<String>, Option<String>) {
+    let mut reads = Vec::new();
+    let writes = match step {
+        Step::Next(stmt) => match &stmt.node {
+            StatementNode::For { var, .. } => {
+                reads.push(var.clone());
+                Some(var.clone())
+            }
+            _ => None,
+        },
+        Step::Statement(stmt) => match &stmt.node {
+            StatementNode::Let { var, value } => {
+                expression_reads(value, &mut reads);
+                Some(var.clone())
+            }
+            StatementNode::Print { items, .. } => {
+                for item in items {
+                    if let PrintItem::Expr(expr) = item {
+                        expression_reads(expr, &mut reads);
+                    }
+                }
+                None
+            }
+            StatementNode::Input(var) => Some(var.clone()),
+            StatementNode::Randomize(Some(seed)) => {
+                expression_reads(seed, &mut reads);
+                None
+            }
+            StatementNode::If { left, right, .. } => {
+                expression_reads(left, &mut reads);
+                expression_reads(right, &mut reads);
+                None
+            }
+            StatementNode::For { var, start, end, step, .. } => {
+                expression_reads(start, &mut reads);
+                expression_reads(end, &mut reads);
+                if let Some(step) = step {
+                    expression_reads(step, &mut reads);
+                }
+                Some(var.clone())
+            }
+            _ => None,
+        },
+    };
+    (reads, writes)
+}
+
+fn check_unreachable(cfg: &Cfg, diagnostics: &mut Vec<Diagnostic>) {
+    let reachable = cfg.reachable();
+    // One warning per run of unreachable statements
+    let mut in_run = false;
+    for (index, block) in cfg.statement_blocks() {
+        if reachable[index] {
+            in_run = false;
+            continue;
+        }
+        for step in &block.steps {
+            let Step::Statement(stmt) = step else {
+                continue;
+            };
+            if in_run || matches!(stmt.node, StatementNode::Rem(_) | StatementNode::Empty) {
+                continue;
+            }
+            in_run = true;
+            let message = match stmt.label {
+                Some(label) => format!("Line {} is never reached", label),
+                None => "Statement is never reached".to_string(),
+            };
+            diagnostics.push(Diagnostic::warning("unreachable-code", message, stmt.span));
+        }
+    }
+}
+
+/// For each block, the variables some path from the start has assigned on
+/// the way in.
+fn assigned_on_entry(cfg: &Cfg) -> Vec<BTreeSet<String>> {
+    let mut assigned = vec![BTreeSet::new(); cfg.blocks.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (index, block) in cfg.blocks.iter().enumerate() {
+            let mut out = assigned[index].clone();
+            out.extend(block.steps.iter().filter_map(|step| step_uses(step).1));
+            for edge in &block.successors {
+                let before = assigned[edge.target].len();
+                assigned[edge.target].extend(out.iter().cloned());
+                changed |= assigned[edge.target].len() != before;
+            }
+        }
+    }
+    assigned
+}
+
+fn check_uninitialized(cfg: &Cfg, diagnostics: &mut Vec<Diagnostic>) {
+    let assigned_on_entry = assigned_on_entry(cfg);
+    let reachable = cfg.reachable();
+    let mut reported = BTreeSet::new();
+    for (index, block) in cfg.statement_blocks() {
+        if !reachable[index] {
+            continue;
+        }
+        let mut assigned = assigned_on_entry[index].clone();
+        for step in &block.steps {
+            let (reads, writes) = step_uses(step);
+            for var in reads {
+                if !assigned.contains(&var) && reported.insert(var.clone()) {
+                    diagnostics.push(Diagnostic::warning(
+                        "uninitialized",
+                        format!("{} is read before it is assigned", var),
+                        step.statement().span,
+                    ));
+                }
+            }
+            assigned.extend(writes);
+        }
+    }
+}
+
+fn check_unused(cfg: &Cfg, diagnostics: &mut Vec<Diagnostic>) {
+    let steps: Vec<&Step> = cfg.statement_blocks().flat_map(|(_, block)| &block.steps).collect();
+    // A FOR variable is used by its NEXT, which does not count as a read
+    // here so that assigning it elsewhere is still reported
+    let read: BTreeSet<String> = steps
+        .iter()
+        .filter(|step| matches!(step, Step::Statement(_)))
+        .flat_map(|step| step_uses(step).0)
+        .collect();
+    let mut reported = BTreeSet::new();
+    for step in steps {
+        let Step::Statement(stmt) = step else {
+            continue;
+        };
+        if matches!(stmt.node, StatementNode::For { .. }) {
+            continue;
+        }
+        if let Some(var) = step_uses(step).1
+            && !read.contains(&var)
+            && reported.insert(var.clone())
+        {
+            diagnostics.push(Diagnostic::warning(
+                "unused-variable",
+                format!("{} is assigned but never used", var),
+                stmt.span,
+            ));
+        }
+    }
+}
+
+fn check_returns(cfg: &Cfg, diagnostics: &mut Vec<Diagnostic>) {
+    for (_, block) in cfg.statement_blocks() {
+        let Some(Step::Statement(stmt)) = block.steps.last() else {
+            continue;
+        };
+        let StatementNode::Gosub(target) = stmt.node else {
+            continue;
+        };
+        let Some(edge) = block.successors.iter().find(|edge| edge.kind == EdgeKind::Gosub) else {
+            continue;
+        };
+        if !reaches_return(cfg, edge.target) {
+            diagnostics.push(Diagnostic::warning(
+                "missing-return",
+                format!("Subroutine at line {} never reaches a RETURN", target),
+                stmt.span,
+            ));
+        }
+    }
+}
+
+/// Whether some path from block `start` ends in a RETURN. Nested GOSUBs are
+/// assumed to come back.
+fn reaches_return(cfg: &Cfg, start: usize) -> bool {
+    let mut seen = vec![false; cfg.blocks.len()];
+    let mut stack = vec![start];
+    while let Some(index) = stack.pop() {
+        if index >= cfg.blocks.len() || seen[index] {
+            continue;
+        }
+        seen[index] = true;
+        let block = &cfg.blocks[index];
+        match block.steps.last() {
+            Some(Step::Statement(Statement { node: StatementNode::Return, .. })) => return true,
+            // Blocks are in source order, so the statement after the GOSUB
+            // starts the next block
+            Some(Step::Statement(Statement { node: StatementNode::Gosub(_), .. })) => stack.push(index + 1),
+            _ => stack.extend(block.successors.iter().map(|edge| edge.target)),
+        }
+    }
+    false
+}
@@ -0,0 +1,632 @@
+//! LLVM IR generation, for `--emit llvm-ir`.
+//!
+//! The program becomes one `main` function in textual IR, with a basic
+//! block for each CFG block, named after the BASIC line it starts on.
+//! Variables live in `alloca`s, so there are no `phi`s to work out; LLVM's
+//! mem2reg pass turns them into registers. Everything else is a call into
+//! the C runtime, `basic_rt.c`, which is declared but not defined here.
+//! Pointers are opaque `ptr`s, so it needs LLVM 15 or later:
+//!
+//! ```text
+//! clang -O2 prog.ll basic_rt.c -lm
+//! ```
+//!
+//! `--checks`, `--numbers mbf`, `--seed` and `--trace` work as they do
+//! for C. With `-g` each instruction carries the BASIC line it came from,
+//! as DWARF line tables.
+
+use crate::cfg::{Cfg, EdgeKind, Step, ENTRY, EXIT};
+use crate::codegen::{self, CodegenOptions, GOSUB_STACK_SIZE};
+use crate::lexer::Token;
+use crate::numbers::Numbers;
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// How many metadata nodes come before the `!DILocation`s.
+const DEBUG_METADATA: usize = 6;
+
+/// Every function the generated code may call, with its declaration.
+const DECLARATIONS: &[(&str, &str)] = &[
+    ("rt_init", "declare void @rt_init(i32)"),
+    ("rt_error", "declare void @rt_error(ptr, i32)"),
+    ("rt_trace", "declare void @rt_trace(i32)"),
+    ("rt_print_number", "declare void @rt_print_number(double)"),
+    ("rt_print_single", "declare void @rt_print_single(double)"),
+    ("rt_print_string", "declare void @rt_print_string(ptr)"),
+    ("rt_print_newline", "declare void @rt_print_newline()"),
+    ("rt_input", "declare void @rt_input(ptr)"),
+    ("rt_rnd", "declare double @rt_rnd(double)"),
+    ("rt_randomize", "declare void @rt_randomize(double)"),
+    ("rt_randomize_timer", "declare void @rt_randomize_timer()"),
+    ("rt_randomize_prompt", "declare void @rt_randomize_prompt()"),
+    ("rt_fix_seed", "declare void @rt_fix_seed(double)"),
+    ("rt_timer", "declare double @rt_timer()"),
+    ("rt_add", "declare double @rt_add(double, double)"),
+    ("rt_sub", "declare double @rt_sub(double, double)"),
+    ("rt_mul", "declare double @rt_mul(double, double)"),
+    ("rt_div", "declare double @rt_div(double, double)"),
+    ("rt_pow", "declare double @rt_pow(double, double)"),
+    ("rt_sqr", "declare double @rt_sqr(double)"),
+    ("rt_exp", "declare double @rt_exp(double)"),
+    ("rt_mbf", "declare double @rt_mbf(double)"),
+    ("llvm.floor.f64", "declare double @llvm.floor.f64(double)"),
+    ("llvm.sqrt.f64", "declare double @llvm.sqrt.f64(double)"),
+    ("llvm.exp.f64", "declare double @llvm.exp.f64(double)"),
+    ("llvm.fabs.f64", "declare double @llvm.fabs.f64(double)"),
+    ("llvm.pow.f64", "declare double @llvm.pow.f64(double, double)"),
+];
+
+pub struct LlvmGenerator {
+    options: CodegenOptions,
+    variables: BTreeSet<String>,
+    /// The body of `main` so far.
+    code: String,
+    /// The last `%tN` register used.
+    temporaries: usize,
+    /// The runtime functions and intrinsics called, to declare.
+    called: BTreeSet<&'static str>,
+    strings: Vec<String>,
+    string_indices: HashMap<String, usize>,
+    /// The name of each block's label.
+    labels: Vec<String>,
+    /// The blocks a RETURN can go back to, by the number GOSUB pushes.
+    return_points: BTreeMap<usize, usize>,
+    uses_return: bool,
+    /// Lines with a jump to a line that does not exist, each getting a
+    /// block that reports it.
+    undefined: BTreeSet<Option<i64>>,
+    /// Whether lines call `rt_trace` as they start.
+    traces: bool,
+    /// With `-g`, the source line the instructions being emitted come from.
+    location: Option<usize>,
+    /// The metadata number of the `!DILocation` for each source line used.
+    locations: BTreeMap<usize, usize>,
+}
+
+impl LlvmGenerator {
+    pub fn new(options: CodegenOptions) -> Self {
+        LlvmGenerator {
+            options,
+            variables: BTreeSet::new(),
+            code: String::new(),
+            temporaries: 0,
+            called: BTreeSet::new(),
+            strings: Vec::new(),
+            string_indices: HashMap::new(),
+            labels: Vec::new(),
+            return_points: BTreeMap::new(),
+            uses_return: false,
+            undefined: BTreeSet::new(),
+            traces: false,
+            location: None,
+            locations: BTreeMap::new(),
+        }
+    }
+
+    fn collect_variables(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            self.collect_variables_node(&stmt.node);
+        }
+    }
+
+    fn collect_variables_node(&mut self, node: &StatementNode) {
+        match node {
+            StatementNode::Let { var, .. } | StatementNode::Input(var) => {
+                self.variables.insert(var.clone());
+            }
+            StatementNode::For { var, body, .. } => {
+                self.variables.insert(var.clone());
+                self.collect_variables(body);
+            }
+            StatementNode::If { then_part, .. } => self.collect_variables_node(&then_part.node),
+            _ => {}
+        }
+    }
+
+    /// The `alloca` holding a variable. Names LLVM does not take as they
+    /// are go in quotes.
+    fn variable(name: &str) -> String {
+        if name.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '_') {
+            format!("%v.{}", name)
+        } else {
+            format!("%\"v.{}\"", name)
+        }
+    }
+
+    /// Add an instruction to `main`.
+    fn emit(&mut self, instruction: &str) {
+        self.code.push_str("  ");
+        self.code.push_str(instruction);
+        if let Some(line) = self.location {
+            let next = DEBUG_METADATA + self.locations.len();
+            let id = *self.locations.entry(line).or_insert(next);
+            self.code.push_str(&format!(", !dbg !{}", id));
+        }
+        self.code.push('\n');
+    }
+
+    /// With `-g`, mark what is emitted next as coming from `stmt`.
+    fn locate(&mut self, stmt: &Statement) {
+        if self.options.line_directives.is_some() {
+            self.location = Some(stmt.span.line);
+        }
+    }
+
+    /// Add an instruction that computes a value, returning its register.
+    fn emit_value(&mut self, instruction: &str) -> String {
+        self.temporaries += 1;
+        let register = format!("%t{}", self.temporaries);
+        self.emit(&format!("{} = {}", register, instruction));
+        register
+    }
+
+    fn call(&mut self, function: &'static str, args: &str) -> String {
+        self.called.insert(function);
+        self.emit_value(&format!("call double @{}({})", function, args))
+    }
+
+    fn call_void(&mut self, function: &'static str, args: &str) {
+        self.called.insert(function);
+        self.emit(&format!("call void @{}({})", function, args));
+    }
+
+    /// A pointer to `text` as a C string.
+    fn string(&mut self, text: &str) -> String {
+        let next = self.strings.len();
+        let index = *self.string_indices.entry(text.to_string()).or_insert(next);
+        if index == next {
+            self.strings.push(text.to_string());
+        }
+        format!("@.str.{}", index)
+    }
+
+    /// A double constant. LLVM only takes decimals it can represent exactly,
+    /// so anything but a whole number is written by its bits.
+    fn double(value: f64) -> String {
+        if value.fract() == 0.0 && value.abs() < 9007199254740992.0 {
+            format!("{:?}", value)
+        } else {
+            format!("0x{:016X}", value.to_bits())
+        }
+    }
+
+    /// `value` rounded to the program's number format.
+    fn round(&mut self, value: String) -> String {
+        match self.options.numbers {
+            Numbers::Double => value,
+            Numbers::Mbf => self.call("rt_mbf", &format!("double {}", value)),
+        }
+    }
+
+    /// A constant, rounded to the number format; one too large for it is
+    /// left for the runtime to report.
+    fn constant(&mut self, value: f64) -> String {
+        match self.options.numbers.round(value) {
+            Some(rounded) => Self::double(rounded),
+            None => self.round(Self::double(value)),
+        }
+    }
+
+    /// Code computing `expr`, returning the register or constant holding it.
+    fn generate_expr(&mut self, expr: &Expression) -> String {
+        match expr {
+            Expression::Number(n) => self.constant(*n as f64),
+            Expression::Float(f, _) => self.constant(*f),
+            // A variable nothing assigns is always 0
+            Expression::Variable(name) if !self.variables.contains(name) => Self::double(0.0),
+            Expression::Variable(name) => self.emit_value(&format!("load double, ptr {}", Self::variable(name))),
+            Expression::BinaryOp { left, operator, right } => {
+                let left = self.generate_expr(left);
+                let right = self.generate_expr(right);
+                let args = format!("double {}, double {}", left, right);
+                let value = if self.options.checks {
+                    let helper = match operator {
+                        BinOp::Add => "rt_add",
+                        BinOp::Subtract => "rt_sub",
+                        BinOp::Multiply => "rt_mul",
+                        BinOp::Divide => "rt_div",
+                        BinOp::Power => "rt_pow",
+                    };
+                    self.call(helper, &args)
+                } else {
+                    match operator {
+                        BinOp::Add => self.emit_value(&format!("fadd double {}, {}", left, right)),
+                        BinOp::Subtract => self.emit_value(&format!("fsub double {}, {}", left, right)),
+                        BinOp::Multiply => self.emit_value(&format!("fmul double {}, {}", left, right)),
+                        BinOp::Divide => self.emit_value(&format!("fdiv double {}, {}", left, right)),
+                        BinOp::Power => self.call("llvm.pow.f64", &args),
+                    }
+                };
+                self.round(value)
+            }
+            Expression::FunctionCall { name, args } => {
+                let upper = name.to_uppercase();
+                let arg = match args.first() {
+                    Some(arg) => self.generate_expr(arg),
+                    // RND on its own is RND(1)
+                    None => Self::double(1.0),
+                };
+                let arg = format!("double {}", arg);
+                match upper.as_str() {
+                    "INT" => self.call("llvm.floor.f64", &arg),
+                    "ABS" => self.call("llvm.fabs.f64", &arg),
+                    "RND" => self.call("rt_rnd", &arg),
+                    "TIMER" => self.call("rt_timer", ""),
+                    "SQR" if self.options.checks => {
+                        let value = self.call("rt_sqr", &arg);
+                        self.round(value)
+                    }
+                    "EXP" if self.options.checks => {
+                        let value = self.call("rt_exp", &arg);
+                        self.round(value)
+                    }
+                    "SQR" => {
+                        let value = self.call("llvm.sqrt.f64", &arg);
+                        self.round(value)
+                    }
+                    _ => {
+                        let value = self.call("llvm.exp.f64", &arg);
+                        self.round(value)
+                    }
+                }
+            }
+        }
+    }
+
+    /// With `--checks`, record that line `label` is running so runtime
+    /// errors can name it.
+    fn track_line(&mut self, label: Option<i64>) {
+        if let Some(line) = label
+            && self.options.checks
+        {
+            self.emit(&format!("store i32 {}, ptr @rt_line", line));
+        }
+    }
+
+    /// When tracing, print line `label` as it starts.
+    fn trace_line(&mut self, label: Option<i64>) {
+        if let Some(line) = label
+            && self.traces
+        {
+            self.call_void("rt_trace", &format!("i32 {}", line));
+        }
+    }
+
+    /// Code for a statement that runs straight through.
+    fn generate_statement(&mut self, stmt: &Statement) {
+        match &stmt.node {
+            StatementNode::Let { var, value } => {
+                let value = self.generate_expr(value);
+                self.emit(&format!("store double {}, ptr {}", value, Self::variable(var)));
+            }
+            StatementNode::Print { items, newline } => {
+                for item in items {
+                    match item {
+                        PrintItem::String(s) => {
+                            let string = self.string(s);
+                            self.call_void("rt_print_string", &format!("ptr {}", string));
+                        }
+                        PrintItem::Expr(expr) => {
+                            let value = self.generate_expr(expr);
+                            let print = match self.options.numbers {
+                                Numbers::Double => "rt_print_number",
+                                Numbers::Mbf => "rt_print_single",
+                            };
+                            self.call_void(print, &format!("double {}", value));
+                        }
+                        PrintItem::Comma | PrintItem::Semicolon => {}
+                    }
+                }
+                if *newline {
+                    self.call_void("rt_print_newline", "");
+                }
+            }
+            StatementNode::Input(var) => {
+                self.call_void("rt_input", &format!("ptr {}", Self::variable(var)));
+                if self.options.numbers == Numbers::Mbf {
+                    let value = self.emit_value(&format!("load double, ptr {}", Self::variable(var)));
+                    let value = self.round(value);
+                    self.emit(&format!("store double {}, ptr {}", value, Self::variable(var)));
+                }
+            }
+            StatementNode::Randomize(None) => self.call_void("rt_randomize_prompt", ""),
+            StatementNode::Randomize(Some(Expression::FunctionCall { name, args })) if name == "TIMER" && args.is_empty() => {
+                self.call_void("rt_randomize_timer", "")
+            }
+            StatementNode::Randomize(Some(seed)) => {
+                let seed = self.generate_expr(seed);
+                self.call_void("rt_randomize", &format!("double {}", seed));
+            }
+            StatementNode::Rem(comment) if !comment.is_empty() => {
+                self.code.push_str(&format!("  ; {}\n", comment));
+            }
+            // --trace has tracing on from the start to the end
+            StatementNode::Tron if !self.options.trace => self.emit("store i32 1, ptr @rt_tracing"),
+            StatementNode::Troff if self.traces && !self.options.trace => self.emit("store i32 0, ptr @rt_tracing"),
+            _ => {}
+        }
+    }
+
+    /// The label a jump to `target` goes to: its block, or one reporting the
+    /// missing line.
+    fn label(&mut self, target: Option<usize>, line: Option<i64>) -> String {
+        match target {
+            Some(target) => format!("%{}", self.labels[target]),
+            None => {
+                self.undefined.insert(line);
+                format!("%{}", Self::undefined_label(line))
+            }
+        }
+    }
+
+    fn undefined_label(line: Option<i64>) -> String {
+        match line {
+            Some(line) => format!("undefined{}", line),
+            None => "undefined".to_string(),
+        }
+    }
+
+    fn goto(&mut self, target: Option<usize>, line: Option<i64>) {
+        let label = self.label(target, line);
+        self.emit(&format!("br label {}", label));
+    }
+
+    /// Compare `left` and `right`, going to `then` if `op` holds between
+    /// them or to `otherwise`. As in C, only `<>` holds for NaN.
+    fn branch(
+        &mut self,
+        left: &Expression,
+        op: &Token,
+        right: &Expression,
+        then: Option<usize>,
+        otherwise: Option<usize>,
+        stmt: &Statement,
+    ) {
+        let left = self.generate_expr(left);
+        let right = self.generate_expr(right);
+        let predicate = match op {
+            Token::NotEqual => "une",
+            Token::LessThan => "olt",
+            Token::LessOrEqual => "ole",
+            Token::GreaterThan => "ogt",
+            Token::GreaterOrEqual => "oge",
+            _ => "oeq",
+        };
+        let condition = self.emit_value(&format!("fcmp {} double {}, {}", predicate, left, right));
+        let then = self.label(then, stmt.label);
+        let otherwise = self.label(otherwise, stmt.label);
+        self.emit(&format!("br i1 {}, label {}, label {}", condition, then, otherwise));
+    }
+
+    /// The code of `block`, ending with the branch to the next one.
+    fn generate_block(&mut self, cfg: &Cfg, block: usize) {
+        let successor = |kind| cfg.blocks[block].successors.iter().find(|edge| edge.kind == kind).map(|edge| edge.target);
+        let Some(last) = cfg.blocks[block].steps.last() else {
+            return self.emit("ret i32 0");
+        };
+        for step in &cfg.blocks[block].steps {
+            if let Step::Statement(stmt) = step {
+                self.locate(stmt);
+                self.trace_line(stmt.label);
+                self.track_line(stmt.label);
+                self.generate_statement(stmt);
+            }
+        }
+        let stmt = last.statement();
+        let line = stmt.label;
+        match (last, &stmt.node) {
+            (Step::Next(_), StatementNode::For { var, end, step, .. }) => {
+                self.trace_line(last.label());
+                self.track_line(last.label());
+                let counter = self.emit_value(&format!("load double, ptr {}", Self::variable(var)));
+                let step = match step {
+                    Some(step) => self.generate_expr(step),
+                    None => Self::double(1.0),
+                };
+                let sum = self.emit_value(&format!("fadd double {}, {}", counter, step));
+                let sum = self.round(sum);
+                self.emit(&format!("store double {}, ptr {}", sum, Self::variable(var)));
+                let counter = Expression::Variable(var.clone());
+                self.branch(&counter, &Token::LessOrEqual, end, successor(EdgeKind::Loop), successor(EdgeKind::Fallthrough), stmt);
+            }
+            (_, StatementNode::For { var, start, end, .. }) => {
+                let start = self.generate_expr(start);
+                self.emit(&format!("store double {}, ptr {}", start, Self::variable(var)));
+                let counter = Expression::Variable(var.clone());
+                self.branch(&counter, &Token::LessOrEqual, end, successor(EdgeKind::Fallthrough), successor(EdgeKind::LoopExit), stmt);
+            }
+            (_, StatementNode::If { left, op, right, .. }) => {
+                self.branch(left, op, right, successor(EdgeKind::Then), successor(EdgeKind::Else), stmt);
+            }
+            (_, StatementNode::Goto(_)) => self.goto(successor(EdgeKind::Goto), line),
+            (_, StatementNode::Gosub(_)) => {
+                let back = cfg.return_point(block);
+                let index = self.return_points.len();
+                self.return_points.insert(index, back);
+                let sp = self.emit_value("load i32, ptr %gosub_sp");
+                let full = self.emit_value(&format!("icmp eq i32 {}, {}", sp, GOSUB_STACK_SIZE));
+                let push = format!("{}.push", self.labels[block]);
+                self.emit(&format!("br i1 {}, label %out_of_memory, label %{}", full, push));
+                self.code.push_str(&format!("\n{}:\n", push));
+                let slot = self.emit_value(&format!("getelementptr [{} x i32], ptr %gosub_stack, i32 0, i32 {}", GOSUB_STACK_SIZE, sp));
+                self.emit(&format!("store i32 {}, ptr {}", index, slot));
+                let sp = self.emit_value(&format!("add i32 {}, 1", sp));
+                self.emit(&format!("store i32 {}, ptr %gosub_sp", sp));
+                self.goto(successor(EdgeKind::Gosub), line);
+            }
+            (_, StatementNode::Return) => {
+                self.uses_return = true;
+                self.emit("br label %gosub_return");
+            }
+            (_, StatementNode::End) => self.emit("br label %exit"),
+            _ => self.goto(successor(EdgeKind::Fallthrough), None),
+        }
+    }
+
+    /// The block RETURN goes through to get back to its GOSUB.
+    fn generate_return_dispatch(&mut self) {
+        self.code.push_str("\ngosub_return:\n");
+        let sp = self.emit_value("load i32, ptr %gosub_sp");
+        let empty = self.emit_value(&format!("icmp eq i32 {}, 0", sp));
+        self.emit(&format!("br i1 {}, label %return_without_gosub, label %gosub_pop", empty));
+        self.code.push_str("\nreturn_without_gosub:\n");
+        let message = self.string("RETURN without GOSUB");
+        self.call_void("rt_error", &format!("ptr {}, i32 1", message));
+        self.emit("unreachable");
+        self.code.push_str("\ngosub_pop:\n");
+        let sp = self.emit_value(&format!("sub i32 {}, 1", sp));
+        self.emit(&format!("store i32 {}, ptr %gosub_sp", sp));
+        let slot = self.emit_value(&format!("getelementptr [{} x i32], ptr %gosub_stack, i32 0, i32 {}", GOSUB_STACK_SIZE, sp));
+        let index = self.emit_value(&format!("load i32, ptr {}", slot));
+        let cases: Vec<String> = self
+            .return_points
+            .clone()
+            .into_iter()
+            .map(|(index, block)| format!("    i32 {}, label %{}\n", index, self.labels[block]))
+            .collect();
+        self.emit(&format!("switch i32 {}, label %exit [\n{}  ]", index, cases.concat()));
+    }
+
+    /// Names for the blocks' labels: the BASIC line each starts on where it
+    /// is the first to.
+    fn name_blocks(&mut self, cfg: &Cfg) {
+        let mut used = BTreeSet::new();
+        for block in 0..cfg.blocks.len() {
+            let name = match (block, cfg.blocks[block].steps.first().and_then(|step| step.label())) {
+                (ENTRY, _) => "start".to_string(),
+                (EXIT, _) => "exit".to_string(),
+                (_, Some(line)) if used.insert(line) => format!("line{}", line),
+                _ => format!("block{}", block),
+            };
+            self.labels.push(name);
+        }
+    }
+
+    /// `text` as the contents of an LLVM string constant, without the
+    /// terminating zero.
+    fn escape(text: &str) -> String {
+        let mut escaped = String::new();
+        for byte in text.bytes() {
+            match byte {
+                b'"' | b'\\' => escaped.push_str(&format!("\\{:02X}", byte)),
+                b' '..=b'~' => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{:02X}", byte)),
+            }
+        }
+        escaped
+    }
+
+    pub fn generate(&mut self, statements: &[Statement]) -> String {
+        self.collect_variables(statements);
+        self.traces = codegen::traces(statements, &self.options);
+        let cfg = Cfg::new(statements);
+        self.name_blocks(&cfg);
+
+        self.code.push_str("start:\n");
+        for var in self.variables.clone() {
+            self.emit(&format!("{} = alloca double", Self::variable(&var)));
+        }
+        self.emit(&format!("%gosub_stack = alloca [{} x i32]", GOSUB_STACK_SIZE));
+        self.emit("%gosub_sp = alloca i32");
+        for var in self.variables.clone() {
+            self.emit(&format!("store double 0.0, ptr {}", Self::variable(&var)));
+        }
+        self.emit("store i32 0, ptr %gosub_sp");
+        let continue_after_errors = self.options.dialect.continues_after_arithmetic_error();
+        self.call_void("rt_init", &format!("i32 {}", continue_after_errors as i32));
+        if let Some(seed) = self.options.seed {
+            self.call_void("rt_fix_seed", &format!("double {}", Self::double(seed as f64)));
+        }
+        if self.options.trace {
+            self.emit("store i32 1, ptr @rt_tracing");
+        }
+        let first = cfg.blocks[ENTRY].successors.first().map(|edge| edge.target);
+        self.goto(first, None);
+
+        for block in 0..cfg.blocks.len() {
+            if block == ENTRY {
+                continue;
+            }
+            let lines: Vec<i64> = cfg.blocks[block].steps.iter().filter_map(|step| step.label()).collect();
+            let comment = match (lines.first(), lines.last()) {
+                (Some(first), Some(last)) if first != last => format!("  ; {}-{}", first, last),
+                (Some(first), _) => format!("  ; {}", first),
+                _ => String::new(),
+            };
+            self.code.push_str(&format!("\n{}:{}\n", self.labels[block], comment));
+            self.generate_block(&cfg, block);
+        }
+        // What follows is not from any one line
+        self.location = None;
+        if self.uses_return {
+            self.generate_return_dispatch();
+        }
+        if !self.return_points.is_empty() {
+            self.code.push_str("\nout_of_memory:\n");
+            let message = self.string("Out of memory");
+            self.call_void("rt_error", &format!("ptr {}, i32 1", message));
+            self.emit("unreachable");
+        }
+        for line in self.undefined.clone() {
+            self.code.push_str(&format!("\n{}:\n", Self::undefined_label(line)));
+            if let Some(line) = line {
+                self.emit(&format!("store i32 {}, ptr @rt_line", line));
+            }
+            let message = self.string("Undefined line number");
+            self.call_void("rt_error", &format!("ptr {}, i32 1", message));
+            self.emit("unreachable");
+        }
+
+        let mut result = String::from("; Generated from BASIC; link with the C runtime, basic_rt.c\n\n");
+        for (index, text) in self.strings.iter().enumerate() {
+            result.push_str(&format!(
+                "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+                index,
+                text.len() + 1,
+                Self::escape(text)
+            ));
+        }
+        if self.options.checks || !self.undefined.is_empty() {
+            result.push_str("@rt_line = external global i32\n");
+        }
+        if self.traces {
+            result.push_str("@rt_tracing = external global i32\n");
+        }
+        result.push('\n');
+        for (name, declaration) in DECLARATIONS {
+            if self.called.contains(name) {
+                result.push_str(declaration);
+                result.push('\n');
+            }
+        }
+        let debug = if self.options.line_directives.is_some() { " !dbg !2" } else { "" };
+        result.push_str(&format!("\ndefine i32 @main(){} {{\n", debug));
+        result.push_str(&self.code);
+        result.push_str("}\n");
+        if let Some(file) = &self.options.line_directives {
+            result.push_str(&self.debug_metadata(file));
+        }
+        result
+    }
+
+    /// The compile unit, file and `main` the `!dbg` locations refer to.
+    fn debug_metadata(&self, file: &str) -> String {
+        let mut metadata = String::from("\n!llvm.dbg.cu = !{!0}\n!llvm.module.flags = !{!4, !5}\n\n");
+        metadata.push_str(
+            "!0 = distinct !DICompileUnit(language: DW_LANG_C99, file: !1, producer: \"basic\", \
+             isOptimized: false, runtimeVersion: 0, emissionKind: LineTablesOnly)\n",
+        );
+        metadata.push_str(&format!("!1 = !DIFile(filename: \"{}\", directory: \"\")\n", Self::escape(file)));
+        metadata.push_str(
+            "!2 = distinct !DISubprogram(name: \"main\", scope: !1, file: !1, line: 1, type: !3, scopeLine: 1, \
+             spFlags: DISPFlagDefinition, unit: !0)\n",
+        );
+        metadata.push_str("!3 = !DISubroutineType(types: !{})\n");
+        metadata.push_str("!4 = !{i32 7, !\"Dwarf Version\", i32 4}\n");
+        metadata.push_str("!5 = !{i32 2, !\"Debug Info Version\", i32 3}\n");
+        for (line, id) in &self.locations {
+            metadata.push_str(&format!("!{} = !DILocation(line: {}, column: 1, scope: !2)\n", id, line));
+        }
+        metadata
+    }
+}
@@ -0,0 +1,460 @@
+//! A Language Server Protocol server for BASIC sources, spoken over any
+//! reader/writer pair (normally stdin/stdout).
+//!
+//! Documents are synchronised in full on every change. Positions are
+//! converted between LSP's UTF-16 columns and the lexer's character offsets.
+
+use crate::builtins::{self, BUILTINS};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::formatter::{FormatOptions, Formatter};
+use crate::json::JsonValue;
+use crate::lexer::{Lexer, Span, Token, KEYWORDS};
+use crate::renum::{line_labels, line_references};
+use crate::Options;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+const SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// Read one `Content-Length` framed message. Returns `None` at end of input,
+/// and why not for a message that has no length or is not JSON, which the
+/// server answers before reading on.
+fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Result<JsonValue, String>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(length) = length else {
+        return Ok(Some(Err("missing Content-Length header".to_string())));
+    };
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let text = String::from_utf8_lossy(&body);
+    Ok(Some(JsonValue::parse(&text)))
+}
+
+fn write_message(writer: &mut impl Write, message: &JsonValue) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Run the server until the client sends `exit`. Returns the process exit
+/// code the protocol asks for: 0 after a `shutdown`, 1 otherwise.
+pub fn serve(mut reader: impl BufRead, mut writer: impl Write) -> io::Result<i32> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut reader)? {
+        let replies = match message {
+            Ok(message) => server.handle(&message),
+            // Without the message there is no id to answer to
+            Err(error) => vec![error_response(&JsonValue::Null, PARSE_ERROR, &error)],
+        };
+        for reply in replies {
+            write_message(&mut writer, &reply)?;
+        }
+        if server.exited {
+            return Ok(if server.shut_down { 0 } else { 1 });
+        }
+    }
+    Ok(1)
+}
+
+fn response(id: &JsonValue, result: JsonValue) -> JsonValue {
+    JsonValue::object([
+        ("jsonrpc", JsonValue::string("2.0")),
+        ("id", id.clone()),
+        ("result", result),
+    ])
+}
+
+fn error_response(id: &JsonValue, code: i64, message: &str) -> JsonValue {
+    JsonValue::object([
+        ("jsonrpc", JsonValue::string("2.0")),
+        ("id", id.clone()),
+        ("error", JsonValue::object([("code", code.into()), ("message", JsonValue::string(message))])),
+    ])
+}
+
+fn notification(method: &str, params: JsonValue) -> JsonValue {
+    JsonValue::object([
+        ("jsonrpc", JsonValue::string("2.0")),
+        ("method", JsonValue::string(method)),
+        ("params", params),
+    ])
+}
+
+/// An open document and its text as characters.
+struct Document {
+    text: String,
+    chars: Vec<char>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let chars = text.chars().collect();
+        Document { text, chars }
+    }
+
+    /// LSP position (line, UTF-16 column) of a character offset.
+    fn position(&self, offset: usize) -> JsonValue {
+        let (mut line, mut character) = (0usize, 0usize);
+        for ch in self.chars.iter().take(offset) {
+            if *ch == '\n' {
+                line += 1;
+                character = 0;
+            } else {
+                character += ch.len_utf16();
+            }
+        }
+        JsonValue::object([("line", line.into()), ("character", character.into())])
+    }
+
+    /// Character offset of an LSP position.
+    fn offset(&self, position: &JsonValue) -> usize {
+        let line = position.get("line").as_i64().unwrap_or(0).max(0) as usize;
+        let character = position.get("character").as_i64().unwrap_or(0).max(0) as usize;
+        let mut offset = 0;
+        let mut current_line = 0;
+        while current_line < line && offset < self.chars.len() {
+            if self.chars[offset] == '\n' {
+                current_line += 1;
+            }
+            offset += 1;
+        }
+        let mut units = 0;
+        while units < character && offset < self.chars.len() && self.chars[offset] != '\n' {
+            units += self.chars[offset].len_utf16();
+            offset += 1;
+        }
+        offset
+    }
+
+    fn range(&self, span: &Span) -> JsonValue {
+        JsonValue::object([("start", self.position(span.start)), ("end", self.position(span.end))])
+    }
+
+    fn tokens(&self) -> Option<Vec<(Token, Span)>> {
+        Lexer::new(&self.text).tokenize_spanned().ok()
+    }
+}
+
+/// The token under `offset`, also matching when the cursor sits just after it.
+fn token_at(tokens: &[(Token, Span)], offset: usize) -> Option<usize> {
+    tokens
+        .iter()
+        .position(|(token, span)| span.start <= offset && offset < span.end && *token != Token::Newline)
+        .or_else(|| {
+            tokens
+                .iter()
+                .position(|(token, span)| {
+                    span.end == offset && matches!(token, Token::Number(_) | Token::Identifier(_) | Token::Timer)
+                })
+        })
+}
+
+/// Whether the identifier at `index` names a function call rather than a variable.
+fn is_call(tokens: &[(Token, Span)], index: usize) -> bool {
+    matches!(tokens.get(index + 1), Some((Token::LeftParen, _)))
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    initialized: bool,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    fn new() -> Self {
+        Server {
+            documents: HashMap::new(),
+            initialized: false,
+            shut_down: false,
+            exited: false,
+        }
+    }
+
+    /// Handle one incoming message, returning the messages to send back.
+    fn handle(&mut self, message: &JsonValue) -> Vec<JsonValue> {
+        let method = message.get("method").as_str().unwrap_or("");
+        let id = message.get("id");
+        let params = message.get("params");
+        let is_request = !id.is_null();
+
+        if method == "exit" {
+            self.exited = true;
+            return Vec::new();
+        }
+        if !self.initialized && method != "initialize" {
+            return if is_request {
+                vec![error_response(id, SERVER_NOT_INITIALIZED, "server not initialized")]
+            } else {
+                Vec::new()
+            };
+        }
+        if self.shut_down && is_request {
+            return vec![error_response(id, INVALID_REQUEST, "server is shutting down")];
+        }
+
+        match method {
+            "initialize" => {
+                self.initialized = true;
+                vec![response(id, Self::capabilities())]
+            }
+            "shutdown" => {
+                self.shut_down = true;
+                vec![response(id, JsonValue::Null)]
+            }
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                let uri = document.get("uri").as_str().unwrap_or("").to_string();
+                let text = document.get("text").as_str().unwrap_or("").to_string();
+                self.documents.insert(uri.clone(), Document::new(text));
+                vec![self.publish_diagnostics(&uri)]
+            }
+            "textDocument/didChange" => {
+                let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+                // Full synchronisation: the last change holds the whole text
+                let text = params
+                    .get("contentChanges")
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text").as_str());
+                if let Some(text) = text {
+                    self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                }
+                vec![self.publish_diagnostics(&uri)]
+            }
+            "textDocument/didClose" => {
+                let uri = params.get("textDocument").get("uri").as_str().unwrap_or("").to_string();
+                self.documents.remove(&uri);
+                vec![notification(
+                    "textDocument/publishDiagnostics",
+                    JsonValue::object([("uri", JsonValue::string(uri)), ("diagnostics", JsonValue::Array(Vec::new()))]),
+                )]
+            }
+            "textDocument/definition" => vec![response(id, self.definition(params))],
+            "textDocument/references" => vec![response(id, self.references(params))],
+            "textDocument/hover" => vec![response(id, self.hover(params))],
+            "textDocument/completion" => vec![response(id, Self::completion())],
+            "textDocument/formatting" => vec![response(id, self.formatting(params))],
+            _ if is_request => vec![error_response(id, METHOD_NOT_FOUND, &format!("unknown method '{}'", method))],
+            _ => Vec::new(),
+        }
+    }
+
+    fn capabilities() -> JsonValue {
+        JsonValue::object([
+            (
+                "capabilities",
+                JsonValue::object([
+                    ("textDocumentSync", 1i64.into()),
+                    ("definitionProvider", true.into()),
+                    ("referencesProvider", true.into()),
+                    ("hoverProvider", true.into()),
+                    ("completionProvider", JsonValue::object([])),
+                    ("documentFormattingProvider", true.into()),
+                ]),
+            ),
+            (
+                "serverInfo",
+                JsonValue::object([
+                    ("name", JsonValue::string("basic-lsp")),
+                    ("version", JsonValue::string(env!("CARGO_PKG_VERSION"))),
+                ]),
+            ),
+        ])
+    }
+
+    fn document<'a>(&'a self, params: &'a JsonValue) -> Option<(&'a str, &'a Document)> {
+        let uri = params.get("textDocument").get("uri").as_str()?;
+        self.documents.get(uri).map(|document| (uri, document))
+    }
+
+    fn diagnostic_json(document: &Document, diagnostic: &Diagnostic) -> JsonValue {
+        let severity: i64 = match diagnostic.severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+        };
+        let mut span = diagnostic.span;
+        span.end = span.end.max(span.start + 1).min(document.chars.len().max(span.start));
+        let mut members = vec![("range", document.range(&span)), ("severity", severity.into())];
+        // LSP's code is optional, and a null one is not allowed
+        if let Some(code) = diagnostic.code {
+            members.push(("code", JsonValue::string(code)));
+        }
+        members.push(("source", JsonValue::string("basic")));
+        members.push(("message", JsonValue::string(&diagnostic.message)));
+        JsonValue::Object(members.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> JsonValue {
+        let diagnostics = match self.documents.get(uri) {
+            Some(document) => {
+                let found = match crate::parse(&document.text, &Options::default()) {
+                    Ok(program) => program.warnings,
+                    Err(diagnostics) => diagnostics,
+                };
+                found.iter().map(|d| Self::diagnostic_json(document, d)).collect()
+            }
+            None => Vec::new(),
+        };
+        notification(
+            "textDocument/publishDiagnostics",
+            JsonValue::object([("uri", JsonValue::string(uri)), ("diagnostics", JsonValue::Array(diagnostics))]),
+        )
+    }
+
+    fn location(uri: &str, document: &Document, span: &Span) -> JsonValue {
+        JsonValue::object([("uri", JsonValue::string(uri)), ("range", document.range(span))])
+    }
+
+    /// Jump from a GOTO/GOSUB/THEN target to the line it names.
+    fn definition(&self, params: &JsonValue) -> JsonValue {
+        let Some((uri, document)) = self.document(params) else {
+            return JsonValue::Null;
+        };
+        let Some(tokens) = document.tokens() else {
+            return JsonValue::Null;
+        };
+        let offset = document.offset(params.get("position"));
+        let Some(index) = token_at(&tokens, offset) else {
+            return JsonValue::Null;
+        };
+        let Token::Number(target) = tokens[index].0 else {
+            return JsonValue::Null;
+        };
+        line_labels(&tokens)
+            .iter()
+            .find(|(label, _)| *label == target)
+            .map_or(JsonValue::Null, |(_, span)| Self::location(uri, document, span))
+    }
+
+    /// Every use of the line number or variable under the cursor.
+    fn references(&self, params: &JsonValue) -> JsonValue {
+        let Some((uri, document)) = self.document(params) else {
+            return JsonValue::Null;
+        };
+        let Some(tokens) = document.tokens() else {
+            return JsonValue::Null;
+        };
+        let offset = document.offset(params.get("position"));
+        let Some(index) = token_at(&tokens, offset) else {
+            return JsonValue::Null;
+        };
+        let include_declaration = params.get("context").get("includeDeclaration").as_bool().unwrap_or(true);
+
+        let mut spans: Vec<Span> = match &tokens[index].0 {
+            Token::Number(target) => {
+                let labels = line_labels(&tokens);
+                let references = line_references(&tokens);
+                let is_line = labels.iter().any(|(_, span)| *span == tokens[index].1)
+                    || references.iter().any(|r| r.span == tokens[index].1);
+                if !is_line {
+                    return JsonValue::Null;
+                }
+                let mut spans: Vec<Span> = Vec::new();
+                if include_declaration {
+                    spans.extend(labels.iter().filter(|(label, _)| label == target).map(|(_, span)| *span));
+                }
+                spans.extend(references.iter().filter(|r| r.target == *target).map(|r| r.span));
+                spans
+            }
+            Token::Identifier(name) if !is_call(&tokens, index) => tokens
+                .iter()
+                .enumerate()
+                .filter(|(i, (token, _))| matches!(token, Token::Identifier(n) if n == name) && !is_call(&tokens, *i))
+                .map(|(_, (_, span))| *span)
+                .collect(),
+            _ => return JsonValue::Null,
+        };
+
+        spans.sort_by_key(|span| span.start);
+        JsonValue::Array(spans.iter().map(|span| Self::location(uri, document, span)).collect())
+    }
+
+    /// Signature and description of the builtin function under the cursor.
+    fn hover(&self, params: &JsonValue) -> JsonValue {
+        let Some((_, document)) = self.document(params) else {
+            return JsonValue::Null;
+        };
+        let Some(tokens) = document.tokens() else {
+            return JsonValue::Null;
+        };
+        let offset = document.offset(params.get("position"));
+        let Some(index) = token_at(&tokens, offset) else {
+            return JsonValue::Null;
+        };
+        // TIMER is a keyword to the lexer, so look up the name as written
+        let span = &tokens[index].1;
+        let name: String = document.chars[span.start..span.end].iter().collect();
+        let Some(builtin) = builtins::lookup(&name) else {
+            return JsonValue::Null;
+        };
+        JsonValue::object([
+            (
+                "contents",
+                JsonValue::object([
+                    ("kind", JsonValue::string("markdown")),
+                    (
+                        "value",
+                        JsonValue::string(format!("```basic\n{}\n```\n{}", builtin.signature, builtin.description)),
+                    ),
+                ]),
+            ),
+            ("range", document.range(span)),
+        ])
+    }
+
+    fn completion() -> JsonValue {
+        const KEYWORD: i64 = 14;
+        const FUNCTION: i64 = 3;
+        let keywords = KEYWORDS.iter().map(|keyword| {
+            JsonValue::object([("label", JsonValue::string(*keyword)), ("kind", KEYWORD.into())])
+        });
+        let functions = BUILTINS.iter().map(|builtin| {
+            JsonValue::object([
+                ("label", JsonValue::string(builtin.name)),
+                ("kind", FUNCTION.into()),
+                ("detail", JsonValue::string(builtin.signature)),
+                ("documentation", JsonValue::string(builtin.description)),
+            ])
+        });
+        JsonValue::Array(keywords.chain(functions).collect())
+    }
+
+    /// Replace the whole document with its canonical form.
+    fn formatting(&self, params: &JsonValue) -> JsonValue {
+        let Some((_, document)) = self.document(params) else {
+            return JsonValue::Null;
+        };
+        let Ok(program) = crate::parse(&document.text, &Options::default()) else {
+            return JsonValue::Null;
+        };
+        let formatted = Formatter::new(FormatOptions::default()).format(&program.statements);
+        if formatted == document.text {
+            return JsonValue::Array(Vec::new());
+        }
+        let whole = Span { start: 0, end: document.chars.len(), line: 1, column: 1 };
+        JsonValue::Array(vec![JsonValue::object([
+            ("range", document.range(&whole)),
+            ("newText", JsonValue::string(formatted)),
+        ])])
+    }
+}
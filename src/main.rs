@@ -1,47 +1,268 @@
-mod lexer;
-mod parser;
-mod codegen;
+mod cli;
 
-use lexer::Lexer;
-use parser::Parser;
+use compiler::cfg::Cfg;
+use compiler::codegen::CodegenOptions;
+use compiler::diagnostic::Diagnostic;
+use compiler::formatter::Formatter;
+use compiler::lexer::Lexer;
+use compiler::parser::Statement;
+use compiler::runtime::Runtime;
+use compiler::{driver, renum, serialize, Options, Target};
 use std::env;
 use std::fs;
-use std::io::{self, Write};
-use crate::codegen::CodeGenerator;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use crate::cli::{CliOptions, Command, Emit, Parsed, USAGE};
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// The program failed to compile, or a file could not be read or written.
+const EXIT_FAILURE: i32 = 1;
+/// The command line itself was wrong.
+const EXIT_USAGE: i32 = 2;
 
-    let input_file = if args.len() >= 2 {
-        &args[1]
+fn read_input(input_file: &str) -> Result<String, String> {
+    if input_file == "-" {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|err| format!("Error reading stdin: {}", err))?;
+        Ok(input)
     } else {
-        "example.bas"
-    };
-    let input = fs::read_to_string(input_file)
-        .unwrap_or_else(|err| {
-            eprintln!("Error reading file '{}': {}", input_file, err);
-            std::process::exit(1);
-        });
+        fs::read_to_string(input_file).map_err(|err| format!("Error reading file '{}': {}", input_file, err))
+    }
+}
 
-    let mut lexer = Lexer::new(&input);
-    let tokens = lexer.tokenize();
+fn write_output(output_file: Option<&str>, text: impl AsRef<[u8]>) -> Result<(), String> {
+    match output_file {
+        Some(output_file) => {
+            fs::write(output_file, text).map_err(|err| format!("Error writing file '{}': {}", output_file, err))
+        }
+        None => io::stdout()
+            .write_all(text.as_ref())
+            .map_err(|err| format!("Error writing stdout: {}", err)),
+    }
+}
+
+/// The input's name as shown in messages.
+fn source_name(options: &CliOptions) -> &str {
+    if options.input == "-" { "<stdin>" } else { &options.input }
+}
 
-    let mut parser = Parser::new(tokens);
-    let ast = parser.parse();
+/// Where `build` writes the executable: `-o`, or the input without its
+/// extension.
+fn executable_path(options: &CliOptions) -> PathBuf {
+    match &options.output {
+        Some(output) => PathBuf::from(output),
+        None if options.input == "-" => PathBuf::from("a.out"),
+        None => Path::new(&options.input).with_extension(""),
+    }
+}
 
-    let mut codegen = CodeGenerator::new();
-    let c_code = codegen.generate(&ast);
+/// Where the C for an executable is written and compiled: next to the
+/// executable with `--keep-c`, or in `scratch`.
+fn c_file(options: &CliOptions, scratch: &driver::TempDir) -> PathBuf {
+    if options.build.keep_c && options.command == Command::Build {
+        executable_path(options).with_extension("c")
+    } else {
+        scratch.path().join("program.c")
+    }
+}
 
-    if args.len() >= 3 {
-        let output_file = &args[2];
-        fs::write(output_file, &c_code)
-            .unwrap_or_else(|err| {
-                eprintln!("Error writing file '{}': {}", output_file, err);
-                std::process::exit(1);
-            });
-        println!("Compiled successfully! Output written to '{}'", output_file);
+/// The options for the library, `c_file` being the file the generated C
+/// goes in, if any.
+fn compile_options(options: &CliOptions, c_file: Option<&Path>) -> Options {
+    Options {
+        dialect: options.dialect,
+        target: if options.emit == Emit::LlvmIr { Target::LlvmIr } else { options.target },
+        warnings: options.warnings.clone(),
+        codegen: CodegenOptions {
+            line_directives: options.line_directives.then(|| source_name(options).to_string()),
+            c_file: c_file.map(|path| path.to_string_lossy().into_owned()),
+            checks: options.checks,
+            trace: options.trace,
+            runtime: options.build.runtime,
+            seed: options.seed,
+            numbers: options.numbers,
+            ..Default::default()
+        },
+        no_opt: options.no_opt,
     }
-    else {
-        io::stdout().write_all(c_code.as_bytes()).unwrap();
+}
+
+/// Print diagnostics to stderr, returning whether any of them is an error.
+fn report(options: &CliOptions, diagnostics: &[Diagnostic]) -> bool {
+    let file = source_name(options);
+    let color = options.color.enabled();
+    for diagnostic in diagnostics {
+        eprintln!("{}", diagnostic.render(file, color));
     }
+    diagnostics.iter().any(|d| d.is_error())
+}
+
+/// Parse and check the program, reporting every diagnostic. Returns `None`
+/// if the program has errors.
+fn parse_program(options: &CliOptions, source: &str) -> Option<Vec<Statement>> {
+    match compiler::parse(source, &compile_options(options, None)) {
+        Ok(program) => {
+            report(options, &program.warnings);
+            Some(program.statements)
+        }
+        Err(diagnostics) => {
+            report(options, &diagnostics);
+            None
+        }
+    }
+}
+
+/// Compile the program, reporting every diagnostic. Returns `None` if the
+/// program has errors.
+fn compile_program(options: &CliOptions, source: &str, c_file: Option<&Path>) -> Option<Vec<u8>> {
+    match compiler::compile(source, &compile_options(options, c_file)) {
+        Ok(output) => {
+            report(options, &output.warnings);
+            Some(output.code)
+        }
+        Err(diagnostics) => {
+            report(options, &diagnostics);
+            None
+        }
+    }
+}
+
+fn execute(options: &CliOptions) -> Result<i32, String> {
+    let input = read_input(&options.input)?;
+    let output_file = options.output.as_deref();
+
+    match options.command {
+        Command::Build if matches!(options.emit, Emit::Tokens | Emit::TokensJson) => match Lexer::new(&input).tokenize_spanned() {
+            Ok(tokens) => {
+                let listing: String = if options.emit == Emit::TokensJson {
+                    serialize::tokens_to_json(&tokens).pretty() + "\n"
+                } else {
+                    tokens
+                        .iter()
+                        .map(|(token, span)| format!("{}:{}\t{:?}\n", span.line, span.column, token))
+                        .collect()
+                };
+                write_output(output_file, &listing)?;
+                Ok(0)
+            }
+            Err(errors) => {
+                report(options, &errors);
+                Ok(EXIT_FAILURE)
+            }
+        },
+        Command::Build if matches!(options.emit, Emit::Ast | Emit::AstJson | Emit::CfgDot) => {
+            let Some(ast) = parse_program(options, &input) else {
+                return Ok(EXIT_FAILURE);
+            };
+            let text = match options.emit {
+                Emit::AstJson => serialize::ast_to_json(&ast).pretty() + "\n",
+                Emit::CfgDot => Cfg::new(&ast).to_dot(),
+                _ => format!("{:#?}\n", ast),
+            };
+            write_output(output_file, &text)?;
+            Ok(0)
+        }
+        Command::Build => {
+            // Only the C for an executable is compiled here
+            let scratch = match (options.emit, options.target) {
+                (Emit::Exe, Target::C) => Some(driver::TempDir::new()?),
+                _ => None,
+            };
+            let c_file = match &scratch {
+                Some(scratch) => Some(c_file(options, scratch)),
+                None if options.emit == Emit::C => output_file.map(PathBuf::from),
+                None => None,
+            };
+            let Some(code) = compile_program(options, &input, c_file.as_deref()) else {
+                return Ok(EXIT_FAILURE);
+            };
+            if options.emit == Emit::LlvmIr
+                && let Some(output_file) = output_file
+            {
+                // The IR calls into the C runtime, which goes next to it
+                driver::write_runtime(Path::new(output_file).parent().unwrap_or(Path::new("")))?;
+            }
+            if options.target != Target::C || options.emit == Emit::LlvmIr {
+                write_output(output_file, &code)?;
+                return Ok(0);
+            }
+            // Generated C is always UTF-8
+            let code = String::from_utf8_lossy(&code);
+            if options.emit == Emit::C {
+                if options.build.runtime == Runtime::External {
+                    // The runtime goes next to the C file that includes it
+                    let Some(output_file) = output_file else {
+                        return Err("--runtime=external needs -o with --emit c".to_string());
+                    };
+                    driver::write_runtime(Path::new(output_file).parent().unwrap_or(Path::new("")))?;
+                }
+                write_output(output_file, code.as_bytes())?;
+            } else {
+                let c_file = c_file.expect("an executable's C has a file");
+                driver::build_executable(&code, &c_file, &executable_path(options), source_name(options), &options.build)?;
+            }
+            Ok(0)
+        }
+        Command::Run => {
+            let scratch = driver::TempDir::new()?;
+            let c_file = c_file(options, &scratch);
+            let Some(c_code) = compile_program(options, &input, Some(&c_file)) else {
+                return Ok(EXIT_FAILURE);
+            };
+            driver::run_c(&String::from_utf8_lossy(&c_code), &c_file, source_name(options), &options.build)
+        }
+        Command::Check => match parse_program(options, &input) {
+            Some(_) => Ok(0),
+            None => Ok(EXIT_FAILURE),
+        },
+        Command::Fmt => {
+            let Some(ast) = parse_program(options, &input) else {
+                return Ok(EXIT_FAILURE);
+            };
+            let formatted = Formatter::new(options.format.clone()).format(&ast);
+            write_output(output_file, &formatted)?;
+            Ok(0)
+        }
+        Command::Debug => {
+            let Some(ast) = parse_program(options, &input) else {
+                return Ok(EXIT_FAILURE);
+            };
+            let options = compile_options(options, None);
+            let codegen = CodegenOptions { dialect: options.dialect, ..options.codegen };
+            compiler::debugger::debug(&input, &ast, codegen, io::stdin().lock(), io::stdout())
+                .map_err(|err| format!("Error writing stdout: {}", err))?;
+            Ok(0)
+        }
+        Command::Renum => {
+            let renumbered = renum::renumber(&input, &options.renum)?;
+            for warning in &renumbered.warnings {
+                eprintln!("{}", warning);
+            }
+            write_output(output_file, &renumbered.source)?;
+            Ok(0)
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let options = match CliOptions::parse(&args) {
+        Ok(Parsed::Options(options)) => options,
+        Ok(Parsed::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+
+    let code = execute(&options).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        EXIT_FAILURE
+    });
+    std::process::exit(code);
 }
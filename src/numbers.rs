@@ -0,0 +1,160 @@
+//! How numbers are represented when a program runs.
+//!
+//! By default every number is an IEEE double. GW-BASIC kept single
+//! precision numbers in Microsoft Binary Format (MBF): a 24-bit mantissa,
+//! rounded to nearest with halves away from zero, and an exponent byte
+//! biased by 128. The largest single is 1.701412E+38; anything bigger is an
+//! "Overflow", and anything smaller than 2^-129 becomes 0.
+//!
+//! [`Numbers::Mbf`] computes in doubles but rounds every result to the
+//! nearest MBF single, so programs print what GW-BASIC printed.
+
+/// The number format a program computes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Numbers {
+    /// IEEE double precision, the default.
+    #[default]
+    Double,
+    /// GW-BASIC's 4-byte MBF singles.
+    Mbf,
+}
+
+/// Bits in an MBF single's mantissa, counting the hidden one.
+const MANTISSA_BITS: i32 = 24;
+/// The exponents an MBF single can have, as in `x = m * 2^e` with
+/// `0.5 <= m < 1`. An exponent byte of 0 means the number is zero.
+const MIN_EXPONENT: i32 = -127;
+const MAX_EXPONENT: i32 = 127;
+
+impl Numbers {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "double" => Some(Numbers::Double),
+            "mbf" => Some(Numbers::Mbf),
+            _ => None,
+        }
+    }
+
+    /// `x` as a number in this format, or `None` if it overflows or is
+    /// NaN, neither of which a constant can be.
+    pub fn round(&self, x: f64) -> Option<f64> {
+        match self {
+            Numbers::Double => x.is_finite().then_some(x),
+            Numbers::Mbf => round_mbf(x).filter(|x| !x.is_nan()),
+        }
+    }
+
+    /// `x` as PRINT writes it, followed by a space: as C's `%g` for
+    /// doubles, and as GW-BASIC did for singles.
+    pub fn format(&self, x: f64) -> String {
+        match self {
+            Numbers::Mbf if x.is_finite() => format_single(x),
+            _ => format!("{} ", format_g(x)),
+        }
+    }
+}
+
+/// `x` as C's `%g` writes it: six significant digits, in exponent form
+/// when very large or small.
+fn format_g(x: f64) -> String {
+    if x.is_nan() {
+        return if x.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+    }
+    if x.is_infinite() {
+        return if x < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    if x == 0.0 {
+        return if x.is_sign_negative() { "-0" } else { "0" }.to_string();
+    }
+    let scientific = format!("{:.5e}", x);
+    let (mantissa, exponent) = scientific.split_once('e').expect("exponent form");
+    let exponent: i32 = exponent.parse().expect("exponent");
+    if !(-4..6).contains(&exponent) {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        return format!("{}e{}{:02}", trim_zeros(mantissa), sign, exponent.abs());
+    }
+    trim_zeros(&format!("{:.*}", (5 - exponent) as usize, x)).to_string()
+}
+
+fn trim_zeros(number: &str) -> &str {
+    if number.contains('.') { number.trim_end_matches('0').trim_end_matches('.') } else { number }
+}
+
+/// Finite `x` as GW-BASIC prints a single: a sign or a space, up to seven
+/// significant digits, and `E+nn` for numbers below .01 or from 1E+7 up.
+fn format_single(x: f64) -> String {
+    if x == 0.0 {
+        return " 0 ".to_string();
+    }
+    let scientific = format!("{:.6e}", x.abs());
+    let (digits, exponent) = scientific.split_once('e').expect("exponent form");
+    let exponent: i32 = exponent.parse().expect("exponent");
+    let mantissa = digits.replace('.', "");
+    let mantissa = mantissa.trim_end_matches('0');
+    let sign = if x < 0.0 { '-' } else { ' ' };
+    let number = if !(-2..7).contains(&exponent) {
+        let (first, rest) = mantissa.split_at(1);
+        let point = if rest.is_empty() { String::new() } else { format!(".{}", rest) };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}{}E{}{:02}", first, point, exponent_sign, exponent.abs())
+    } else if exponent < 0 {
+        format!(".{}{}", "0".repeat((-exponent - 1) as usize), mantissa)
+    } else if mantissa.len() <= exponent as usize + 1 {
+        format!("{:0<width$}", mantissa, width = exponent as usize + 1)
+    } else {
+        let (whole, fraction) = mantissa.split_at(exponent as usize + 1);
+        format!("{}.{}", whole, fraction)
+    };
+    format!("{}{} ", sign, number)
+}
+
+/// The largest MBF single, 1.701412E+38.
+pub fn mbf_max() -> f64 {
+    (1.0 - (-MANTISSA_BITS as f64).exp2()) * (MAX_EXPONENT as f64).exp2()
+}
+
+/// `x` rounded to the nearest MBF single, or `None` if it is too large.
+/// NaN stays NaN.
+pub fn round_mbf(x: f64) -> Option<f64> {
+    if x == 0.0 || x.is_nan() {
+        return Some(x);
+    }
+    if x.is_infinite() {
+        return None;
+    }
+    // Far outside the range, where frexp's powers of two would not fit
+    if x.abs() >= ((MAX_EXPONENT + 1) as f64).exp2() {
+        return None;
+    }
+    if x.abs() < ((MIN_EXPONENT - 2) as f64).exp2() {
+        return Some(0.0);
+    }
+    let (mantissa, mut exponent) = frexp(x);
+    let mut scaled = (mantissa * (MANTISSA_BITS as f64).exp2()).round();
+    if scaled.abs() == (MANTISSA_BITS as f64).exp2() {
+        scaled /= 2.0;
+        exponent += 1;
+    }
+    if exponent > MAX_EXPONENT {
+        return None;
+    }
+    if exponent < MIN_EXPONENT {
+        return Some(0.0);
+    }
+    Some(scaled * ((exponent - MANTISSA_BITS) as f64).exp2())
+}
+
+/// Split finite, non-zero `x` into `m * 2^e` with `0.5 <= |m| < 1`.
+fn frexp(x: f64) -> (f64, i32) {
+    let mut exponent = x.abs().log2().floor() as i32 + 1;
+    let mut mantissa = x / (exponent as f64).exp2();
+    // log2 can be off by one either side of a power of two
+    if mantissa.abs() >= 1.0 {
+        mantissa /= 2.0;
+        exponent += 1;
+    } else if mantissa.abs() < 0.5 {
+        mantissa *= 2.0;
+        exponent -= 1;
+    }
+    (mantissa, exponent)
+}
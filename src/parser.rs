@@ -1,9 +1,11 @@
-use crate::lexer::{Token};
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{Span, Token};
 
 #[derive(Debug, Clone)]
 pub enum Expression {
     Number(i64),
-    Float(f64),
+    /// A floating-point literal and its original spelling.
+    Float(f64, String),
     Variable(String),
     BinaryOp {
         left: Box<Expression>,
@@ -29,6 +31,10 @@ pub enum BinOp {
 pub enum PrintItem {
     Expr(Expression),
     String(String),
+    /// `,` between items
+    Comma,
+    /// `;` between items
+    Semicolon,
 }
 
 /// Represents an executable line or block in the language.
@@ -36,6 +42,8 @@ pub enum PrintItem {
 pub struct Statement {
     pub label: Option<i64>,
     pub node: StatementNode,
+    /// Where the statement starts, including its line number.
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
@@ -57,6 +65,8 @@ pub enum StatementNode {
         end: Expression,
         step: Option<Expression>,
         body: Vec<Statement>,
+        /// Line number of the closing NEXT, if it has one.
+        next_label: Option<i64>,
     },
     /// IF <left> <op> <right> THEN <line_or_stmt>
     If {
@@ -67,24 +77,41 @@ pub enum StatementNode {
     },
     /// GOTO <line>
     Goto(i64),
+    /// GOSUB <line>
+    Gosub(i64),
+    /// RETURN
+    Return,
+    /// RANDOMIZE [<seed>]; without a seed the user is asked for one
+    Randomize(Option<Expression>),
     /// INPUT <var>
     Input(String),
+    /// TRON: print each line number as it runs
+    Tron,
+    /// TROFF: stop printing line numbers
+    Troff,
     /// REM <comment>
-    Rem,
+    Rem(String),
+    /// A line number with no statement
+    Empty,
     /// END
     End,
 }
 
 pub struct Parser {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
     position: usize,
+    errors: Vec<Diagnostic>,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<(Token, Span)>) -> Self {
+        let (tokens, spans) = tokens.into_iter().unzip();
         Parser {
             tokens,
+            spans,
             position: 0,
+            errors: Vec::new(),
         }
     }
 
@@ -97,15 +124,28 @@ impl Parser {
         }
     }
 
+    fn current_span(&self) -> Span {
+        self.spans
+            .get(self.position)
+            .or(self.spans.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
     fn advance(&mut self) {
         self.position += 1;
     }
 
-    fn expect(&mut self, expected: Token) {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, Diagnostic> {
+        Err(Diagnostic::error(message, self.current_span()))
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), Diagnostic> {
         if self.current_token() != &expected {
-            panic!("expected {:?}, got {:?}", expected, self.current_token());
+            return self.error(format!("expected {:?}, got {:?}", expected, self.current_token()));
         }
         self.advance();
+        Ok(())
     }
 
     fn skip_newlines(&mut self) {
@@ -114,15 +154,23 @@ impl Parser {
         }
     }
 
-    fn parse_primary(&mut self) -> Expression {
+    /// Record a syntax error and skip the rest of the line.
+    fn recover(&mut self, error: Diagnostic) {
+        self.errors.push(error);
+        while !matches!(self.current_token(), Token::Newline | Token::Eof) {
+            self.advance();
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, Diagnostic> {
         match self.current_token().clone() {
             Token::Number(n) => {
                 self.advance();
-                Expression::Number(n)
+                Ok(Expression::Number(n))
             }
-            Token::Float(f) => {
+            Token::Float(f, text) => {
                 self.advance();
-                Expression::Float(f)
+                Ok(Expression::Float(f, text))
             }
             Token::Identifier(name) => {
                 self.advance();
@@ -131,7 +179,7 @@ impl Parser {
                     let mut args = Vec::new();
                     if self.current_token() != &Token::RightParen {
                         loop {
-                            args.push(self.parse_expr());
+                            args.push(self.parse_expr()?);
                             if self.current_token() == &Token::Comma {
                                 self.advance();
                             } else {
@@ -139,39 +187,46 @@ impl Parser {
                             }
                         }
                     }
-                    self.expect(Token::RightParen);
-                    Expression::FunctionCall { name, args }
+                    self.expect(Token::RightParen)?;
+                    Ok(Expression::FunctionCall { name, args })
+                } else if name.eq_ignore_ascii_case("RND") {
+                    // RND on its own is RND(1)
+                    Ok(Expression::FunctionCall { name, args: Vec::new() })
                 } else {
-                    Expression::Variable(name)
+                    Ok(Expression::Variable(name))
                 }
             }
+            Token::Timer => {
+                self.advance();
+                Ok(Expression::FunctionCall { name: "TIMER".to_string(), args: Vec::new() })
+            }
             Token::LeftParen => {
                 self.advance();
-                let expr = self.parse_expr();
-                self.expect(Token::RightParen);
-                expr
+                let expr = self.parse_expr()?;
+                self.expect(Token::RightParen)?;
+                Ok(expr)
             }
-            _ => panic!("Unexpected token in expression: {:?}", self.current_token()),
+            _ => self.error(format!("Unexpected token in expression: {:?}", self.current_token())),
         }
     }
 
-    fn parse_power(&mut self) -> Expression {
-        let mut left = self.parse_primary();
+    fn parse_power(&mut self) -> Result<Expression, Diagnostic> {
+        let mut left = self.parse_primary()?;
 
         while self.current_token() == &Token::OperatorPower {
             self.advance();
-            let right = self.parse_power(); // Right associative
+            let right = self.parse_power()?; // Right associative
             left = Expression::BinaryOp {
                 left: Box::new(left),
                 operator: BinOp::Power,
                 right: Box::new(right),
             };
         }
-        left
+        Ok(left)
     }
 
-    fn parse_term(&mut self) -> Expression {
-        let mut left = self.parse_power();
+    fn parse_term(&mut self) -> Result<Expression, Diagnostic> {
+        let mut left = self.parse_power()?;
 
         while matches!(self.current_token(), Token::OperatorMultiply | Token::OperatorDivide) {
             let op = match self.current_token() {
@@ -180,18 +235,18 @@ impl Parser {
                 _ => unreachable!(),
             };
             self.advance();
-            let right = self.parse_power();
+            let right = self.parse_power()?;
             left = Expression::BinaryOp {
                 left: Box::new(left),
                 operator: op,
                 right: Box::new(right),
             };
         }
-        left
+        Ok(left)
     }
 
-    fn parse_expr(&mut self) -> Expression {
-        let mut left = self.parse_term();
+    fn parse_expr(&mut self) -> Result<Expression, Diagnostic> {
+        let mut left = self.parse_term()?;
 
         while matches!(self.current_token(), Token::OperatorAdd | Token::OperatorSubtract) {
             let op = match self.current_token() {
@@ -200,7 +255,7 @@ impl Parser {
                 _ => unreachable!(),
             };
             self.advance();
-            let right = self.parse_term();
+            let right = self.parse_term()?;
             left = Expression::BinaryOp {
                 left: Box::new(left),
                 operator: op,
@@ -208,34 +263,34 @@ impl Parser {
             };
         }
 
-        left
+        Ok(left)
     }
 
-    /// Parse a LET statement: LET X = 10
-    fn parse_let(&mut self) -> StatementNode {
-        self.expect(Token::Let);
+    /// Parse a LET statement: LET X = 10, or the implicit form X = 10
+    fn parse_let(&mut self) -> Result<StatementNode, Diagnostic> {
+        if self.current_token() == &Token::Let {
+            self.advance();
+        }
 
         let var = match self.current_token().clone() {
             Token::Identifier(name) => {
                 self.advance();
                 name
             }
-            _ => panic!("Expected identifier after LET"),
+            _ => return self.error("Expected identifier after LET"),
         };
 
-        self.expect(Token::Equal);
-        let expr = self.parse_expr();
+        self.expect(Token::Equal)?;
+        let expr = self.parse_expr()?;
 
-        StatementNode::Let { var, value: expr }
+        Ok(StatementNode::Let { var, value: expr })
     }
 
     /// Parse a PRINT statement: PRINT X, "HELLO";
-    fn parse_print(&mut self) -> StatementNode {
-        self.expect(Token::Print);
-        
-        let mut items = Vec::new();
-        let mut newline = true;
+    fn parse_print(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::Print)?;
 
+        let mut items = Vec::new();
         loop {
             match self.current_token() {
                 Token::Newline | Token::Eof => break,
@@ -246,83 +301,90 @@ impl Parser {
                 }
                 Token::Comma => {
                     self.advance();
-                    if matches!(self.current_token(), Token::Newline | Token::Eof) {
-                        newline = false;
-                        break;
-                    }
+                    items.push(PrintItem::Comma);
                 }
                 Token::Semicolon => {
                     self.advance();
-                    if matches!(self.current_token(), Token::Newline | Token::Eof) {
-                        newline = false;
-                        break;
-                    }
+                    items.push(PrintItem::Semicolon);
                 }
                 _ => {
-                    items.push(PrintItem::Expr(self.parse_expr()));
+                    items.push(PrintItem::Expr(self.parse_expr()?));
                 }
             }
-
-            match self.current_token() {
-                Token::Comma | Token::Semicolon => {
-                    self.advance();
-                    if matches!(self.current_token(), Token::Newline | Token::Eof) {
-                        newline = false;
-                        break;
-                    }
-                }
-                _ => break,
-            }
         }
 
-        StatementNode::Print { items, newline }
+        // A trailing separator suppresses the newline
+        let newline = !matches!(items.last(), Some(PrintItem::Comma | PrintItem::Semicolon));
+
+        Ok(StatementNode::Print { items, newline })
     }
 
     /// Parse a FOR loop: FOR I = 1 TO 5 STEP 2 ... NEXT
-    fn parse_for(&mut self) -> StatementNode {
-        self.expect(Token::For);
+    fn parse_for(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::For)?;
 
         let var = match self.current_token().clone() {
             Token::Identifier(name) => {
                 self.advance();
                 name
             }
-            _ => panic!("Expected identifier after FOR"),
+            _ => return self.error("Expected identifier after FOR"),
         };
-        self.expect(Token::Equal);
-        let start = self.parse_expr();
-        self.expect(Token::To);
-        let end = self.parse_expr();
+        self.expect(Token::Equal)?;
+        let start = self.parse_expr()?;
+        self.expect(Token::To)?;
+        let end = self.parse_expr()?;
 
         let mut step = None;
         if self.current_token() == &Token::Step {
             self.advance();
-            step = Some(self.parse_expr());
+            step = Some(self.parse_expr()?);
         }
 
         self.skip_newlines();
 
         let mut body = Vec::new();
-        while self.current_token() != &Token::Next && self.current_token() != &Token::Eof {
-            body.push(self.parse_statement());
+        while !self.at_next() && self.current_token() != &Token::Eof {
+            match self.parse_line() {
+                Ok(stmt) => body.push(stmt),
+                Err(error) => self.recover(error),
+            }
             self.skip_newlines();
         }
 
-        self.expect(Token::Next);
-        
+        let next_label = if let Token::Number(n) = self.current_token() {
+            let l = *n;
+            self.advance();
+            Some(l)
+        } else {
+            None
+        };
+        if self.current_token() != &Token::Next {
+            return self.error(format!("FOR {} without NEXT", var));
+        }
+        self.advance();
+
         // Optional NEXT <var>
         if let Token::Identifier(_) = self.current_token() {
             self.advance();
         }
 
-        StatementNode::For { var, start, end, step, body }
+        Ok(StatementNode::For { var, start, end, step, body, next_label })
     }
 
-    fn parse_if(&mut self) -> StatementNode {
-        self.expect(Token::If);
-        let left = self.parse_expr();
+    /// Whether the parser is at a NEXT, possibly preceded by its line number.
+    fn at_next(&self) -> bool {
+        match self.current_token() {
+            Token::Next => true,
+            Token::Number(_) => self.tokens.get(self.position + 1) == Some(&Token::Next),
+            _ => false,
+        }
+    }
+
+    fn parse_if(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::If)?;
+        let left = self.parse_expr()?;
 
-        // Using '[' as '<=' and ']' as '>='
         let op = match self.current_token().clone() {
             Token::Equal | Token::NotEqual |
             Token::LessThan | Token::LessOrEqual |
@@ -331,50 +393,72 @@ impl Parser {
                 self.advance();
                 t
             }
-            _ => panic!("Expected comparison operator in IF"),
+            _ => return self.error("Expected comparison operator in IF"),
         };
-        
-        let right = self.parse_expr();
-        self.expect(Token::Then);
+
+        let right = self.parse_expr()?;
+        self.expect(Token::Then)?;
 
         let then_stmt = if let Token::Number(line) = self.current_token() {
             let l = *line;
+            let span = self.current_span();
             self.advance();
-            Statement { label: None, node: StatementNode::Goto(l) }
+            Statement { label: None, node: StatementNode::Goto(l), span }
         } else {
-            self.parse_statement()
+            self.parse_statement()?
         };
 
-        StatementNode::If { left, op, right, then_part: Box::new(then_stmt) }
+        Ok(StatementNode::If { left, op, right, then_part: Box::new(then_stmt) })
+    }
+
+    fn parse_goto(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::Goto)?;
+        match self.current_token() {
+            Token::Number(line) => {
+                let l = *line;
+                self.advance();
+                Ok(StatementNode::Goto(l))
+            }
+            _ => self.error("Expected line number after GOTO"),
+        }
     }
 
-    fn parse_goto(&mut self) -> StatementNode {
-        self.expect(Token::Goto);
+    fn parse_gosub(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::Gosub)?;
         match self.current_token() {
             Token::Number(line) => {
                 let l = *line;
                 self.advance();
-                StatementNode::Goto(l)
+                Ok(StatementNode::Gosub(l))
             }
-            _ => panic!("Expected line number after GOTO"),
+            _ => self.error("Expected line number after GOSUB"),
+        }
+    }
+
+    fn parse_randomize(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::Randomize)?;
+        match self.current_token() {
+            Token::Newline | Token::Eof => Ok(StatementNode::Randomize(None)),
+            _ => Ok(StatementNode::Randomize(Some(self.parse_expr()?))),
         }
     }
 
-    fn parse_input(&mut self) -> StatementNode {
-        self.expect(Token::Input);
+    fn parse_input(&mut self) -> Result<StatementNode, Diagnostic> {
+        self.expect(Token::Input)?;
         let var = match self.current_token().clone() {
             Token::Identifier(name) => {
                 self.advance();
                 name
             }
-            _ => panic!("Expected identifier after INPUT"),
+            _ => return self.error("Expected identifier after INPUT"),
         };
-        StatementNode::Input(var)
+        Ok(StatementNode::Input(var))
     }
 
     /// Entry point for parsing any statement.
-    fn parse_statement(&mut self) -> Statement {
+    fn parse_statement(&mut self) -> Result<Statement, Diagnostic> {
         self.skip_newlines();
+        let mut span = self.current_span();
 
         let label = if let Token::Number(n) = self.current_token() {
             let l = *n;
@@ -385,15 +469,31 @@ impl Parser {
         };
 
         let node = match self.current_token() {
-            Token::Let => self.parse_let(),
-            Token::Print => self.parse_print(),
-            Token::For => self.parse_for(),
-            Token::If => self.parse_if(),
-            Token::Goto => self.parse_goto(),
-            Token::Input => self.parse_input(),
-            Token::Rem => {
+            Token::Let => self.parse_let()?,
+            Token::Identifier(_) if self.tokens.get(self.position + 1) == Some(&Token::Equal) => self.parse_let()?,
+            Token::Print => self.parse_print()?,
+            Token::For => self.parse_for()?,
+            Token::If => self.parse_if()?,
+            Token::Goto => self.parse_goto()?,
+            Token::Gosub => self.parse_gosub()?,
+            Token::Return => {
+                self.advance();
+                StatementNode::Return
+            }
+            Token::Input => self.parse_input()?,
+            Token::Randomize => self.parse_randomize()?,
+            Token::Tron => {
+                self.advance();
+                StatementNode::Tron
+            }
+            Token::Troff => {
+                self.advance();
+                StatementNode::Troff
+            }
+            Token::Rem(comment) => {
+                let comment = comment.clone();
                 self.advance();
-                StatementNode::Rem
+                StatementNode::Rem(comment)
             }
             Token::End => {
                 self.advance();
@@ -401,24 +501,46 @@ impl Parser {
             }
             Token::Newline | Token::Eof => {
                 // Just a line number or empty line
-                StatementNode::Rem
+                StatementNode::Empty
             }
-            _ => panic!("Unexpected token at start of statement: {:?}", self.current_token()),
+            _ => return self.error(format!("Unexpected token at start of statement: {:?}", self.current_token())),
         };
 
-        Statement { label, node }
+        // Extend the span over the whole statement
+        if let Some(last) = self.position.checked_sub(1).and_then(|i| self.spans.get(i)) {
+            span.end = span.end.max(last.end);
+        }
+
+        Ok(Statement { label, node, span })
+    }
+
+    /// Parse a statement that must be the last thing on its line.
+    fn parse_line(&mut self) -> Result<Statement, Diagnostic> {
+        let stmt = self.parse_statement()?;
+        if !matches!(self.current_token(), Token::Newline | Token::Eof) {
+            return self.error(format!("Unexpected {:?} after statement", self.current_token()));
+        }
+        Ok(stmt)
     }
 
-    pub fn parse(&mut self) -> Vec<Statement> {
+    /// Parse the whole program, collecting every syntax error rather than
+    /// stopping at the first one.
+    pub fn parse(&mut self) -> Result<Vec<Statement>, Vec<Diagnostic>> {
         let mut statements = Vec::new();
 
         self.skip_newlines();
         while self.current_token() != &Token::Eof {
-            statements.push(self.parse_statement());
+            match self.parse_line() {
+                Ok(stmt) => statements.push(stmt),
+                Err(error) => self.recover(error),
+            }
             self.skip_newlines();
         }
 
-        statements
+        if self.errors.is_empty() {
+            Ok(statements)
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 }
-
@@ -0,0 +1,256 @@
+use crate::lexer::{Lexer, Span, Token};
+use std::collections::{HashMap, HashSet};
+
+/// Highest line number BASIC accepts.
+pub const MAX_LINE_NUMBER: i64 = 65529;
+
+/// Parameters of `RENUM [new][,old][,inc]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenumOptions {
+    /// First number of the renumbered block.
+    pub new_start: i64,
+    /// First existing line to renumber; `None` means the first line of the program.
+    pub old_start: Option<i64>,
+    /// Increment between renumbered lines.
+    pub increment: i64,
+}
+
+impl Default for RenumOptions {
+    fn default() -> Self {
+        RenumOptions {
+            new_start: 10,
+            old_start: None,
+            increment: 10,
+        }
+    }
+}
+
+impl RenumOptions {
+    /// Parse the argument list of a RENUM command, e.g. `1000,,20`.
+    /// Omitted fields keep their default values.
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut options = RenumOptions::default();
+        let fields: Vec<&str> = args.split(',').map(|f| f.trim()).collect();
+        if fields.len() > 3 {
+            return Err("RENUM takes at most three arguments".to_string());
+        }
+
+        let number = |field: &str| -> Result<Option<i64>, String> {
+            if field.is_empty() {
+                return Ok(None);
+            }
+            match field.parse::<i64>() {
+                Ok(n) if (0..=MAX_LINE_NUMBER).contains(&n) => Ok(Some(n)),
+                _ => Err(format!("Illegal RENUM argument: {}", field)),
+            }
+        };
+
+        if let Some(n) = number(fields[0])? {
+            options.new_start = n;
+        }
+        if fields.len() > 1 {
+            options.old_start = number(fields[1])?;
+        }
+        if fields.len() > 2 && let Some(n) = number(fields[2])? {
+            if n == 0 {
+                return Err("RENUM increment must not be zero".to_string());
+            }
+            options.increment = n;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Result of a successful renumbering.
+#[derive(Debug, Clone)]
+pub struct Renumbered {
+    pub source: String,
+    /// The new number of every line that was renumbered, by its old one.
+    pub mapping: HashMap<i64, i64>,
+    /// References to lines that do not exist; they are left untouched.
+    pub warnings: Vec<String>,
+}
+
+/// Renumber the program in `source`, rewriting line labels and every
+/// reference to them (GOTO, GOSUB, THEN, ELSE, ON ... GOTO/GOSUB lists,
+/// RESTORE, RESUME and ERL comparisons).
+///
+/// GW-BASIC only rewrote `ERL <op> n`; `n <op> ERL` is rewritten too, as
+/// it names a line just the same.
+///
+/// The rewrite happens on the source text, so spacing and comments are kept.
+pub fn renumber(source: &str, options: &RenumOptions) -> Result<Renumbered, String> {
+    let tokens = Lexer::new(source).tokenize_spanned().map_err(|errors| {
+        let messages: Vec<String> = errors
+            .iter()
+            .map(|e| format!("{}:{}: {}", e.span.line, e.span.column, e.message))
+            .collect();
+        messages.join("\n")
+    })?;
+
+    let labels = line_labels(&tokens);
+    let mapping = build_mapping(&labels, options)?;
+
+    let mut replacements: Vec<(Span, i64)> = labels
+        .iter()
+        .filter_map(|(old, span)| mapping.get(old).map(|new| (*span, *new)))
+        .collect();
+
+    let mut warnings = Vec::new();
+    for reference in line_references(&tokens) {
+        match mapping.get(&reference.target) {
+            Some(new) => replacements.push((reference.span, *new)),
+            None if !labels.iter().any(|(n, _)| *n == reference.target) => {
+                let location = match reference.line {
+                    Some(line) => format!(" in {}", line),
+                    None => String::new(),
+                };
+                warnings.push(format!("Undefined line {}{}", reference.target, location));
+            }
+            None => {}
+        }
+    }
+
+    replacements.sort_by_key(|(span, _)| span.start);
+    let chars: Vec<char> = source.chars().collect();
+    let mut result = String::with_capacity(source.len());
+    let mut position = 0;
+    for (span, new) in replacements {
+        result.extend(&chars[position..span.start]);
+        result.push_str(&new.to_string());
+        position = span.end;
+    }
+    result.extend(&chars[position..]);
+
+    Ok(Renumbered { source: result, mapping, warnings })
+}
+
+/// The line numbers that label lines, with where they appear.
+pub fn line_labels(tokens: &[(Token, Span)]) -> Vec<(i64, Span)> {
+    let mut labels = Vec::new();
+    let mut at_line_start = true;
+    for (token, span) in tokens {
+        if at_line_start && let Token::Number(n) = token {
+            labels.push((*n, *span));
+        }
+        at_line_start = *token == Token::Newline;
+    }
+    labels
+}
+
+/// A use of a line number as a jump target or similar.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineReference {
+    pub target: i64,
+    pub span: Span,
+    /// The line the reference appears on, if it is numbered.
+    pub line: Option<i64>,
+}
+
+/// Every reference to a line number in the token stream.
+pub fn line_references(tokens: &[(Token, Span)]) -> Vec<LineReference> {
+    let mut references = Vec::new();
+    let mut current_line: Option<i64> = None;
+    let mut at_line_start = true;
+    for (i, (token, span)) in tokens.iter().enumerate() {
+        if at_line_start {
+            current_line = match token {
+                Token::Number(n) => Some(*n),
+                _ => None,
+            };
+        }
+        // <line> <op> ERL, ERL's comparison the other way round
+        if !at_line_start
+            && let Token::Number(target) = token
+            && tokens.get(i + 1).is_some_and(|(op, _)| is_comparison(op))
+            && tokens.get(i + 2).is_some_and(|(erl, _)| is_erl(erl))
+        {
+            references.push(LineReference { target: *target, span: *span, line: current_line });
+        }
+        at_line_start = *token == Token::Newline;
+
+        let mut next = i + 1;
+        for _ in 0..reference_count(tokens, i) {
+            let Some((Token::Number(target), span)) = tokens.get(next) else {
+                break;
+            };
+            references.push(LineReference { target: *target, span: *span, line: current_line });
+            next += 1;
+            // ON ... GOTO/GOSUB carry a comma-separated list of targets.
+            if matches!(tokens.get(next), Some((Token::Comma, _))) {
+                next += 1;
+            } else {
+                break;
+            }
+        }
+    }
+    references
+}
+
+/// Map every renumbered line to its new number, refusing mappings that would
+/// change the order of the program or collide with an existing line.
+fn build_mapping(labels: &[(i64, Span)], options: &RenumOptions) -> Result<HashMap<i64, i64>, String> {
+    let old_start = options
+        .old_start
+        .unwrap_or_else(|| labels.first().map(|(n, _)| *n).unwrap_or(0));
+
+    let mut seen = HashSet::new();
+    if let Some((line, _)) = labels.iter().find(|(old, _)| !seen.insert(*old)) {
+        return Err(format!("Illegal function call: line {} is defined more than once", line));
+    }
+
+    let mut mapping = HashMap::new();
+    let mut numbered = Vec::with_capacity(labels.len());
+    let mut next_number = options.new_start;
+    for (old, _) in labels {
+        if *old >= old_start {
+            if next_number > MAX_LINE_NUMBER {
+                return Err(format!("Illegal function call: line {} would exceed {}", old, MAX_LINE_NUMBER));
+            }
+            mapping.insert(*old, next_number);
+            numbered.push((*old, next_number));
+            next_number += options.increment;
+        } else {
+            numbered.push((*old, *old));
+        }
+    }
+
+    for pair in numbered.windows(2) {
+        let ((old_a, new_a), (old_b, new_b)) = (pair[0], pair[1]);
+        if new_a == new_b {
+            return Err(format!("Illegal function call: lines {} and {} would both become {}", old_a, old_b, new_a));
+        }
+        if new_a > new_b {
+            return Err(format!("Illegal function call: line {} would move before line {}", old_b, old_a));
+        }
+    }
+
+    Ok(mapping)
+}
+
+/// How many line-number references may follow the token at `index`.
+fn reference_count(tokens: &[(Token, Span)], index: usize) -> usize {
+    match &tokens[index].0 {
+        Token::Goto | Token::Gosub => usize::MAX,
+        Token::Then | Token::Else => 1,
+        Token::Identifier(name) => match name.to_uppercase().as_str() {
+            "RESTORE" | "RESUME" => 1,
+            _ => 0,
+        },
+        // ERL <op> <line>
+        token if is_comparison(token) && index > 0 && is_erl(&tokens[index - 1].0) => 1,
+        _ => 0,
+    }
+}
+
+fn is_comparison(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Equal | Token::NotEqual | Token::LessThan | Token::LessOrEqual | Token::GreaterThan | Token::GreaterOrEqual
+    )
+}
+
+fn is_erl(token: &Token) -> bool {
+    matches!(token, Token::Identifier(name) if name.eq_ignore_ascii_case("ERL"))
+}
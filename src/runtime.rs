@@ -0,0 +1,44 @@
+//! The C runtime the generated code calls into.
+//!
+//! `basic_rt.h` and `basic_rt.c` are compiled into this crate. Generated C
+//! either carries a copy of both at the top ([`Runtime::Inline`]) or
+//! includes the header and is compiled together with `basic_rt.c`
+//! ([`Runtime::External`]).
+//!
+//! It covers what the language has: printing numbers and string literals,
+//! numeric INPUT, RND, errors, tracing and arithmetic. There are no string
+//! variables or files in the language, so no entry points for them either.
+
+/// The runtime's header.
+pub const HEADER: &str = include_str!("runtime/basic_rt.h");
+/// The runtime's implementation.
+pub const SOURCE: &str = include_str!("runtime/basic_rt.c");
+
+pub const HEADER_NAME: &str = "basic_rt.h";
+pub const SOURCE_NAME: &str = "basic_rt.c";
+
+/// Where the generated C gets its runtime from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Runtime {
+    /// Paste the runtime into the generated file, which then stands alone.
+    #[default]
+    Inline,
+    /// `#include "basic_rt.h"` and build with `basic_rt.c` alongside.
+    External,
+}
+
+impl Runtime {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "inline" => Some(Runtime::Inline),
+            "external" => Some(Runtime::External),
+            _ => None,
+        }
+    }
+}
+
+/// The whole runtime as one block of C, for [`Runtime::Inline`].
+pub fn inline_source() -> String {
+    let source = SOURCE.replace(&format!("#include \"{}\"\n", HEADER_NAME), "");
+    format!("{}\n{}", HEADER, source)
+}
@@ -0,0 +1,260 @@
+#include "basic_rt.h"
+
+#include <float.h>
+#include <string.h>
+#include <time.h>
+
+int rt_line = 0;
+int rt_tracing = 0;
+
+static int rt_continue_after_errors = 1;
+
+void rt_init(int continue_after_errors) {
+    rt_continue_after_errors = continue_after_errors;
+}
+
+void rt_error(const char *message, int fatal) {
+    fflush(stdout);
+    if (rt_line > 0) {
+        fprintf(stderr, "%s in %d\n", message, rt_line);
+    } else {
+        fprintf(stderr, "%s\n", message);
+    }
+    if (fatal) {
+        exit(1);
+    }
+}
+
+void rt_trace(int line) {
+    if (rt_tracing) {
+        printf("[%d]", line);
+    }
+}
+
+void rt_print_number(double x) {
+    printf("%g ", x);
+}
+
+void rt_print_single(double x) {
+    if (x == 0.0) {
+        fputs(" 0 ", stdout);
+        return;
+    }
+    if (isnan(x)) {
+        printf("%g ", x);
+        return;
+    }
+    /* Seven significant digits, without the zeros at the end */
+    char digits[16];
+    snprintf(digits, sizeof digits, "%.6e", fabs(x));
+    int exponent = atoi(strchr(digits, 'e') + 1);
+    char mantissa[8];
+    int count = 0;
+    for (const char *p = digits; *p != 'e'; p++) {
+        if (*p != '.') {
+            mantissa[count++] = *p;
+        }
+    }
+    while (count > 1 && mantissa[count - 1] == '0') {
+        count--;
+    }
+    mantissa[count] = '\0';
+
+    putchar(x < 0.0 ? '-' : ' ');
+    if (exponent >= 7 || exponent < -2) {
+        printf("%c", mantissa[0]);
+        if (count > 1) {
+            printf(".%s", mantissa + 1);
+        }
+        printf("E%c%02d ", exponent < 0 ? '-' : '+', abs(exponent));
+    } else if (exponent < 0) {
+        putchar('.');
+        for (int i = exponent + 1; i < 0; i++) {
+            putchar('0');
+        }
+        printf("%s ", mantissa);
+    } else if (count <= exponent + 1) {
+        fputs(mantissa, stdout);
+        for (int i = count; i < exponent + 1; i++) {
+            putchar('0');
+        }
+        putchar(' ');
+    } else {
+        printf("%.*s.%s ", exponent + 1, mantissa, mantissa + exponent + 1);
+    }
+}
+
+void rt_print_string(const char *s) {
+    fputs(s, stdout);
+}
+
+void rt_print_newline(void) {
+    putchar('\n');
+}
+
+/* Prompt until a number is typed. The program ends when input runs out. */
+static double rt_read_number(const char *prompt) {
+    char line[256];
+    for (;;) {
+        fputs(prompt, stdout);
+        fflush(stdout);
+        if (fgets(line, sizeof line, stdin) == NULL) {
+            /* Nothing left to read: end the program as END would */
+            putchar('\n');
+            exit(0);
+        }
+        char *end;
+        double value = strtod(line, &end);
+        end += strspn(end, " \t\r\n");
+        if (end != line && *end == '\0') {
+            return value;
+        }
+        printf("?Redo from start\n");
+    }
+}
+
+void rt_input(double *x) {
+    *x = rt_read_number("? ");
+}
+
+/* GW-BASIC's generator: a 24-bit linear congruential sequence, starting
+   from the same seed every run, so RND gives GW-BASIC's numbers, .1213501,
+   .651861, .8688611 and so on. How RND(x) for x < 0 and RANDOMIZE make a
+   new seed is this runtime's own: the same argument always gives the same
+   sequence, as in GW-BASIC, but not the sequence GW-BASIC gives. */
+#define RT_RND_MODULUS 16777216u
+#define RT_RND_SEED 0x4fc752u
+#define RT_RND_MULTIPLIER 214013u
+#define RT_RND_INCREMENT 2531011u
+
+static uint32_t rt_seed = RT_RND_SEED;
+static int rt_seed_fixed = 0;
+static double rt_fixed_seed;
+
+static void rt_next(void) {
+    rt_seed = (rt_seed * RT_RND_MULTIPLIER + RT_RND_INCREMENT) % RT_RND_MODULUS;
+}
+
+double rt_rnd(double x) {
+    if (x < 0.0) {
+        /* Start over from the bits of x, so RND(-n) always gives the same */
+        float f = (float)x;
+        uint32_t bits;
+        memcpy(&bits, &f, sizeof bits);
+        rt_seed = (bits + (bits >> 24)) % RT_RND_MODULUS;
+        rt_next();
+    } else if (x > 0.0) {
+        rt_next();
+    }
+    return (double)rt_seed / RT_RND_MODULUS;
+}
+
+void rt_randomize(double n) {
+    /* The low 16 bits of n replace the top of the seed */
+    double whole = isnan(n) ? 0.0 : fmod(trunc(n), 65536.0);
+    uint32_t bits = (uint32_t)(int32_t)whole & 0xffffu;
+    rt_seed = (bits << 8) | (rt_seed & 0xffu);
+}
+
+void rt_randomize_timer(void) {
+    rt_randomize(rt_seed_fixed ? rt_fixed_seed : rt_timer());
+}
+
+void rt_randomize_prompt(void) {
+    if (rt_seed_fixed) {
+        rt_randomize(rt_fixed_seed);
+        return;
+    }
+    rt_randomize(rt_read_number("Random number seed (-32768 to 32767)? "));
+}
+
+void rt_fix_seed(double n) {
+    rt_seed_fixed = 1;
+    rt_fixed_seed = n;
+    rt_randomize(n);
+}
+
+double rt_timer(void) {
+    time_t now = time(NULL);
+    struct tm *local = localtime(&now);
+    return local->tm_hour * 3600.0 + local->tm_min * 60.0 + local->tm_sec;
+}
+
+static double rt_overflow(double x) {
+    if (isinf(x)) {
+        rt_error("Overflow", !rt_continue_after_errors);
+        return copysign(DBL_MAX, x);
+    }
+    return x;
+}
+
+static double rt_division_by_zero(double sign) {
+    rt_error("Division by zero", !rt_continue_after_errors);
+    return copysign(DBL_MAX, sign);
+}
+
+double rt_add(double a, double b) {
+    return rt_overflow(a + b);
+}
+
+double rt_sub(double a, double b) {
+    return rt_overflow(a - b);
+}
+
+double rt_mul(double a, double b) {
+    return rt_overflow(a * b);
+}
+
+double rt_div(double a, double b) {
+    if (b == 0.0) {
+        return rt_division_by_zero(a);
+    }
+    return rt_overflow(a / b);
+}
+
+double rt_pow(double a, double b) {
+    if (a == 0.0 && b < 0.0) {
+        return rt_division_by_zero(1.0);
+    }
+    if (a < 0.0 && b != floor(b)) {
+        rt_error("Illegal function call", 1);
+    }
+    return rt_overflow(pow(a, b));
+}
+
+double rt_sqr(double x) {
+    if (x < 0.0) {
+        rt_error("Illegal function call", 1);
+    }
+    return sqrt(x);
+}
+
+double rt_exp(double x) {
+    return rt_overflow(exp(x));
+}
+
+/* MBF singles keep 24 bits of mantissa and exponents 2^-128 to 2^127 */
+#define RT_MBF_BITS 24
+#define RT_MBF_MAX_EXPONENT 127
+#define RT_MBF_MIN_EXPONENT (-127)
+
+double rt_mbf(double x) {
+    if (x == 0.0 || isnan(x)) {
+        return x;
+    }
+    int exponent;
+    double mantissa = frexp(x, &exponent);
+    double scaled = round(ldexp(mantissa, RT_MBF_BITS));
+    if (fabs(scaled) == ldexp(1.0, RT_MBF_BITS)) {
+        scaled /= 2.0;
+        exponent++;
+    }
+    if (isinf(x) || exponent > RT_MBF_MAX_EXPONENT) {
+        rt_error("Overflow", !rt_continue_after_errors);
+        return copysign(ldexp(ldexp(1.0, RT_MBF_BITS) - 1.0, RT_MBF_MAX_EXPONENT - RT_MBF_BITS), x);
+    }
+    if (exponent < RT_MBF_MIN_EXPONENT) {
+        return 0.0;
+    }
+    return ldexp(scaled, exponent - RT_MBF_BITS);
+}
@@ -0,0 +1,67 @@
+/* Runtime support for C generated from BASIC programs. The language has
+   no string variables or files, so only printing string literals is here. */
+#ifndef BASIC_RT_H
+#define BASIC_RT_H
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <math.h>
+
+/* The BASIC line running, for error messages; 0 if not tracked. */
+extern int rt_line;
+
+/* Whether TRON is on. Programs without TRON or --trace never look. */
+extern int rt_tracing;
+
+/* Print [line] as GW-BASIC does before each line runs, if TRON is on. */
+void rt_trace(int line);
+
+/* Set up the runtime. With continue_after_errors, division by zero and
+   overflow print a message and go on with the largest number. */
+void rt_init(int continue_after_errors);
+
+/* Report a runtime error, ending the program if it is fatal. */
+void rt_error(const char *message, int fatal);
+
+void rt_print_number(double x);
+/* PRINT an MBF single the way GW-BASIC does: a sign or space, up to seven
+   digits and a space. */
+void rt_print_single(double x);
+void rt_print_string(const char *s);
+void rt_print_newline(void);
+
+/* INPUT one number into *x. The program ends when input runs out. */
+void rt_input(double *x);
+
+/* RND(x): the next number in [0, 1) for x > 0, the last one again for
+   x = 0, and a fresh sequence seeded from x for x < 0. */
+double rt_rnd(double x);
+
+/* RANDOMIZE n, RANDOMIZE TIMER and RANDOMIZE on its own, which asks. */
+void rt_randomize(double n);
+void rt_randomize_timer(void);
+void rt_randomize_prompt(void);
+
+/* Seed with n, and have RANDOMIZE TIMER and RANDOMIZE on its own use n
+   too, so runs can be repeated (--seed). */
+void rt_fix_seed(double n);
+
+/* TIMER: seconds since midnight. */
+double rt_timer(void);
+
+/* Arithmetic that reports division by zero, overflow and illegal
+   function calls, as used with --checks. */
+double rt_add(double a, double b);
+double rt_sub(double a, double b);
+double rt_mul(double a, double b);
+double rt_div(double a, double b);
+double rt_pow(double a, double b);
+double rt_sqr(double x);
+double rt_exp(double x);
+
+/* x rounded to the nearest MBF single (24-bit mantissa, exponent byte
+   biased by 128), reporting Overflow above 1.701412E+38. */
+double rt_mbf(double x);
+
+#endif
@@ -0,0 +1,257 @@
+// Runtime support for JavaScript generated from BASIC programs.
+
+/** A runtime error that stopped the program. */
+export class BasicError extends Error {
+    constructor(message, line) {
+        super(line === null ? message : `${message} in ${line}`);
+        this.name = "BasicError";
+        /** The message without the line, as GW-BASIC words it. */
+        this.basicMessage = message;
+        /** The BASIC line it happened on, or null. */
+        this.line = line;
+    }
+}
+
+/** Thrown when input runs out, which ends the program as END would. */
+class End {}
+
+/** GW-BASIC's generator, as in basic_rt.c. */
+const RND_MODULUS = 1 << 24;
+const RND_SEED = 0x4fc752;
+const RND_MULTIPLIER = 214013;
+const RND_INCREMENT = 2531011;
+
+/** A number as C's strtod reads it, the whole line being the number. */
+const NUMBER = /^[+-]?(?:(?:\d+\.?\d*|\.\d+)(?:e[+-]?\d+)?|0x[0-9a-f]+|inf(?:inity)?|nan(?:\([0-9a-z_]*\))?)$/i;
+
+/** `x`, a positive finite number, rounded to `digits` significant digits
+    as C rounds them: exactly, with ties to even. Returns the digits and the
+    decimal exponent of the first. */
+function significantDigits(x, digits) {
+    const view = new DataView(new ArrayBuffer(8));
+    view.setFloat64(0, x);
+    const bits = view.getBigUint64(0);
+    let mantissa = bits & 0xfffffffffffffn;
+    let exponent = Number((bits >> 52n) & 0x7ffn);
+    if (exponent === 0) {
+        exponent = 1;
+    } else {
+        mantissa |= 1n << 52n;
+    }
+    exponent -= 1075;
+    // x is numerator / denominator exactly
+    let numerator = exponent >= 0 ? mantissa << BigInt(exponent) : mantissa;
+    let denominator = exponent >= 0 ? 1n : 1n << BigInt(-exponent);
+    const ten = (n) => 10n ** BigInt(n);
+    // Whether x >= 10^n
+    const atLeast = (n) => (n >= 0 ? numerator >= denominator * ten(n) : numerator * ten(-n) >= denominator);
+    let decimal = Math.floor(Math.log10(x));
+    // log10 can be one out either way near powers of ten
+    while (atLeast(decimal + 1)) {
+        decimal++;
+    }
+    while (!atLeast(decimal)) {
+        decimal--;
+    }
+    const shift = digits - 1 - decimal;
+    if (shift >= 0) {
+        numerator *= ten(shift);
+    } else {
+        denominator *= ten(-shift);
+    }
+    let quotient = numerator / denominator;
+    const twice = 2n * (numerator - quotient * denominator);
+    if (twice > denominator || (twice === denominator && quotient % 2n === 1n)) {
+        quotient++;
+    }
+    if (quotient === ten(digits)) {
+        quotient /= 10n;
+        decimal++;
+    }
+    return [quotient.toString(), decimal];
+}
+
+function trimZeros(number) {
+    return number.includes(".") ? number.replace(/0+$/, "").replace(/\.$/, "") : number;
+}
+
+/** `x` as C's `%g` writes it: six significant digits, in exponent form
+    when very large or small. */
+function formatNumber(x) {
+    const view = new DataView(new ArrayBuffer(8));
+    view.setFloat64(0, x);
+    const negative = view.getUint8(0) >= 0x80;
+    const sign = negative ? "-" : "";
+    if (Number.isNaN(x)) {
+        return sign + "nan";
+    }
+    if (!Number.isFinite(x)) {
+        return sign + "inf";
+    }
+    if (x === 0) {
+        return sign + "0";
+    }
+    const [digits, exponent] = significantDigits(Math.abs(x), 6);
+    if (exponent < -4 || exponent >= 6) {
+        const mantissa = trimZeros(digits[0] + "." + digits.slice(1));
+        const magnitude = String(Math.abs(exponent)).padStart(2, "0");
+        return `${sign}${mantissa}e${exponent < 0 ? "-" : "+"}${magnitude}`;
+    }
+    const fixed = exponent < 0 ? "0." + "0".repeat(-exponent - 1) + digits : digits.slice(0, exponent + 1) + "." + digits.slice(exponent + 1);
+    return sign + trimZeros(fixed);
+}
+
+/** The line as a number, or null if it is not one. A blank line is 0, as
+    it is to C's runtime. */
+function parseNumber(line) {
+    const text = line.trim();
+    if (text === "") {
+        return 0;
+    }
+    if (!NUMBER.test(text)) {
+        return null;
+    }
+    const negative = text.startsWith("-");
+    const unsigned = text.replace(/^[+-]/, "").toLowerCase();
+    let value;
+    if (unsigned.startsWith("inf")) {
+        value = Infinity;
+    } else if (unsigned.startsWith("nan")) {
+        value = NaN;
+    } else if (unsigned.startsWith("0x")) {
+        value = parseInt(unsigned.slice(2), 16);
+    } else {
+        value = Number(unsigned);
+    }
+    return negative ? -value : value;
+}
+
+/** x ^ y, as C's pow: 1 ^ y and (-1) ^ infinity are 1 even for NaN y. */
+function pow(x, y) {
+    if (x === 1 || (x === -1 && (y === Infinity || y === -Infinity))) {
+        return 1;
+    }
+    return Math.pow(x, y);
+}
+
+/** What the program's statements share. */
+class Runtime {
+    constructor(io) {
+        this.io = io;
+        this.seed = RND_SEED;
+        /** Used by RANDOMIZE TIMER and RANDOMIZE on its own instead (`--seed`). */
+        this.fixedSeed = null;
+        this.gosubStack = [];
+    }
+
+    error(message, line) {
+        return new BasicError(message, line);
+    }
+
+    printString(text) {
+        this.io.print(text);
+    }
+
+    printNumber(x) {
+        this.io.print(formatNumber(x) + " ");
+    }
+
+    printNewline() {
+        this.io.print("\n");
+    }
+
+    /** Prompt until a number is typed. */
+    async readNumber(prompt) {
+        for (;;) {
+            this.io.print(prompt);
+            const line = await this.io.input();
+            if (line === null || line === undefined) {
+                this.io.print("\n");
+                throw new End();
+            }
+            const value = parseNumber(line);
+            if (value !== null) {
+                return value;
+            }
+            this.io.print("?Redo from start\n");
+        }
+    }
+
+    input() {
+        return this.readNumber("? ");
+    }
+
+    /** GOSUB_STACK_SIZE is defined after the runtime, by the compiler. */
+    gosub(back, line) {
+        if (this.gosubStack.length === GOSUB_STACK_SIZE) {
+            throw this.error("Out of memory", line);
+        }
+        this.gosubStack.push(back);
+    }
+
+    gosubReturn(line) {
+        if (this.gosubStack.length === 0) {
+            throw this.error("RETURN without GOSUB", line);
+        }
+        return this.gosubStack.pop();
+    }
+
+    nextSeed() {
+        // The product is below 2^53, so it is exact
+        this.seed = (this.seed * RND_MULTIPLIER + RND_INCREMENT) % RND_MODULUS;
+    }
+
+    /** RND(x): the next number in [0, 1) for x > 0, the last one again for
+        x = 0, and a fresh sequence seeded from x for x < 0. */
+    rnd(x) {
+        if (x < 0) {
+            const view = new DataView(new ArrayBuffer(4));
+            view.setFloat32(0, x);
+            const bits = view.getUint32(0);
+            this.seed = ((bits + (bits >>> 24)) >>> 0) % RND_MODULUS;
+            this.nextSeed();
+        } else if (x > 0) {
+            this.nextSeed();
+        }
+        return this.seed / RND_MODULUS;
+    }
+
+    /** RANDOMIZE n: the low 16 bits of n replace the top of the seed. */
+    randomize(n) {
+        const bits = (Math.trunc(n) % 65536 | 0) & 0xffff;
+        this.seed = (bits << 8) | (this.seed & 0xff);
+    }
+
+    randomizeTimer() {
+        this.randomize(this.fixedSeed ?? Runtime.timer());
+    }
+
+    async randomizePrompt() {
+        const n = this.fixedSeed ?? (await this.readNumber("Random number seed (-32768 to 32767)? "));
+        this.randomize(n);
+    }
+
+    fixSeed(n) {
+        this.fixedSeed = n;
+        this.randomize(n);
+    }
+
+    /** TIMER: seconds since midnight, local time. */
+    static timer() {
+        const now = new Date();
+        return now.getHours() * 3600 + now.getMinutes() * 60 + now.getSeconds();
+    }
+}
+
+/** Run the program, printing with `print(text)` and reading lines with
+    `input()`, which returns a string, a promise of one, or null once input
+    has run out. Rejects with a BasicError if the program stops on one. */
+export async function run({ print, input }) {
+    try {
+        await program(new Runtime({ print, input }));
+    } catch (error) {
+        if (!(error instanceof End)) {
+            throw error;
+        }
+    }
+}
@@ -0,0 +1,234 @@
+// Runtime support for Rust generated from BASIC programs.
+
+// Not every program uses all of it, and the program keeps BASIC's names
+#![allow(
+    dead_code,
+    non_snake_case,
+    unreachable_code,
+    unused_assignments,
+    unused_labels,
+    unused_mut,
+    unused_parens,
+    unused_variables
+)]
+
+/// Where a BASIC program prints to and reads from.
+pub trait Io {
+    /// Write `text` as it is.
+    fn print(&mut self, text: &str);
+    /// Read a line, or `None` when input has run out.
+    fn input(&mut self) -> Option<String>;
+}
+
+/// The terminal: stdout and stdin.
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn print(&mut self, text: &str) {
+        print!("{}", text);
+    }
+
+    fn input(&mut self) -> Option<String> {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line),
+        }
+    }
+}
+
+/// A runtime error that stopped the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub message: &'static str,
+    /// The BASIC line it happened on.
+    pub line: Option<i64>,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{} in {}", self.message, line),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Why a program stopped before its last line.
+enum Stop {
+    /// Input ran out, which ends the program as END would.
+    End,
+    Error(Error),
+}
+
+/// GW-BASIC's generator, as in basic_rt.c.
+const RND_MODULUS: u32 = 1 << 24;
+const RND_SEED: u32 = 0x4fc752;
+const RND_MULTIPLIER: u32 = 214013;
+const RND_INCREMENT: u32 = 2531011;
+
+/// What the program's statements share.
+struct Runtime<'a, I: Io> {
+    io: &'a mut I,
+    seed: u32,
+    /// Used by RANDOMIZE TIMER and RANDOMIZE on its own instead (`--seed`).
+    fixed_seed: Option<f64>,
+    gosub_stack: Vec<usize>,
+}
+
+impl<'a, I: Io> Runtime<'a, I> {
+    fn new(io: &'a mut I) -> Self {
+        Runtime { io, seed: RND_SEED, fixed_seed: None, gosub_stack: Vec::new() }
+    }
+
+    fn error(&self, message: &'static str, line: Option<i64>) -> Stop {
+        Stop::Error(Error { message, line })
+    }
+
+    fn print_string(&mut self, text: &str) {
+        self.io.print(text);
+    }
+
+    fn print_number(&mut self, x: f64) {
+        let text = format!("{} ", Self::format_number(x));
+        self.io.print(&text);
+    }
+
+    fn print_newline(&mut self) {
+        self.io.print("\n");
+    }
+
+    /// `x` as C's `%g` writes it: six significant digits, in exponent form
+    /// when very large or small.
+    fn format_number(x: f64) -> String {
+        if x.is_nan() {
+            return if x.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+        }
+        if x.is_infinite() {
+            return if x < 0.0 { "-inf" } else { "inf" }.to_string();
+        }
+        if x == 0.0 {
+            return if x.is_sign_negative() { "-0" } else { "0" }.to_string();
+        }
+        let scientific = format!("{:.5e}", x);
+        let (mantissa, exponent) = scientific.split_once('e').expect("exponent form");
+        let exponent: i32 = exponent.parse().expect("exponent");
+        if !(-4..6).contains(&exponent) {
+            let mantissa = Self::trim_zeros(mantissa);
+            let sign = if exponent < 0 { '-' } else { '+' };
+            return format!("{}e{}{:02}", mantissa, sign, exponent.abs());
+        }
+        let fixed = format!("{:.*}", (5 - exponent) as usize, x);
+        Self::trim_zeros(&fixed).to_string()
+    }
+
+    fn trim_zeros(number: &str) -> &str {
+        if number.contains('.') {
+            number.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            number
+        }
+    }
+
+    /// Prompt until a number is typed.
+    fn read_number(&mut self, prompt: &str) -> Result<f64, Stop> {
+        loop {
+            self.io.print(prompt);
+            let Some(line) = self.io.input() else {
+                self.io.print("\n");
+                return Err(Stop::End);
+            };
+            match line.trim().parse() {
+                Ok(value) => return Ok(value),
+                Err(_) => self.io.print("?Redo from start\n"),
+            }
+        }
+    }
+
+    fn input(&mut self) -> Result<f64, Stop> {
+        self.read_number("? ")
+    }
+
+    /// GOSUB_STACK_SIZE is defined after the runtime, by the compiler.
+    fn gosub(&mut self, back: usize, line: Option<i64>) -> Result<(), Stop> {
+        if self.gosub_stack.len() == GOSUB_STACK_SIZE {
+            return Err(self.error("Out of memory", line));
+        }
+        self.gosub_stack.push(back);
+        Ok(())
+    }
+
+    fn gosub_return(&mut self, line: Option<i64>) -> Result<usize, Stop> {
+        self.gosub_stack.pop().ok_or_else(|| self.error("RETURN without GOSUB", line))
+    }
+
+    fn next_seed(&mut self) {
+        self.seed = self.seed.wrapping_mul(RND_MULTIPLIER).wrapping_add(RND_INCREMENT) % RND_MODULUS;
+    }
+
+    /// RND(x): the next number in [0, 1) for x > 0, the last one again for
+    /// x = 0, and a fresh sequence seeded from x for x < 0.
+    fn rnd(&mut self, x: f64) -> f64 {
+        if x < 0.0 {
+            let bits = (x as f32).to_bits();
+            self.seed = bits.wrapping_add(bits >> 24) % RND_MODULUS;
+            self.next_seed();
+        } else if x > 0.0 {
+            self.next_seed();
+        }
+        self.seed as f64 / RND_MODULUS as f64
+    }
+
+    /// RANDOMIZE n: the low 16 bits of n replace the top of the seed.
+    fn randomize(&mut self, n: f64) {
+        let whole = if n.is_nan() { 0.0 } else { n.trunc() % 65536.0 };
+        let bits = (whole as i32 as u32) & 0xffff;
+        self.seed = (bits << 8) | (self.seed & 0xff);
+    }
+
+    fn randomize_timer(&mut self) {
+        let n = self.fixed_seed.unwrap_or_else(Self::timer);
+        self.randomize(n);
+    }
+
+    fn randomize_prompt(&mut self) -> Result<(), Stop> {
+        let n = match self.fixed_seed {
+            Some(n) => n,
+            None => self.read_number("Random number seed (-32768 to 32767)? ")?,
+        };
+        self.randomize(n);
+        Ok(())
+    }
+
+    fn fix_seed(&mut self, n: f64) {
+        self.fixed_seed = Some(n);
+        self.randomize(n);
+    }
+
+    /// TIMER: seconds since midnight, UTC.
+    fn timer() -> f64 {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        (now.as_secs() % 86400) as f64
+    }
+}
+
+/// Run the program, printing to and reading from `io`.
+pub fn run<I: Io>(io: &mut I) -> Result<(), Error> {
+    match program(Runtime::new(io)) {
+        Ok(()) | Err(Stop::End) => Ok(()),
+        Err(Stop::Error(error)) => Err(error),
+    }
+}
+
+fn main() {
+    if let Err(error) = run(&mut StdIo) {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+}
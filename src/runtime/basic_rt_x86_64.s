@@ -0,0 +1,809 @@
+# Runtime support for x86-64 assembly generated from BASIC programs.
+#
+# Linux only: it talks to the kernel through system calls and needs no C
+# library. Numbers are doubles in SSE registers; EXP, ^ and turning numbers
+# into text and back use the x87 unit for its extra precision.
+#
+# Routines take and return doubles in %xmm0 (and %xmm1), strings as a
+# pointer in %rdi and a length in %rsi, and may change any register the
+# System V ABI lets a callee change. The errors take the BASIC line in %rdx,
+# or -1.
+
+    .set SYS_READ, 0
+    .set SYS_WRITE, 1
+    .set SYS_TIME, 201
+    .set SYS_EXIT_GROUP, 231
+    .set OUT_SIZE, 4096
+    .set IN_SIZE, 4096
+    .set LINE_SIZE, 256
+    # GOSUB_STACK_SIZE is set after the runtime, by the compiler
+
+    .bss
+    .balign 8
+out_len: .zero 8
+in_pos: .zero 8
+in_len: .zero 8
+gosub_sp: .zero 8
+gosub_stack: .zero 8 * GOSUB_STACK_SIZE
+out_buf: .zero OUT_SIZE
+in_buf: .zero IN_SIZE
+line_buf: .zero LINE_SIZE
+num_buf: .zero 32
+
+    .data
+    .balign 4
+# GW-BASIC's generator, as in basic_rt.c
+rnd_seed: .long 0x4fc752
+
+    .section .rodata
+    .balign 8
+one: .double 1.0
+ten: .double 10.0
+hundred_thousand: .double 100000.0
+million: .double 1000000.0
+infinity: .quad 0x7ff0000000000000
+exp_limit: .double 1000.0
+exp2_limit: .double 1100.0
+rnd_scale: .double 5.9604644775390625e-08     # 2^-24
+nan_text: .ascii "nan"
+inf_text: .ascii "inf"
+in_text: .ascii " in "
+input_prompt: .ascii "? "
+    .set INPUT_PROMPT_LEN, . - input_prompt
+seed_prompt: .ascii "Random number seed (-32768 to 32767)? "
+    .set SEED_PROMPT_LEN, . - seed_prompt
+redo_text: .ascii "?Redo from start\n"
+    .set REDO_LEN, . - redo_text
+undefined_line_text: .ascii "Undefined line number"
+    .set UNDEFINED_LINE_LEN, . - undefined_line_text
+return_text: .ascii "RETURN without GOSUB"
+    .set RETURN_LEN, . - return_text
+memory_text: .ascii "Out of memory"
+    .set MEMORY_LEN, . - memory_text
+
+    .text
+
+# Write out_buf to the file descriptor in %edi and empty it.
+rt_flush_to:
+    movl %edi, %r8d
+    leaq out_buf(%rip), %rsi
+    movq out_len(%rip), %rdx
+1:  testq %rdx, %rdx
+    jz 2f
+    movl $SYS_WRITE, %eax
+    movl %r8d, %edi
+    syscall
+    testq %rax, %rax
+    jle 2f
+    addq %rax, %rsi
+    subq %rax, %rdx
+    jmp 1b
+2:  movq $0, out_len(%rip)
+    ret
+
+rt_flush:
+    movl $1, %edi
+    jmp rt_flush_to
+
+# Print the byte in %dil.
+rt_putc:
+    movq out_len(%rip), %rax
+    cmpq $OUT_SIZE, %rax
+    jb 1f
+    pushq %rdi
+    call rt_flush
+    popq %rdi
+    xorl %eax, %eax
+1:  leaq out_buf(%rip), %rcx
+    movb %dil, (%rcx,%rax)
+    incq %rax
+    movq %rax, out_len(%rip)
+    ret
+
+rt_print_string:
+    pushq %rbx
+    pushq %r12
+    movq %rdi, %rbx
+    leaq (%rdi,%rsi), %r12
+1:  cmpq %r12, %rbx
+    jae 2f
+    movzbl (%rbx), %edi
+    call rt_putc
+    incq %rbx
+    jmp 1b
+2:  popq %r12
+    popq %rbx
+    ret
+
+rt_print_newline:
+    movl $'\n', %edi
+    jmp rt_putc
+
+# Print the number in %rax, which is not negative.
+rt_print_integer:
+    subq $24, %rsp
+    leaq 24(%rsp), %rsi
+    movl $10, %ecx
+1:  xorl %edx, %edx
+    divq %rcx
+    addb $'0', %dl
+    decq %rsi
+    movb %dl, (%rsi)
+    testq %rax, %rax
+    jnz 1b
+    movq %rsi, %rdi
+    leaq 24(%rsp), %rsi
+    subq %rdi, %rsi
+    call rt_print_string
+    addq $24, %rsp
+    ret
+
+# Print "message in line" on stderr, then stop.
+rt_error:
+    pushq %rbx
+    movq %rdx, %rbx
+    pushq %rdi
+    pushq %rsi
+    call rt_flush
+    popq %rsi
+    popq %rdi
+    call rt_print_string
+    testq %rbx, %rbx
+    js 1f
+    leaq in_text(%rip), %rdi
+    movl $4, %esi
+    call rt_print_string
+    movq %rbx, %rax
+    call rt_print_integer
+1:  call rt_print_newline
+    movl $2, %edi
+    call rt_flush_to
+    movl $SYS_EXIT_GROUP, %eax
+    movl $1, %edi
+    syscall
+
+rt_undefined_line:
+    leaq undefined_line_text(%rip), %rdi
+    movl $UNDEFINED_LINE_LEN, %esi
+    jmp rt_error
+
+rt_return_without_gosub:
+    leaq return_text(%rip), %rdi
+    movl $RETURN_LEN, %esi
+    jmp rt_error
+
+rt_out_of_memory:
+    leaq memory_text(%rip), %rdi
+    movl $MEMORY_LEN, %esi
+    jmp rt_error
+
+rt_exit:
+    call rt_flush
+    movl $SYS_EXIT_GROUP, %eax
+    xorl %edi, %edi
+    syscall
+
+# %st(0) = 10^%edi, for %edi >= 0.
+rt_pow10:
+    fld1
+    fldl ten(%rip)
+1:  testl %edi, %edi
+    jz 3f
+    testl $1, %edi
+    jz 2f
+    fmul %st(0), %st(1)
+2:  fmul %st(0), %st
+    shrl %edi
+    jmp 1b
+3:  fstp %st(0)
+    ret
+
+# %xmm0 as C's "%g" writes it: six significant digits, in exponent form
+# when very large or small. Returns the text in %rdi and %rsi.
+rt_format:
+    subq $40, %rsp
+    leaq num_buf(%rip), %r8
+    movq %xmm0, %rax
+    btrq $63, %rax
+    jnc 1f
+    movb $'-', (%r8)
+    incq %r8
+1:  movq %rax, (%rsp)
+    movabsq $0x7ff0000000000000, %rcx
+    cmpq %rcx, %rax
+    ja .Lformat_nan
+    je .Lformat_inf
+    testq %rax, %rax
+    jz .Lformat_zero
+    # Guess the decimal exponent e, then scale |x| to six digits by
+    # 10^(5 - e), correcting e until the digits are right
+    fldlg2
+    fldl (%rsp)
+    fyl2x
+    fistpl 8(%rsp)
+    movl 8(%rsp), %r9d
+.Lformat_scale:
+    movl $5, %edi
+    subl %r9d, %edi
+    movl %edi, %r10d
+    jns 2f
+    negl %edi
+2:  call rt_pow10
+    testl %r10d, %r10d
+    js 3f
+    fmull (%rsp)
+    jmp 4f
+3:  fdivrl (%rsp)
+    # Too many digits before the point, or too few
+4:  fldl million(%rip)
+    fcomip %st(1), %st
+    ja 5f
+    fstp %st(0)
+    incl %r9d
+    jmp .Lformat_scale
+5:  fldl hundred_thousand(%rip)
+    fcomip %st(1), %st
+    jbe 6f
+    fstp %st(0)
+    decl %r9d
+    jmp .Lformat_scale
+    # Rounding can still carry into a seventh digit
+6:  fistpl 8(%rsp)
+    movl 8(%rsp), %eax
+    cmpl $1000000, %eax
+    jb 7f
+    movl $100000, %eax
+    incl %r9d
+    # The six digits go at 16(%rsp); %r11 counts those left after
+    # dropping zeros at the end
+7:  movl $6, %r11d
+    movl $10, %ecx
+8:  xorl %edx, %edx
+    divl %ecx
+    addb $'0', %dl
+    movb %dl, 15(%rsp,%r11)
+    decl %r11d
+    jnz 8b
+    movl $6, %r11d
+9:  cmpl $1, %r11d
+    je 19f
+    cmpb $'0', 15(%rsp,%r11)
+    jne 19f
+    decl %r11d
+    jmp 9b
+19: xorl %ecx, %ecx
+    cmpl $-4, %r9d
+    jl .Lformat_exponent
+    cmpl $6, %r9d
+    jge .Lformat_exponent
+    testl %r9d, %r9d
+    js .Lformat_fraction
+    # d.ddd with e + 1 digits before the point
+10: movb 16(%rsp,%rcx), %al
+    movb %al, (%r8)
+    incq %r8
+    incl %ecx
+    cmpl %r9d, %ecx
+    jle 10b
+    cmpl %r11d, %ecx
+    jge .Lformat_done
+    movb $'.', (%r8)
+    incq %r8
+11: movb 16(%rsp,%rcx), %al
+    movb %al, (%r8)
+    incq %r8
+    incl %ecx
+    cmpl %r11d, %ecx
+    jl 11b
+    jmp .Lformat_done
+    # 0.000ddd
+.Lformat_fraction:
+    movw $0x2e30, (%r8)                     # "0."
+    addq $2, %r8
+    movl %r9d, %edx
+12: incl %edx
+    jz 13f
+    movb $'0', (%r8)
+    incq %r8
+    jmp 12b
+13: movb 16(%rsp,%rcx), %al
+    movb %al, (%r8)
+    incq %r8
+    incl %ecx
+    cmpl %r11d, %ecx
+    jl 13b
+    jmp .Lformat_done
+    # d.ddde+XX
+.Lformat_exponent:
+    movb 16(%rsp), %al
+    movb %al, (%r8)
+    incq %r8
+    movl $1, %ecx
+    cmpl %r11d, %ecx
+    jge 15f
+    movb $'.', (%r8)
+    incq %r8
+14: movb 16(%rsp,%rcx), %al
+    movb %al, (%r8)
+    incq %r8
+    incl %ecx
+    cmpl %r11d, %ecx
+    jl 14b
+15: movb $'e', (%r8)
+    movb $'+', 1(%r8)
+    testl %r9d, %r9d
+    jns 16f
+    movb $'-', 1(%r8)
+    negl %r9d
+16: addq $2, %r8
+    movl %r9d, %eax
+    cmpl $100, %eax
+    jb 17f
+    xorl %edx, %edx
+    movl $100, %ecx
+    divl %ecx
+    addb $'0', %al
+    movb %al, (%r8)
+    incq %r8
+    movl %edx, %eax
+17: movb $10, %cl
+    divb %cl
+    addw $0x3030, %ax
+    movw %ax, (%r8)
+    addq $2, %r8
+    jmp .Lformat_done
+.Lformat_nan:
+    leaq nan_text(%rip), %rsi
+    jmp 18f
+.Lformat_inf:
+    leaq inf_text(%rip), %rsi
+18: movzwl (%rsi), %eax
+    movw %ax, (%r8)
+    movb 2(%rsi), %al
+    movb %al, 2(%r8)
+    addq $3, %r8
+    jmp .Lformat_done
+.Lformat_zero:
+    movb $'0', (%r8)
+    incq %r8
+.Lformat_done:
+    leaq num_buf(%rip), %rdi
+    movq %r8, %rsi
+    subq %rdi, %rsi
+    addq $40, %rsp
+    ret
+
+rt_print_number:
+    call rt_format
+    call rt_print_string
+    movl $' ', %edi
+    jmp rt_putc
+
+# The next byte of input in %eax, or -1 at the end.
+rt_getc:
+    movq in_pos(%rip), %rax
+    cmpq in_len(%rip), %rax
+    jb 1f
+    movl $SYS_READ, %eax
+    xorl %edi, %edi
+    leaq in_buf(%rip), %rsi
+    movl $IN_SIZE, %edx
+    syscall
+    testq %rax, %rax
+    jle 2f
+    movq %rax, in_len(%rip)
+    xorl %eax, %eax
+1:  leaq in_buf(%rip), %rcx
+    movzbl (%rcx,%rax), %edx
+    incq %rax
+    movq %rax, in_pos(%rip)
+    movl %edx, %eax
+    ret
+2:  movl $-1, %eax
+    ret
+
+# Read a line into line_buf, without its newline. Returns its length in
+# %rax, or -1 if input has run out.
+rt_read_line:
+    pushq %rbx
+    xorl %ebx, %ebx
+1:  call rt_getc
+    cmpl $-1, %eax
+    je 3f
+    cmpl $'\n', %eax
+    je 2f
+    cmpq $LINE_SIZE, %rbx
+    jae 1b
+    leaq line_buf(%rip), %rcx
+    movb %al, (%rcx,%rbx)
+    incq %rbx
+    jmp 1b
+3:  testq %rbx, %rbx
+    jnz 2f
+    movq $-1, %rbx
+2:  movq %rbx, %rax
+    popq %rbx
+    ret
+
+# Skip blanks from %rdi up to %rsi. Changes only %rdi and %rcx.
+rt_skip_blanks:
+1:  cmpq %rsi, %rdi
+    jae 2f
+    movzbl (%rdi), %ecx
+    cmpl $' ', %ecx
+    je 3f
+    subl $'\t', %ecx
+    cmpl $'\r' - '\t', %ecx
+    ja 2f
+3:  incq %rdi
+    jmp 1b
+2:  ret
+
+# Parse the number in the first %rsi bytes of line_buf, as strtod would,
+# with blanks around it; a blank line is 0. Returns %eax = 1 and the number in %xmm0, or
+# %eax = 0 if the line is not a number.
+rt_parse_number:
+    subq $24, %rsp
+    leaq line_buf(%rip), %rdi
+    addq %rdi, %rsi
+    call rt_skip_blanks
+    # %r8 = negative, %rax = the digits, %r9 = the power of ten to scale
+    # them by, %r10 = digits seen, %r11 = after the point
+    xorl %r8d, %r8d
+    xorl %eax, %eax
+    movq %rax, (%rsp)
+    # A blank line is 0
+    cmpq %rsi, %rdi
+    jae 16f
+    cmpb $'+', (%rdi)
+    je 1f
+    cmpb $'-', (%rdi)
+    jne 2f
+    movl $1, %r8d
+1:  incq %rdi
+2:  xorl %eax, %eax
+    xorl %r9d, %r9d
+    xorl %r10d, %r10d
+    xorl %r11d, %r11d
+    movabsq $100000000000000000, %rdx      # 10^17: more digits no longer fit
+3:  cmpq %rsi, %rdi
+    jae 6f
+    movzbl (%rdi), %ecx
+    cmpl $'.', %ecx
+    jne 4f
+    testl %r11d, %r11d
+    jnz 6f
+    movl $1, %r11d
+    incq %rdi
+    jmp 3b
+4:  subl $'0', %ecx
+    cmpl $9, %ecx
+    ja 6f
+    incl %r10d
+    incq %rdi
+    cmpq %rdx, %rax
+    jae 5f
+    imulq $10, %rax
+    addq %rcx, %rax
+    subl %r11d, %r9d
+    jmp 3b
+5:  testl %r11d, %r11d
+    jnz 3b
+    incl %r9d
+    jmp 3b
+6:  testl %r10d, %r10d
+    jz .Lparse_fail
+    # An exponent, if digits follow the E
+    cmpq %rsi, %rdi
+    jae 10f
+    movzbl (%rdi), %ecx
+    orl $0x20, %ecx
+    cmpl $'e', %ecx
+    jne 10f
+    leaq 1(%rdi), %rdx
+    xorl %r10d, %r10d                       # negative exponent
+    cmpq %rsi, %rdx
+    jae 10f
+    cmpb $'+', (%rdx)
+    je 7f
+    cmpb $'-', (%rdx)
+    jne 8f
+    movl $1, %r10d
+7:  incq %rdx
+8:  xorl %r11d, %r11d                       # the exponent
+9:  cmpq %rsi, %rdx
+    jae 11f
+    movzbl (%rdx), %ecx
+    subl $'0', %ecx
+    cmpl $9, %ecx
+    ja 11f
+    cmpl $100000, %r11d
+    jae 12f
+    imull $10, %r11d
+    addl %ecx, %r11d
+12: incq %rdx
+    jmp 9b
+    # No digits after all if the E or its sign comes last
+11: cmpb $'9', -1(%rdx)
+    ja 10f
+    cmpb $'0', -1(%rdx)
+    jb 10f
+    movq %rdx, %rdi
+    testl %r10d, %r10d
+    jz 13f
+    negl %r11d
+13: addl %r11d, %r9d
+10: call rt_skip_blanks
+    cmpq %rsi, %rdi
+    jne .Lparse_fail
+    # The number is %rax * 10^%r9
+    movq %rax, (%rsp)
+    testq %rax, %rax
+    jz 16f
+    movl %r9d, %edi
+    testl %edi, %edi
+    jns 14f
+    negl %edi
+14: call rt_pow10
+    fildq (%rsp)
+    testl %r9d, %r9d
+    js 15f
+    fmul %st(1), %st
+    jmp 17f
+15: fdiv %st(1), %st
+17: fstp %st(1)
+    fstpl (%rsp)
+16: movq (%rsp), %xmm0
+    testl %r8d, %r8d
+    jz 18f
+    movq (%rsp), %rax
+    btcq $63, %rax
+    movq %rax, %xmm0
+18: movl $1, %eax
+    addq $24, %rsp
+    ret
+.Lparse_fail:
+    xorl %eax, %eax
+    addq $24, %rsp
+    ret
+
+# Prompt with %rdi and %rsi until a number is typed. The program ends when
+# input runs out.
+rt_read_number:
+    pushq %rbx
+    pushq %r12
+    movq %rdi, %rbx
+    movq %rsi, %r12
+1:  movq %rbx, %rdi
+    movq %r12, %rsi
+    call rt_print_string
+    call rt_flush
+    call rt_read_line
+    testq %rax, %rax
+    js 3f
+    movq %rax, %rsi
+    call rt_parse_number
+    testl %eax, %eax
+    jnz 2f
+    leaq redo_text(%rip), %rdi
+    movl $REDO_LEN, %esi
+    call rt_print_string
+    jmp 1b
+2:  popq %r12
+    popq %rbx
+    ret
+    # Nothing left to read: end the program as END would
+3:  call rt_print_newline
+    jmp rt_exit
+
+rt_input:
+    leaq input_prompt(%rip), %rdi
+    movl $INPUT_PROMPT_LEN, %esi
+    jmp rt_read_number
+
+# RND(x): the next number in [0, 1) for x > 0, the last one again for
+# x = 0, and a fresh sequence seeded from x for x < 0.
+rt_rnd:
+    xorpd %xmm1, %xmm1
+    ucomisd %xmm1, %xmm0
+    jp 2f
+    ja 1f
+    je 2f
+    # Start over from the bits of x as a single
+    cvtsd2ss %xmm0, %xmm0
+    movd %xmm0, %eax
+    movl %eax, %ecx
+    shrl $24, %ecx
+    addl %ecx, %eax
+    andl $0xffffff, %eax
+    movl %eax, rnd_seed(%rip)
+1:  imull $214013, rnd_seed(%rip), %eax
+    addl $2531011, %eax
+    andl $0xffffff, %eax
+    movl %eax, rnd_seed(%rip)
+2:  cvtsi2sdl rnd_seed(%rip), %xmm0
+    mulsd rnd_scale(%rip), %xmm0
+    ret
+
+# RANDOMIZE n: the low 16 bits of n replace the top of the seed.
+rt_randomize:
+    xorl %eax, %eax
+    ucomisd %xmm0, %xmm0
+    jp 1f
+    cvttsd2si %xmm0, %rax
+1:  andl $0xffff, %eax
+    shll $8, %eax
+    movzbl rnd_seed(%rip), %ecx
+    orl %ecx, %eax
+    movl %eax, rnd_seed(%rip)
+    ret
+
+rt_randomize_prompt:
+    leaq seed_prompt(%rip), %rdi
+    movl $SEED_PROMPT_LEN, %esi
+    call rt_read_number
+    jmp rt_randomize
+
+# TIMER: seconds since midnight, UTC.
+rt_timer:
+    movl $SYS_TIME, %eax
+    xorl %edi, %edi
+    syscall
+    xorl %edx, %edx
+    movl $86400, %ecx
+    divq %rcx
+    cvtsi2sd %rdx, %xmm0
+    ret
+
+# INT(x): the largest whole number not above x.
+rt_floor:
+    movq %xmm0, %rax
+    btrq $63, %rax
+    movabsq $0x4330000000000000, %rcx      # 2^52, from where every double is whole
+    cmpq %rcx, %rax
+    jae 1f
+    testq %rax, %rax
+    jz 1f
+    cvttsd2si %xmm0, %rax
+    cvtsi2sd %rax, %xmm1
+    ucomisd %xmm0, %xmm1
+    jbe 2f
+    subsd one(%rip), %xmm1
+2:  movapd %xmm1, %xmm0
+1:  ret
+
+# 2^%st(0), popped, in %xmm0. |%st(0)| must not be much above 1100.
+rt_exp2:
+    fld %st(0)
+    frndint
+    fxch %st(1)
+    fsub %st(1), %st
+    f2xm1
+    fld1
+    faddp
+    fscale
+    fstp %st(1)
+    fstpl -8(%rsp)
+    movsd -8(%rsp), %xmm0
+    ret
+
+rt_exp:
+    ucomisd %xmm0, %xmm0
+    jp 1f
+    ucomisd exp_limit(%rip), %xmm0
+    ja 2f
+    movsd exp_limit(%rip), %xmm1
+    xorpd %xmm2, %xmm2
+    subsd %xmm1, %xmm2
+    ucomisd %xmm2, %xmm0
+    jb 3f
+    movsd %xmm0, -8(%rsp)
+    fldl -8(%rsp)
+    fldl2e
+    fmulp
+    jmp rt_exp2
+1:  ret
+2:  movsd infinity(%rip), %xmm0
+    ret
+3:  xorpd %xmm0, %xmm0
+    ret
+
+# x^y, for x in %xmm0 and y in %xmm1, as C's pow gives it.
+rt_pow:
+    subq $24, %rsp
+    # x^0 and 1^y are 1, even for NaN
+    xorpd %xmm2, %xmm2
+    ucomisd %xmm2, %xmm1
+    jp 1f
+    je .Lpow_one
+1:  ucomisd one(%rip), %xmm0
+    jp 2f
+    je .Lpow_one
+2:  ucomisd %xmm0, %xmm0
+    jp .Lpow_nan
+    ucomisd %xmm1, %xmm1
+    jp .Lpow_nan
+    # Whole powers by repeated squaring
+    cvttsd2si %xmm1, %rax
+    cvtsi2sd %rax, %xmm2
+    ucomisd %xmm2, %xmm1
+    jne .Lpow_fraction
+    movq %rax, %rcx
+    testq %rcx, %rcx
+    jns 3f
+    negq %rcx
+3:  movsd %xmm0, (%rsp)
+    fld1
+    fldl (%rsp)
+4:  testq %rcx, %rcx
+    jz 6f
+    testq $1, %rcx
+    jz 5f
+    fmul %st(0), %st(1)
+5:  fmul %st(0), %st
+    shrq %rcx
+    jmp 4b
+6:  fstp %st(0)
+    testq %rax, %rax
+    jns 7f
+    fld1
+    fdiv %st(1), %st
+    fstp %st(1)
+7:  fstpl (%rsp)
+    movsd (%rsp), %xmm0
+    addq $24, %rsp
+    ret
+.Lpow_fraction:
+    xorpd %xmm2, %xmm2
+    ucomisd %xmm2, %xmm0
+    jae 8f
+    # A negative x only has an infinite power apart from whole ones
+    movq %xmm1, %rax
+    btrq $63, %rax
+    movabsq $0x7ff0000000000000, %rcx
+    cmpq %rcx, %rax
+    jne .Lpow_invalid
+    movq %xmm0, %rax
+    btrq $63, %rax
+    movq %rax, %xmm0
+    ucomisd one(%rip), %xmm0
+    je .Lpow_one
+    # 2^(y log2 x), where log2 0 is -inf and the infinities come out of
+    # the limits below
+8:  movsd %xmm1, (%rsp)
+    movsd %xmm0, 8(%rsp)
+    fldl (%rsp)
+    fldl 8(%rsp)
+    fyl2x
+    fstl (%rsp)
+    movsd (%rsp), %xmm0
+    ucomisd exp2_limit(%rip), %xmm0
+    ja 9f
+    movsd exp2_limit(%rip), %xmm1
+    xorpd %xmm2, %xmm2
+    subsd %xmm1, %xmm2
+    ucomisd %xmm2, %xmm0
+    jb 10f
+    addq $24, %rsp
+    jmp rt_exp2
+9:  fstp %st(0)
+    movsd infinity(%rip), %xmm0
+    addq $24, %rsp
+    ret
+10: fstp %st(0)
+    xorpd %xmm0, %xmm0
+    addq $24, %rsp
+    ret
+.Lpow_one:
+    movsd one(%rip), %xmm0
+    addq $24, %rsp
+    ret
+.Lpow_nan:
+    addsd %xmm1, %xmm0
+    addq $24, %rsp
+    ret
+.Lpow_invalid:
+    xorpd %xmm0, %xmm0
+    divsd %xmm0, %xmm0
+    addq $24, %rsp
+    ret
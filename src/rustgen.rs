@@ -0,0 +1,90 @@
+//! Rust source generation, for `--target rust`.
+//!
+//! The output is a standalone safe Rust program: the runtime from
+//! `runtime/basic_rt.rs`, then the BASIC program as one function. Embedded
+//! as a module, it is called as `run(&mut io)` with anything implementing
+//! its `Io` trait; built on its own, `main` runs it on the terminal.
+//!
+//! The program is written by the [`Emitter`] shared with JavaScript, as
+//! `while` and `loop` loops or as `loop { match pc { ... } }`.
+
+use crate::emitter::{Emitter, Syntax};
+
+/// The runtime every generated program starts with.
+pub const RUNTIME: &str = include_str!("runtime/basic_rt.rs");
+
+/// Rust's spelling of the generated code.
+pub struct Rust;
+
+impl Syntax for Rust {
+    const RUNTIME: &'static str = RUNTIME;
+    const RESERVED: &'static [&'static str] = &[
+        "as", "async", "await", "box", "break", "const", "continue", "crate", "do", "dyn", "else", "enum", "extern",
+        "false", "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move",
+        "mut", "override", "pc", "priv", "pub", "ref", "return", "rt", "self", "Self", "static", "struct", "super",
+        "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "value", "virtual", "where", "while",
+        "yield",
+    ];
+    // Written as f64::f(x) so the method is found on literals too
+    const MATH: &'static str = "f64::";
+    const POWER: &'static str = "f64::powf";
+    const TIMER: &'static str = "Runtime::<I>::timer()";
+    const EQUAL: &'static str = "==";
+    const NOT_EQUAL: &'static str = "!=";
+    const LABEL: &'static str = "'";
+    const LOOP: &'static str = "loop";
+    const EXIT: &'static str = "return Ok(());";
+    const FINISH: &'static str = "Ok(())";
+    const PROGRAM: &'static str = "fn program<I: Io>(mut rt: Runtime<I>) -> Result<(), Stop> {";
+    const PC: &'static str = "let mut pc";
+    const DISPATCH: &'static str = "match pc";
+    const DEFAULT: &'static str = "_ => return Ok(()),";
+    const END_CASE: Option<&'static str> = None;
+
+    fn case(index: usize) -> String {
+        format!("{} =>", index)
+    }
+
+    fn test(condition: &str) -> String {
+        condition.to_string()
+    }
+
+    fn method(name: &str, args: &str) -> String {
+        format!("rt.{}({})", name, args)
+    }
+
+    fn checked(call: String) -> String {
+        call + "?"
+    }
+
+    fn awaited(call: String) -> String {
+        call + "?"
+    }
+
+    fn line(line: Option<i64>) -> String {
+        format!("{:?}", line)
+    }
+
+    fn raise(error: String) -> String {
+        format!("return Err({});", error)
+    }
+
+    fn declare(var: &str) -> String {
+        format!("let mut {}: f64 = 0.0;", var)
+    }
+
+    fn constant(name: &str, value: usize) -> String {
+        format!("const {}: usize = {};", name, value)
+    }
+
+    fn print_number(indent: &str, value: &str) -> String {
+        // RND borrows the runtime too, so it runs first
+        if value.contains("rt.") {
+            format!("{}let value = {};\n{}rt.print_number(value);\n", indent, value, indent)
+        } else {
+            format!("{}rt.print_number({});\n", indent, value)
+        }
+    }
+}
+
+pub type RustGenerator = Emitter<Rust>;
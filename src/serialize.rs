@@ -0,0 +1,231 @@
+//! JSON export of tokens and the AST for external tools.
+//!
+//! Both documents carry a `format` name and a `version`. The version is
+//! bumped whenever a field is removed or changes meaning; new fields may be
+//! added without a bump, so consumers should ignore keys they do not know.
+
+use crate::json::JsonValue;
+use crate::lexer::{Span, Token};
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+
+/// Version of the token and AST JSON formats.
+pub const FORMAT_VERSION: i64 = 1;
+
+fn span_json(span: &Span) -> JsonValue {
+    JsonValue::object([
+        ("start", span.start.into()),
+        ("end", span.end.into()),
+        ("line", span.line.into()),
+        ("column", span.column.into()),
+    ])
+}
+
+fn comparison_name(op: &Token) -> &'static str {
+    match op {
+        Token::Equal => "=",
+        Token::NotEqual => "<>",
+        Token::LessThan => "<",
+        Token::LessOrEqual => "<=",
+        Token::GreaterThan => ">",
+        Token::GreaterOrEqual => ">=",
+        _ => "?",
+    }
+}
+
+fn token_json(token: &Token, span: &Span) -> JsonValue {
+    let (kind, value) = match token {
+        Token::Identifier(name) => ("identifier", JsonValue::string(name)),
+        Token::String(s) => ("string", JsonValue::string(s)),
+        Token::Number(n) => ("number", (*n).into()),
+        Token::Float(f, text) => {
+            return JsonValue::object([
+                ("kind", JsonValue::string("float")),
+                ("value", (*f).into()),
+                ("text", JsonValue::string(text)),
+                ("span", span_json(span)),
+            ]);
+        }
+        Token::OperatorAdd => ("operator", JsonValue::string("+")),
+        Token::OperatorSubtract => ("operator", JsonValue::string("-")),
+        Token::OperatorMultiply => ("operator", JsonValue::string("*")),
+        Token::OperatorDivide => ("operator", JsonValue::string("/")),
+        Token::OperatorPower => ("operator", JsonValue::string("^")),
+        Token::Equal | Token::NotEqual | Token::LessThan | Token::LessOrEqual
+        | Token::GreaterThan | Token::GreaterOrEqual => ("operator", JsonValue::string(comparison_name(token))),
+        Token::Comma => ("punctuation", JsonValue::string(",")),
+        Token::Semicolon => ("punctuation", JsonValue::string(";")),
+        Token::LeftParen => ("punctuation", JsonValue::string("(")),
+        Token::RightParen => ("punctuation", JsonValue::string(")")),
+        Token::Newline => ("newline", JsonValue::Null),
+        Token::Eof => ("eof", JsonValue::Null),
+        Token::Rem(comment) => ("comment", JsonValue::string(comment)),
+        Token::Else => ("keyword", JsonValue::string("ELSE")),
+        Token::End => ("keyword", JsonValue::string("END")),
+        Token::For => ("keyword", JsonValue::string("FOR")),
+        Token::Gosub => ("keyword", JsonValue::string("GOSUB")),
+        Token::Goto => ("keyword", JsonValue::string("GOTO")),
+        Token::If => ("keyword", JsonValue::string("IF")),
+        Token::Input => ("keyword", JsonValue::string("INPUT")),
+        Token::Let => ("keyword", JsonValue::string("LET")),
+        Token::Next => ("keyword", JsonValue::string("NEXT")),
+        Token::Print => ("keyword", JsonValue::string("PRINT")),
+        Token::Return => ("keyword", JsonValue::string("RETURN")),
+        Token::Randomize => ("keyword", JsonValue::string("RANDOMIZE")),
+        Token::Timer => ("keyword", JsonValue::string("TIMER")),
+        Token::Step => ("keyword", JsonValue::string("STEP")),
+        Token::Then => ("keyword", JsonValue::string("THEN")),
+        Token::To => ("keyword", JsonValue::string("TO")),
+        Token::Tron => ("keyword", JsonValue::string("TRON")),
+        Token::Troff => ("keyword", JsonValue::string("TROFF")),
+    };
+    JsonValue::object([
+        ("kind", JsonValue::string(kind)),
+        ("value", value),
+        ("span", span_json(span)),
+    ])
+}
+
+/// `{"format": "basic-tokens", "version": 1, "tokens": [...]}`
+pub fn tokens_to_json(tokens: &[(Token, Span)]) -> JsonValue {
+    JsonValue::object([
+        ("format", JsonValue::string("basic-tokens")),
+        ("version", FORMAT_VERSION.into()),
+        ("tokens", JsonValue::Array(tokens.iter().map(|(t, s)| token_json(t, s)).collect())),
+    ])
+}
+
+fn binop_name(operator: &BinOp) -> &'static str {
+    match operator {
+        BinOp::Add => "add",
+        BinOp::Subtract => "subtract",
+        BinOp::Multiply => "multiply",
+        BinOp::Divide => "divide",
+        BinOp::Power => "power",
+    }
+}
+
+pub fn expression_to_json(expr: &Expression) -> JsonValue {
+    match expr {
+        Expression::Number(n) => JsonValue::object([
+            ("kind", JsonValue::string("number")),
+            ("value", (*n).into()),
+        ]),
+        Expression::Float(f, text) => JsonValue::object([
+            ("kind", JsonValue::string("float")),
+            ("value", (*f).into()),
+            ("text", JsonValue::string(text)),
+        ]),
+        Expression::Variable(name) => JsonValue::object([
+            ("kind", JsonValue::string("variable")),
+            ("name", JsonValue::string(name)),
+        ]),
+        Expression::BinaryOp { left, operator, right } => JsonValue::object([
+            ("kind", JsonValue::string("binary")),
+            ("op", JsonValue::string(binop_name(operator))),
+            ("left", expression_to_json(left)),
+            ("right", expression_to_json(right)),
+        ]),
+        Expression::FunctionCall { name, args } => JsonValue::object([
+            ("kind", JsonValue::string("call")),
+            ("name", JsonValue::string(name)),
+            ("args", JsonValue::Array(args.iter().map(expression_to_json).collect())),
+        ]),
+    }
+}
+
+fn print_item_json(item: &PrintItem) -> JsonValue {
+    match item {
+        PrintItem::Expr(expr) => JsonValue::object([
+            ("kind", JsonValue::string("expr")),
+            ("expr", expression_to_json(expr)),
+        ]),
+        PrintItem::String(s) => JsonValue::object([
+            ("kind", JsonValue::string("string")),
+            ("value", JsonValue::string(s)),
+        ]),
+        PrintItem::Comma => JsonValue::object([("kind", JsonValue::string("comma"))]),
+        PrintItem::Semicolon => JsonValue::object([("kind", JsonValue::string("semicolon"))]),
+    }
+}
+
+fn node_members(node: &StatementNode) -> Vec<(String, JsonValue)> {
+    let members: Vec<(&str, JsonValue)> = match node {
+        StatementNode::Let { var, value } => vec![
+            ("kind", JsonValue::string("let")),
+            ("var", JsonValue::string(var)),
+            ("value", expression_to_json(value)),
+        ],
+        StatementNode::Print { items, newline } => vec![
+            ("kind", JsonValue::string("print")),
+            ("items", JsonValue::Array(items.iter().map(print_item_json).collect())),
+            ("newline", (*newline).into()),
+        ],
+        StatementNode::For { var, start, end, step, body, next_label } => vec![
+            ("kind", JsonValue::string("for")),
+            ("var", JsonValue::string(var)),
+            ("start", expression_to_json(start)),
+            ("end", expression_to_json(end)),
+            ("step", step.as_ref().map_or(JsonValue::Null, expression_to_json)),
+            ("body", statements_json(body)),
+            ("next_label", (*next_label).into()),
+        ],
+        StatementNode::If { left, op, right, then_part } => vec![
+            ("kind", JsonValue::string("if")),
+            ("left", expression_to_json(left)),
+            ("op", JsonValue::string(comparison_name(op))),
+            ("right", expression_to_json(right)),
+            ("then", statement_to_json(then_part)),
+        ],
+        StatementNode::Goto(line) => vec![
+            ("kind", JsonValue::string("goto")),
+            ("target", (*line).into()),
+        ],
+        StatementNode::Gosub(line) => vec![
+            ("kind", JsonValue::string("gosub")),
+            ("target", (*line).into()),
+        ],
+        StatementNode::Return => vec![("kind", JsonValue::string("return"))],
+        StatementNode::Tron => vec![("kind", JsonValue::string("tron"))],
+        StatementNode::Troff => vec![("kind", JsonValue::string("troff"))],
+        StatementNode::Randomize(seed) => {
+            let mut members = vec![("kind", JsonValue::string("randomize"))];
+            if let Some(seed) = seed {
+                members.push(("seed", expression_to_json(seed)));
+            }
+            members
+        }
+        StatementNode::Input(var) => vec![
+            ("kind", JsonValue::string("input")),
+            ("var", JsonValue::string(var)),
+        ],
+        StatementNode::Rem(comment) => vec![
+            ("kind", JsonValue::string("rem")),
+            ("text", JsonValue::string(comment)),
+        ],
+        StatementNode::End => vec![("kind", JsonValue::string("end"))],
+        StatementNode::Empty => vec![("kind", JsonValue::string("empty"))],
+    };
+    members.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+}
+
+pub fn statement_to_json(stmt: &Statement) -> JsonValue {
+    let mut members = vec![
+        ("label".to_string(), stmt.label.into()),
+        ("span".to_string(), span_json(&stmt.span)),
+    ];
+    members.extend(node_members(&stmt.node));
+    JsonValue::Object(members)
+}
+
+fn statements_json(statements: &[Statement]) -> JsonValue {
+    JsonValue::Array(statements.iter().map(statement_to_json).collect())
+}
+
+/// `{"format": "basic-ast", "version": 1, "statements": [...]}`
+pub fn ast_to_json(statements: &[Statement]) -> JsonValue {
+    JsonValue::object([
+        ("format", JsonValue::string("basic-ast")),
+        ("version", FORMAT_VERSION.into()),
+        ("statements", statements_json(statements)),
+    ])
+}
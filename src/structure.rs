@@ -0,0 +1,521 @@
+//! Recovery of structured control flow from the CFG, for readable C.
+//!
+//! This is a Relooper-style pass. Starting from the first block, a block
+//! that control can come back to becomes a loop holding every block on the
+//! way back; otherwise it is emitted as is and followed by whatever it
+//! branches to. A two-way IF becomes `if`/`else`, each arm taking the
+//! blocks only it can reach, with the blocks both arms reach placed after
+//! it. FOR loops stay `for` loops and their bodies are structured the same
+//! way.
+//!
+//! A jump is then written as falling through, `break` or `continue` where
+//! one of those lands in the right place. Any other jump, such as one into
+//! the middle of a loop from outside, becomes a `goto` to a label on the
+//! target block, so every program can be translated, structured or not.
+//! Blocks that structured flow never reaches (subroutines, dead code) are
+//! placed after the main flow and entered by label.
+
+use crate::cfg::{Cfg, EdgeKind, Step, EXIT};
+use crate::parser::{Statement, StatementNode};
+use std::collections::{BTreeSet, HashMap};
+
+/// Where a jump goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Block(usize),
+    /// The end of the program.
+    Exit,
+    /// A line that does not exist, jumped to from the line given.
+    Missing(Option<i64>),
+}
+
+/// A structured C statement built from CFG blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Structured {
+    /// The straight-line code of a block. An IF at its end is left to the
+    /// `If` (or loop) that follows.
+    Block(usize),
+    /// `for` over the FOR in block `header`, closed by the NEXT in `next`.
+    For {
+        header: usize,
+        next: usize,
+        body: Vec<Structured>,
+    },
+    /// `if` on the IF that ends block `cond`.
+    If {
+        cond: usize,
+        negated: bool,
+        then_part: Vec<Structured>,
+        else_part: Vec<Structured>,
+    },
+    /// `while (1)`. `continued` records whether the body uses `continue`.
+    Loop {
+        body: Vec<Structured>,
+        continued: bool,
+    },
+    While {
+        cond: usize,
+        negated: bool,
+        body: Vec<Structured>,
+    },
+    /// `do ... while`; the body ends with `Block(cond)`.
+    DoWhile {
+        body: Vec<Structured>,
+        cond: usize,
+        negated: bool,
+    },
+    Break,
+    Continue,
+    Goto(Target),
+}
+
+impl Structured {
+    /// Whether control never continues past this statement.
+    pub fn is_jump(&self) -> bool {
+        matches!(self, Structured::Break | Structured::Continue | Structured::Goto(_))
+    }
+}
+
+/// A program as structured statements, plus the blocks that need a label.
+#[derive(Debug, Clone)]
+pub struct Structure {
+    pub body: Vec<Structured>,
+    pub labelled: BTreeSet<usize>,
+}
+
+/// How control leaves a block that is not a FOR.
+enum Terminator {
+    Jump(Target),
+    Cond { then: Target, otherwise: Target },
+    /// RETURN, which goes through the GOSUB dispatch.
+    None,
+}
+
+struct LoopContext {
+    continue_to: Target,
+    break_to: Option<Target>,
+    continued: bool,
+}
+
+struct Structurer<'c, 'a> {
+    cfg: &'c Cfg<'a>,
+    /// The innermost FOR header around each block, `None` at the top level.
+    parent: Vec<Option<usize>>,
+    /// The NEXT block of each FOR header.
+    next_of: HashMap<usize, usize>,
+    successors: HashMap<usize, Vec<usize>>,
+    predecessors: HashMap<usize, Vec<usize>>,
+    labelled: BTreeSet<usize>,
+    loops: Vec<LoopContext>,
+}
+
+/// Structure the program in `cfg`.
+pub fn structure(cfg: &Cfg) -> Structure {
+    let mut structurer = Structurer::new(cfg);
+    let top: BTreeSet<usize> = (EXIT + 1..cfg.blocks.len()).filter(|&b| structurer.is_node(b, None)).collect();
+    let body = match top.first() {
+        Some(&first) => structurer.region_all(top, first, Some(Target::Exit)),
+        None => Vec::new(),
+    };
+    // GOSUB jumps to its target by label
+    for block in &cfg.blocks {
+        for edge in &block.successors {
+            if edge.kind == EdgeKind::Gosub {
+                structurer.labelled.insert(edge.target);
+            }
+        }
+    }
+    Structure { body: simplify(cfg, body), labelled: structurer.labelled }
+}
+
+fn is_for(step: &Step) -> bool {
+    matches!(step, Step::Statement(Statement { node: StatementNode::For { .. }, .. }))
+}
+
+impl<'c, 'a> Structurer<'c, 'a> {
+    fn new(cfg: &'c Cfg<'a>) -> Self {
+        let mut parent = vec![None; cfg.blocks.len()];
+        let mut next_of = HashMap::new();
+        let mut open: Vec<usize> = Vec::new();
+        for (index, block) in cfg.statement_blocks() {
+            parent[index] = open.last().copied();
+            match block.steps.first() {
+                Some(step) if is_for(step) => open.push(index),
+                Some(Step::Next(_)) => {
+                    if let Some(header) = open.pop() {
+                        next_of.insert(header, index);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut structurer = Structurer {
+            cfg,
+            parent,
+            next_of,
+            successors: HashMap::new(),
+            predecessors: HashMap::new(),
+            labelled: BTreeSet::new(),
+            loops: Vec::new(),
+        };
+        for index in EXIT + 1..cfg.blocks.len() {
+            let level = structurer.parent[index];
+            if !structurer.is_node(index, level) {
+                continue;
+            }
+            let targets: Vec<Target> = match structurer.next_of.get(&index) {
+                Some(&next) => vec![structurer.after_loop(next)],
+                None => match structurer.terminator(index) {
+                    Terminator::Jump(target) => vec![target],
+                    Terminator::Cond { then, otherwise } => vec![then, otherwise],
+                    Terminator::None => Vec::new(),
+                },
+            };
+            for target in targets {
+                if let Target::Block(to) = target
+                    && structurer.is_node(to, level)
+                {
+                    structurer.successors.entry(index).or_default().push(to);
+                    structurer.predecessors.entry(to).or_default().push(index);
+                }
+            }
+        }
+        structurer
+    }
+
+    /// Whether `block` is structured directly at the level inside the FOR
+    /// `level` (or at the top), rather than inside a nested loop.
+    fn is_node(&self, block: usize, level: Option<usize>) -> bool {
+        block > EXIT
+            && self.parent[block] == level
+            && !matches!(self.cfg.blocks[block].steps.first(), Some(Step::Next(_)))
+    }
+
+    fn target(&self, block: usize) -> Target {
+        if block == EXIT { Target::Exit } else { Target::Block(block) }
+    }
+
+    /// Where control goes once the loop closed by `next` is done.
+    fn after_loop(&self, next: usize) -> Target {
+        self.cfg.blocks[next]
+            .successors
+            .iter()
+            .find(|edge| edge.kind == EdgeKind::Fallthrough)
+            .map_or(Target::Exit, |edge| self.target(edge.target))
+    }
+
+    fn edge_target(&self, block: usize, kind: EdgeKind) -> Option<Target> {
+        self.cfg.blocks[block]
+            .successors
+            .iter()
+            .find(|edge| edge.kind == kind)
+            .map(|edge| self.target(edge.target))
+    }
+
+    fn terminator(&self, block: usize) -> Terminator {
+        let Some(last) = self.cfg.blocks[block].steps.last() else {
+            return Terminator::None;
+        };
+        let fallthrough = || self.edge_target(block, EdgeKind::Fallthrough).unwrap_or(Target::Exit);
+        let missing = Target::Missing(last.statement().label);
+        match &last.statement().node {
+            StatementNode::If { then_part, .. } => {
+                let missing = match then_part.node {
+                    StatementNode::Goto(_) => missing,
+                    _ => Target::Exit,
+                };
+                Terminator::Cond {
+                    then: self.edge_target(block, EdgeKind::Then).unwrap_or(missing),
+                    otherwise: self.edge_target(block, EdgeKind::Else).unwrap_or(Target::Exit),
+                }
+            }
+            StatementNode::Goto(_) => Terminator::Jump(self.edge_target(block, EdgeKind::Goto).unwrap_or(missing)),
+            StatementNode::Gosub(_) => Terminator::Jump(self.target(self.cfg.return_point(block))),
+            StatementNode::Return => Terminator::None,
+            StatementNode::End => Terminator::Jump(Target::Exit),
+            _ => Terminator::Jump(fallthrough()),
+        }
+    }
+
+    /// Nodes of `set` reachable from `from`, not counting `from` itself
+    /// unless it is on a cycle.
+    fn reach(&self, from: usize, set: &BTreeSet<usize>, edges: &HashMap<usize, Vec<usize>>) -> BTreeSet<usize> {
+        let mut seen = BTreeSet::new();
+        let mut stack: Vec<usize> = edges.get(&from).cloned().unwrap_or_default();
+        while let Some(node) = stack.pop() {
+            if !set.contains(&node) || !seen.insert(node) {
+                continue;
+            }
+            stack.extend(edges.get(&node).into_iter().flatten());
+        }
+        seen
+    }
+
+    /// Emit the region starting at `entry` with every node of `set` it
+    /// reaches, then any nodes of `set` left over, entered by label.
+    fn region_all(&mut self, mut set: BTreeSet<usize>, entry: usize, follow: Option<Target>) -> Vec<Structured> {
+        let out = self.region(&mut set, entry, follow);
+        self.leftovers(set, out, follow)
+    }
+
+    fn leftovers(&mut self, mut set: BTreeSet<usize>, mut out: Vec<Structured>, follow: Option<Target>) -> Vec<Structured> {
+        while let Some(&entry) = set.first() {
+            // The code so far must not run on into the leftovers
+            if let Some(follow) = follow
+                && !self.ends_in_jump(&out)
+            {
+                out.extend(self.branch(follow, None));
+            }
+            out.extend(self.region(&mut set, entry, None));
+        }
+        out
+    }
+
+    /// Whether control never runs off the end of `code`.
+    fn ends_in_jump(&self, code: &[Structured]) -> bool {
+        match code.last() {
+            Some(Structured::Block(block)) => matches!(self.terminator(*block), Terminator::None),
+            Some(last) => last.is_jump(),
+            None => false,
+        }
+    }
+
+    /// Emit the region starting at `entry`, taking its nodes out of `set`.
+    /// Control reaches `follow` when the region's code runs off its end.
+    fn region(&mut self, set: &mut BTreeSet<usize>, entry: usize, follow: Option<Target>) -> Vec<Structured> {
+        let back = self.reach(entry, set, &self.predecessors);
+        if !back.contains(&entry) {
+            return self.simple(entry, set, follow);
+        }
+
+        // Every node on a path from the entry back to it is in the loop
+        let forward = self.reach(entry, set, &self.successors);
+        let body: BTreeSet<usize> = forward.intersection(&back).copied().collect();
+        for node in &body {
+            set.remove(node);
+        }
+        let exit = body
+            .iter()
+            .flat_map(|node| self.successors.get(node).into_iter().flatten())
+            .filter(|node| set.contains(node))
+            .min()
+            .copied();
+
+        self.loops.push(LoopContext {
+            continue_to: Target::Block(entry),
+            break_to: exit.map(Target::Block).or(follow),
+            continued: false,
+        });
+        let mut inner = body;
+        inner.remove(&entry);
+        let code = self.simple(entry, &mut inner, Some(Target::Block(entry)));
+        let code = self.leftovers(inner, code, Some(Target::Block(entry)));
+        let context = self.loops.pop().unwrap();
+
+        let mut out = vec![Structured::Loop { body: code, continued: context.continued }];
+        if let Some(exit) = exit {
+            out.extend(self.region(set, exit, follow));
+        }
+        out
+    }
+
+    /// Emit one node and then the region it leads to.
+    fn simple(&mut self, node: usize, set: &mut BTreeSet<usize>, follow: Option<Target>) -> Vec<Structured> {
+        set.remove(&node);
+        if let Some(&next) = self.next_of.get(&node) {
+            let after = self.after_loop(next);
+            let mut out = vec![self.for_loop(node, next, after)];
+            out.extend(self.jump(set, after, follow));
+            return out;
+        }
+
+        let mut out = vec![Structured::Block(node)];
+        match self.terminator(node) {
+            Terminator::Jump(target) => out.extend(self.jump(set, target, follow)),
+            Terminator::Cond { then, otherwise } => out.extend(self.conditional(node, set, then, otherwise, follow)),
+            Terminator::None => {}
+        }
+        out
+    }
+
+    fn for_loop(&mut self, header: usize, next: usize, after: Target) -> Structured {
+        let level = Some(header);
+        let body: BTreeSet<usize> = (header + 1..next).filter(|&b| self.is_node(b, level)).collect();
+        self.loops.push(LoopContext {
+            continue_to: Target::Block(next),
+            break_to: Some(after),
+            continued: false,
+        });
+        let code = match body.first() {
+            Some(&first) => self.region_all(body, first, Some(Target::Block(next))),
+            None => Vec::new(),
+        };
+        self.loops.pop();
+        Structured::For { header, next, body: code }
+    }
+
+    /// Continue at `target`: inline if it is still to be placed, otherwise
+    /// a branch.
+    fn jump(&mut self, set: &mut BTreeSet<usize>, target: Target, follow: Option<Target>) -> Vec<Structured> {
+        match target {
+            Target::Block(block) if set.contains(&block) => self.region(set, block, follow),
+            _ => self.branch(target, follow),
+        }
+    }
+
+    fn conditional(
+        &mut self,
+        cond: usize,
+        set: &mut BTreeSet<usize>,
+        then: Target,
+        otherwise: Target,
+        follow: Option<Target>,
+    ) -> Vec<Structured> {
+        let placeable = |target: Target| match target {
+            Target::Block(block) if set.contains(&block) => Some(block),
+            _ => None,
+        };
+        let arm_reach = |block: Option<usize>| -> BTreeSet<usize> {
+            block.map_or_else(BTreeSet::new, |b| {
+                let mut reach = self.reach(b, set, &self.successors);
+                reach.insert(b);
+                reach
+            })
+        };
+        let (then_block, else_block) = (placeable(then), placeable(otherwise));
+        let (then_reach, else_reach) = (arm_reach(then_block), arm_reach(else_block));
+
+        // An arm owns the blocks only it can reach, provided the other arm
+        // cannot reach its first block
+        let owns = |block: Option<usize>, other: &BTreeSet<usize>| {
+            block.filter(|b| then != otherwise && !other.contains(b))
+        };
+        let then_owned = owns(then_block, &else_reach);
+        let else_owned = owns(else_block, &then_reach);
+        let then_set: BTreeSet<usize> = match then_owned {
+            Some(_) => then_reach.difference(&else_reach).copied().collect(),
+            None => BTreeSet::new(),
+        };
+        let else_set: BTreeSet<usize> = match else_owned {
+            Some(_) => else_reach.difference(&then_reach).copied().collect(),
+            None => BTreeSet::new(),
+        };
+        for node in then_set.iter().chain(&else_set) {
+            set.remove(node);
+        }
+
+        // Where the arms meet again
+        let mut joins: BTreeSet<usize> = then_set
+            .iter()
+            .chain(&else_set)
+            .flat_map(|node| self.successors.get(node).into_iter().flatten())
+            .filter(|node| set.contains(node))
+            .copied()
+            .collect();
+        joins.extend(then_block.filter(|_| then_owned.is_none()));
+        joins.extend(else_block.filter(|_| else_owned.is_none()));
+        let join = joins.first().copied();
+        let arm_follow = join.map(Target::Block).or(follow);
+
+        let then_part = match then_owned {
+            Some(block) => self.region_all(then_set, block, arm_follow),
+            None => self.branch(then, arm_follow),
+        };
+        let else_part = match else_owned {
+            Some(block) => self.region_all(else_set, block, arm_follow),
+            None => self.branch(otherwise, arm_follow),
+        };
+        let mut out = vec![Structured::If { cond, negated: false, then_part, else_part }];
+        if let Some(join) = join {
+            out.extend(self.region(set, join, follow));
+        }
+        out
+    }
+
+    /// A jump to a block that has already been placed (or is not at this
+    /// level).
+    fn branch(&mut self, target: Target, follow: Option<Target>) -> Vec<Structured> {
+        if Some(target) == follow {
+            return Vec::new();
+        }
+        if let Some(context) = self.loops.last_mut() {
+            if target == context.continue_to {
+                context.continued = true;
+                return vec![Structured::Continue];
+            }
+            if Some(target) == context.break_to {
+                return vec![Structured::Break];
+            }
+        }
+        if let Target::Block(block) = target {
+            self.labelled.insert(block);
+        }
+        vec![Structured::Goto(target)]
+    }
+}
+
+/// Whether block `cond` holds nothing but its closing IF.
+fn only_test(cfg: &Cfg, cond: usize) -> bool {
+    cfg.blocks[cond].steps.len() == 1
+}
+
+/// Tidy the structure: drop empty `then` arms, pull code after a jump out
+/// of `else`, and turn `while (1)` loops with a test at the top or bottom
+/// into `while` and `do ... while`.
+fn simplify(cfg: &Cfg, statements: Vec<Structured>) -> Vec<Structured> {
+    let mut out = Vec::new();
+    for statement in statements {
+        match statement {
+            Structured::If { cond, negated, then_part, else_part } => {
+                let (then_part, else_part) = (simplify(cfg, then_part), simplify(cfg, else_part));
+                let (negated, then_part, else_part) = if then_part.is_empty() {
+                    (!negated, else_part, then_part)
+                } else {
+                    (negated, then_part, else_part)
+                };
+                if then_part.last().is_some_and(Structured::is_jump) {
+                    out.push(Structured::If { cond, negated, then_part, else_part: Vec::new() });
+                    out.extend(else_part);
+                } else {
+                    out.push(Structured::If { cond, negated, then_part, else_part });
+                }
+            }
+            Structured::For { header, next, body } => {
+                out.push(Structured::For { header, next, body: simplify(cfg, body) });
+            }
+            Structured::Loop { body, continued } => out.push(simplify_loop(cfg, simplify(cfg, body), continued)),
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn simplify_loop(cfg: &Cfg, mut body: Vec<Structured>, continued: bool) -> Structured {
+    // while (1) { if (c) break; ... }  =>  while (!c) { ... }
+    if let [Structured::Block(block), Structured::If { cond, negated, then_part, else_part }, ..] = body.as_slice()
+        && block == cond
+        && only_test(cfg, *cond)
+        && then_part.as_slice() == [Structured::Break]
+        && else_part.is_empty()
+    {
+        let (cond, negated) = (*cond, !*negated);
+        body.drain(..2);
+        return Structured::While { cond, negated, body };
+    }
+
+    // while (1) { ...; if (c) break; }  =>  do { ... } while (!c);
+    // A continue would skip the test, so loops using it stay as they are.
+    if !continued
+        && let [.., Structured::Block(block), Structured::If { cond, negated, then_part, else_part }] = body.as_slice()
+        && block == cond
+        && then_part.as_slice() == [Structured::Break]
+        && else_part.is_empty()
+    {
+        let (cond, negated) = (*cond, !*negated);
+        body.pop();
+        return Structured::DoWhile { body, cond, negated };
+    }
+
+    Structured::Loop { body, continued }
+}
@@ -0,0 +1,646 @@
+//! WebAssembly generation, for `--target wasm`.
+//!
+//! The program becomes a binary module, encoded here without outside
+//! tools. It exports `run`, which runs the program, and `memory`, which
+//! holds its strings. Everything else comes from the host through imports
+//! from the `basic` module:
+//!
+//! - `print_string(ptr: i32, len: i32)`: write the UTF-8 string at `ptr`.
+//! - `print_number(x: f64)`: write `x` as C's `%g` would, then a space.
+//! - `print_newline()`.
+//! - `input(ptr: i32, len: i32) -> f64`: write the prompt at `ptr` and
+//!   read a number, asking again after "?Redo from start" until one is
+//!   typed. When input runs out the host should end the program, for
+//!   example by throwing.
+//! - `error(ptr: i32, len: i32, line: i32)`: report a runtime error on
+//!   BASIC line `line` (-1 if unknown); `run` returns straight after.
+//! - `exp(x: f64) -> f64`, `pow(x: f64, y: f64) -> f64` and
+//!   `timer() -> f64` (seconds since midnight).
+//!
+//! Control flow is a dispatch loop over the CFG blocks: a `br_table` on
+//! the current block picks the code to run, which sets the next block and
+//! branches back to the top. GOSUB pushes the block to return to on a
+//! stack at the start of memory. RND is GW-BASIC's generator, in the
+//! module itself.
+
+use crate::cfg::{Cfg, EdgeKind, Step, ENTRY, EXIT};
+use crate::codegen::{CodegenOptions, GOSUB_STACK_SIZE};
+use crate::lexer::Token;
+use crate::parser::{BinOp, Expression, PrintItem, Statement, StatementNode};
+use std::collections::HashMap;
+
+/// Value types.
+const I32: u8 = 0x7f;
+const F64: u8 = 0x7c;
+/// The block type of a block that takes and leaves nothing.
+const EMPTY: u8 = 0x40;
+
+/// Instructions.
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0b;
+const BR: u8 = 0x0c;
+const BR_TABLE: u8 = 0x0e;
+const RETURN: u8 = 0x0f;
+const CALL: u8 = 0x10;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const GLOBAL_GET: u8 = 0x23;
+const GLOBAL_SET: u8 = 0x24;
+const I32_LOAD: u8 = 0x28;
+const I32_STORE: u8 = 0x36;
+const I32_CONST: u8 = 0x41;
+const F64_CONST: u8 = 0x44;
+const I32_EQZ: u8 = 0x45;
+const F64_EQ: u8 = 0x61;
+const F64_NE: u8 = 0x62;
+const F64_LT: u8 = 0x63;
+const F64_GT: u8 = 0x64;
+const F64_LE: u8 = 0x65;
+const F64_GE: u8 = 0x66;
+const I32_ADD: u8 = 0x6a;
+const I32_SUB: u8 = 0x6b;
+const I32_MUL: u8 = 0x6c;
+const I32_GE_U: u8 = 0x4f;
+const I32_AND: u8 = 0x71;
+const I32_OR: u8 = 0x72;
+const I32_SHL: u8 = 0x74;
+const I32_SHR_U: u8 = 0x76;
+const I64_REM_S: u8 = 0x81;
+const F64_ABS: u8 = 0x99;
+const F64_FLOOR: u8 = 0x9c;
+const F64_SQRT: u8 = 0x9f;
+const F64_ADD: u8 = 0xa0;
+const F64_SUB: u8 = 0xa1;
+const F64_MUL: u8 = 0xa2;
+const F64_DIV: u8 = 0xa3;
+const I32_WRAP_I64: u8 = 0xa7;
+const F32_DEMOTE_F64: u8 = 0xb6;
+const F64_CONVERT_I32_U: u8 = 0xb8;
+const I32_REINTERPRET_F32: u8 = 0xbc;
+const I64_CONST: u8 = 0x42;
+/// `i64.trunc_sat_f64_s`, which gives 0 for NaN instead of trapping.
+const I64_TRUNC_SAT_F64_S: [u8; 2] = [0xfc, 0x06];
+
+/// Functions, imported ones first, with their parameters and results.
+const PRINT_STRING: u32 = 0;
+const PRINT_NUMBER: u32 = 1;
+const PRINT_NEWLINE: u32 = 2;
+const INPUT: u32 = 3;
+const ERROR: u32 = 4;
+const EXP: u32 = 5;
+const POW: u32 = 6;
+const TIMER: u32 = 7;
+const RND: u32 = 8;
+const RANDOMIZE: u32 = 9;
+const RUN: u32 = 10;
+
+const IMPORTS: &[(&str, &[u8], &[u8])] = &[
+    ("print_string", &[I32, I32], &[]),
+    ("print_number", &[F64], &[]),
+    ("print_newline", &[], &[]),
+    ("input", &[I32, I32], &[F64]),
+    ("error", &[I32, I32, I32], &[]),
+    ("exp", &[F64], &[F64]),
+    ("pow", &[F64, F64], &[F64]),
+    ("timer", &[], &[F64]),
+];
+
+/// Signatures of the functions the module defines: RND, RANDOMIZE and run.
+const FUNCTIONS: &[(&[u8], &[u8])] = &[(&[F64], &[F64]), (&[F64], &[]), (&[], &[])];
+
+/// The GOSUB return points are kept at the start of memory, and the
+/// strings follow them.
+const STRINGS: u32 = GOSUB_STACK_SIZE as u32 * 4;
+
+/// GW-BASIC's generator, as in the C runtime.
+const RND_SEED: i32 = 0x4fc752;
+const RND_MULTIPLIER: i32 = 214013;
+const RND_INCREMENT: i32 = 2531011;
+
+fn uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// A vector: its length, then its bytes.
+fn vector(out: &mut Vec<u8>, count: usize, bytes: &[u8]) {
+    uleb(out, count as u64);
+    out.extend_from_slice(bytes);
+}
+
+fn name(out: &mut Vec<u8>, text: &str) {
+    vector(out, text.len(), text.as_bytes());
+}
+
+fn section(out: &mut Vec<u8>, id: u8, count: usize, contents: &[u8]) {
+    let mut body = Vec::new();
+    vector(&mut body, count, contents);
+    out.push(id);
+    vector(out, body.len(), &body);
+}
+
+fn function_type(out: &mut Vec<u8>, params: &[u8], results: &[u8]) {
+    out.push(0x60);
+    vector(out, params.len(), params);
+    vector(out, results.len(), results);
+}
+
+/// Code being generated for one function.
+#[derive(Default)]
+struct Code(Vec<u8>);
+
+impl Code {
+    fn op(&mut self, op: u8) -> &mut Self {
+        self.0.push(op);
+        self
+    }
+
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.0.push(I32_CONST);
+        sleb(&mut self.0, value as i64);
+        self
+    }
+
+    fn f64(&mut self, value: f64) -> &mut Self {
+        self.0.push(F64_CONST);
+        self.0.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    /// An instruction taking one index or depth.
+    fn with(&mut self, op: u8, index: u32) -> &mut Self {
+        self.0.push(op);
+        uleb(&mut self.0, index as u64);
+        self
+    }
+
+    /// A load or store of a 4-byte aligned i32 at `offset`.
+    fn memory(&mut self, op: u8, offset: u32) -> &mut Self {
+        self.0.push(op);
+        uleb(&mut self.0, 2);
+        uleb(&mut self.0, offset as u64);
+        self
+    }
+}
+
+pub struct WasmGenerator {
+    options: CodegenOptions,
+    /// The local each variable lives in.
+    locals: HashMap<String, u32>,
+    /// Strings in memory, by offset.
+    data: Vec<u8>,
+    strings: HashMap<String, u32>,
+}
+
+impl WasmGenerator {
+    pub fn new(options: CodegenOptions) -> Self {
+        WasmGenerator {
+            options,
+            locals: HashMap::new(),
+            data: Vec::new(),
+            strings: HashMap::new(),
+        }
+    }
+
+    fn collect_variables(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            self.collect_variables_node(&stmt.node);
+        }
+    }
+
+    fn collect_variables_node(&mut self, node: &StatementNode) {
+        match node {
+            StatementNode::Let { var, .. } | StatementNode::Input(var) => self.add_variable(var),
+            StatementNode::For { var, body, .. } => {
+                self.add_variable(var);
+                self.collect_variables(body);
+            }
+            StatementNode::If { then_part, .. } => self.collect_variables_node(&then_part.node),
+            _ => {}
+        }
+    }
+
+    fn add_variable(&mut self, var: &str) {
+        let next = self.locals.len() as u32;
+        self.locals.entry(var.to_string()).or_insert(next);
+    }
+
+    /// Push the offset and length of `text` in memory.
+    fn string(&mut self, code: &mut Code, text: &str) {
+        let offset = match self.strings.get(text) {
+            Some(&offset) => offset,
+            None => {
+                let offset = STRINGS + self.data.len() as u32;
+                self.data.extend_from_slice(text.as_bytes());
+                self.strings.insert(text.to_string(), offset);
+                offset
+            }
+        };
+        code.i32(offset as i32).i32(text.len() as i32);
+    }
+
+    fn generate_expr(&mut self, code: &mut Code, expr: &Expression) {
+        match expr {
+            Expression::Number(n) => {
+                code.f64(*n as f64);
+            }
+            Expression::Float(f, _) => {
+                code.f64(*f);
+            }
+            Expression::Variable(name) => {
+                // A variable nothing assigns is always 0
+                match self.locals.get(name) {
+                    Some(&local) => code.with(LOCAL_GET, local),
+                    None => code.f64(0.0),
+                };
+            }
+            Expression::BinaryOp { left, operator, right } => {
+                self.generate_expr(code, left);
+                self.generate_expr(code, right);
+                match operator {
+                    BinOp::Add => code.op(F64_ADD),
+                    BinOp::Subtract => code.op(F64_SUB),
+                    BinOp::Multiply => code.op(F64_MUL),
+                    BinOp::Divide => code.op(F64_DIV),
+                    BinOp::Power => code.with(CALL, POW),
+                };
+            }
+            Expression::FunctionCall { name, args } => {
+                for arg in args {
+                    self.generate_expr(code, arg);
+                }
+                match name.to_uppercase().as_str() {
+                    "INT" => code.op(F64_FLOOR),
+                    "SQR" => code.op(F64_SQRT),
+                    "ABS" => code.op(F64_ABS),
+                    "EXP" => code.with(CALL, EXP),
+                    "TIMER" => code.with(CALL, TIMER),
+                    _ => {
+                        // RND on its own is RND(1)
+                        if args.is_empty() {
+                            code.f64(1.0);
+                        }
+                        code.with(CALL, RND)
+                    }
+                };
+            }
+        }
+    }
+
+    /// Push the seed RANDOMIZE uses instead of asking or reading the clock,
+    /// if `--seed` gave one.
+    fn fixed_seed(&self, code: &mut Code) -> bool {
+        match self.options.seed {
+            Some(seed) => {
+                code.f64(seed as f64);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Code for a statement that runs straight through.
+    fn generate_statement(&mut self, code: &mut Code, stmt: &Statement) {
+        match &stmt.node {
+            StatementNode::Let { var, value } => {
+                self.generate_expr(code, value);
+                code.with(LOCAL_SET, self.locals[var]);
+            }
+            StatementNode::Print { items, newline } => {
+                for item in items {
+                    match item {
+                        PrintItem::String(s) => {
+                            self.string(code, s);
+                            code.with(CALL, PRINT_STRING);
+                        }
+                        PrintItem::Expr(expr) => {
+                            self.generate_expr(code, expr);
+                            code.with(CALL, PRINT_NUMBER);
+                        }
+                        PrintItem::Comma | PrintItem::Semicolon => {}
+                    }
+                }
+                if *newline {
+                    code.with(CALL, PRINT_NEWLINE);
+                }
+            }
+            StatementNode::Input(var) => {
+                self.string(code, "? ");
+                code.with(CALL, INPUT).with(LOCAL_SET, self.locals[var]);
+            }
+            StatementNode::Randomize(None) => {
+                if !self.fixed_seed(code) {
+                    self.string(code, "Random number seed (-32768 to 32767)? ");
+                    code.with(CALL, INPUT);
+                }
+                code.with(CALL, RANDOMIZE);
+            }
+            StatementNode::Randomize(Some(Expression::FunctionCall { name, args })) if name == "TIMER" && args.is_empty() => {
+                if !self.fixed_seed(code) {
+                    code.with(CALL, TIMER);
+                }
+                code.with(CALL, RANDOMIZE);
+            }
+            StatementNode::Randomize(Some(seed)) => {
+                self.generate_expr(code, seed);
+                code.with(CALL, RANDOMIZE);
+            }
+            _ => {}
+        }
+    }
+
+    fn generate_condition(&mut self, code: &mut Code, stmt: &Statement) {
+        let StatementNode::If { left, op, right, .. } = &stmt.node else {
+            return;
+        };
+        self.generate_expr(code, left);
+        self.generate_expr(code, right);
+        code.op(match op {
+            Token::NotEqual => F64_NE,
+            Token::LessThan => F64_LT,
+            Token::LessOrEqual => F64_LE,
+            Token::GreaterThan => F64_GT,
+            Token::GreaterOrEqual => F64_GE,
+            _ => F64_EQ,
+        });
+    }
+
+    /// Go to block `target`, `depth` levels inside the dispatch loop; a
+    /// jump to a line that does not exist is a runtime error.
+    fn generate_goto(&mut self, code: &mut Code, target: Option<usize>, depth: u32, line: Option<i64>) {
+        match target {
+            Some(EXIT) => {
+                code.op(RETURN);
+            }
+            Some(target) => {
+                code.i32(target as i32).with(LOCAL_SET, self.pc()).with(BR, depth);
+            }
+            None => self.generate_error(code, "Undefined line number", line),
+        }
+    }
+
+    fn generate_error(&mut self, code: &mut Code, message: &str, line: Option<i64>) {
+        self.string(code, message);
+        code.i32(line.map_or(-1, |line| line as i32)).with(CALL, ERROR).op(RETURN);
+    }
+
+    /// `if` on the value on the stack, going to `then` or `otherwise`.
+    fn generate_branch(&mut self, code: &mut Code, then: Option<usize>, otherwise: Option<usize>, depth: u32, line: Option<i64>) {
+        code.op(IF).op(EMPTY);
+        self.generate_goto(code, then, depth + 1, line);
+        code.op(ELSE);
+        self.generate_goto(code, otherwise, depth + 1, line);
+        code.op(END);
+    }
+
+    fn pc(&self) -> u32 {
+        self.locals.len() as u32
+    }
+
+    fn sp(&self) -> u32 {
+        self.locals.len() as u32 + 1
+    }
+
+    fn successor(cfg: &Cfg, block: usize, kind: EdgeKind) -> Option<usize> {
+        cfg.blocks[block].successors.iter().find(|edge| edge.kind == kind).map(|edge| edge.target)
+    }
+
+    /// The code of `block`, which ends by going to the next block; `depth`
+    /// is how far the dispatch loop is.
+    fn generate_block(&mut self, code: &mut Code, cfg: &Cfg, block: usize, depth: u32) {
+        if block == ENTRY {
+            let first = Self::successor(cfg, block, EdgeKind::Fallthrough);
+            return self.generate_goto(code, first, depth, None);
+        }
+        let Some(last) = cfg.blocks[block].steps.last() else {
+            code.op(RETURN);
+            return;
+        };
+        for step in &cfg.blocks[block].steps {
+            if let Step::Statement(stmt) = step {
+                self.generate_statement(code, stmt);
+            }
+        }
+        let stmt = last.statement();
+        let line = stmt.label;
+        let successor = |kind| Self::successor(cfg, block, kind);
+        match (last, &stmt.node) {
+            (Step::Next(_), StatementNode::For { var, end, step, .. }) => {
+                let local = self.locals[var];
+                code.with(LOCAL_GET, local);
+                match step {
+                    Some(step) => self.generate_expr(code, step),
+                    None => {
+                        code.f64(1.0);
+                    }
+                }
+                code.op(F64_ADD).with(LOCAL_SET, local).with(LOCAL_GET, local);
+                self.generate_expr(code, end);
+                code.op(F64_LE);
+                self.generate_branch(code, successor(EdgeKind::Loop), successor(EdgeKind::Fallthrough), depth, line);
+            }
+            (_, StatementNode::For { var, start, end, .. }) => {
+                let local = self.locals[var];
+                self.generate_expr(code, start);
+                code.with(LOCAL_SET, local).with(LOCAL_GET, local);
+                self.generate_expr(code, end);
+                code.op(F64_LE);
+                self.generate_branch(code, successor(EdgeKind::Fallthrough), successor(EdgeKind::LoopExit), depth, line);
+            }
+            (_, StatementNode::If { .. }) => {
+                self.generate_condition(code, stmt);
+                self.generate_branch(code, successor(EdgeKind::Then), successor(EdgeKind::Else), depth, line);
+            }
+            (_, StatementNode::Goto(_)) => self.generate_goto(code, successor(EdgeKind::Goto), depth, line),
+            (_, StatementNode::Gosub(_)) => {
+                let back = cfg.return_point(block);
+                let sp = self.sp();
+                code.with(LOCAL_GET, sp).i32(GOSUB_STACK_SIZE as i32 * 4).op(I32_GE_U).op(IF).op(EMPTY);
+                self.generate_error(code, "Out of memory", line);
+                code.op(END);
+                code.with(LOCAL_GET, sp).i32(back as i32).memory(I32_STORE, 0);
+                code.with(LOCAL_GET, sp).i32(4).op(I32_ADD).with(LOCAL_SET, sp);
+                self.generate_goto(code, successor(EdgeKind::Gosub), depth, line);
+            }
+            (_, StatementNode::Return) => {
+                let sp = self.sp();
+                code.with(LOCAL_GET, sp).op(I32_EQZ).op(IF).op(EMPTY);
+                self.generate_error(code, "RETURN without GOSUB", line);
+                code.op(END);
+                code.with(LOCAL_GET, sp).i32(4).op(I32_SUB).with(LOCAL_SET, sp);
+                code.with(LOCAL_GET, sp).memory(I32_LOAD, 0).with(LOCAL_SET, self.pc()).with(BR, depth);
+            }
+            (_, StatementNode::End) => {
+                code.op(RETURN);
+            }
+            _ => self.generate_goto(code, successor(EdgeKind::Fallthrough), depth, None),
+        }
+    }
+
+    /// `seed = (seed * a + c) mod 2^24`, in i32 arithmetic that wraps
+    /// modulo 2^32.
+    fn next_seed(code: &mut Code) {
+        code.with(GLOBAL_GET, 0).i32(RND_MULTIPLIER).op(I32_MUL).i32(RND_INCREMENT).op(I32_ADD);
+        code.i32(0xffffff).op(I32_AND).with(GLOBAL_SET, 0);
+    }
+
+    /// RND(x): the next number in [0, 1) for x > 0, the last one again for
+    /// x = 0, and a fresh sequence seeded from x for x < 0.
+    fn rnd() -> Code {
+        let mut code = Code::default();
+        code.with(LOCAL_GET, 0).f64(0.0).op(F64_LT).op(IF).op(EMPTY);
+        // Start over from the bits of x as a single
+        code.with(LOCAL_GET, 0).op(F32_DEMOTE_F64).op(I32_REINTERPRET_F32).with(LOCAL_SET, 1);
+        code.with(LOCAL_GET, 1).with(LOCAL_GET, 1).i32(24).op(I32_SHR_U).op(I32_ADD);
+        code.i32(0xffffff).op(I32_AND).with(GLOBAL_SET, 0);
+        Self::next_seed(&mut code);
+        code.op(ELSE);
+        code.with(LOCAL_GET, 0).f64(0.0).op(F64_GT).op(IF).op(EMPTY);
+        Self::next_seed(&mut code);
+        code.op(END).op(END);
+        code.with(GLOBAL_GET, 0).op(F64_CONVERT_I32_U).f64(16777216.0).op(F64_DIV);
+        code
+    }
+
+    /// RANDOMIZE n: the low 16 bits of n replace the top of the seed.
+    fn randomize() -> Code {
+        let mut code = Code::default();
+        code.with(LOCAL_GET, 0);
+        code.0.extend_from_slice(&I64_TRUNC_SAT_F64_S);
+        code.op(I64_CONST);
+        sleb(&mut code.0, 65536);
+        code.op(I64_REM_S).op(I32_WRAP_I64).i32(0xffff).op(I32_AND).i32(8).op(I32_SHL);
+        code.with(GLOBAL_GET, 0).i32(0xff).op(I32_AND).op(I32_OR).with(GLOBAL_SET, 0);
+        code
+    }
+
+    /// The body of `run`.
+    fn generate_run(&mut self, cfg: &Cfg) -> Code {
+        let blocks = cfg.blocks.len();
+        let mut code = Code::default();
+        if self.fixed_seed(&mut code) {
+            code.with(CALL, RANDOMIZE);
+        }
+        code.op(LOOP).op(EMPTY);
+        for _ in 0..blocks {
+            code.op(BLOCK).op(EMPTY);
+        }
+        code.with(LOCAL_GET, self.pc()).op(BR_TABLE);
+        uleb(&mut code.0, blocks as u64);
+        for block in 0..blocks {
+            uleb(&mut code.0, block as u64);
+        }
+        // Anything else ends the program
+        uleb(&mut code.0, EXIT as u64);
+        for block in 0..blocks {
+            code.op(END);
+            self.generate_block(&mut code, cfg, block, (blocks - 1 - block) as u32);
+        }
+        code.op(END);
+        code
+    }
+
+    pub fn generate(&mut self, statements: &[Statement]) -> Vec<u8> {
+        self.collect_variables(statements);
+        let cfg = Cfg::new(statements);
+        let run = self.generate_run(&cfg);
+
+        let mut module = b"\0asm".to_vec();
+        module.extend_from_slice(&1u32.to_le_bytes());
+
+        let mut types = Vec::new();
+        for (_, params, results) in IMPORTS {
+            function_type(&mut types, params, results);
+        }
+        for (params, results) in FUNCTIONS {
+            function_type(&mut types, params, results);
+        }
+        section(&mut module, 1, IMPORTS.len() + FUNCTIONS.len(), &types);
+
+        let mut imports = Vec::new();
+        for (index, (field, _, _)) in IMPORTS.iter().enumerate() {
+            name(&mut imports, "basic");
+            name(&mut imports, field);
+            imports.push(0x00);
+            uleb(&mut imports, index as u64);
+        }
+        section(&mut module, 2, IMPORTS.len(), &imports);
+
+        let mut functions = Vec::new();
+        for index in RND..=RUN {
+            uleb(&mut functions, index as u64);
+        }
+        section(&mut module, 3, FUNCTIONS.len(), &functions);
+
+        // Enough 64K pages for the GOSUB stack and the strings
+        let pages = (STRINGS as usize + self.data.len()).div_ceil(65536);
+        let mut memory = vec![0x00];
+        uleb(&mut memory, pages as u64);
+        section(&mut module, 5, 1, &memory);
+
+        let mut globals = vec![I32, 0x01];
+        let mut seed = Code::default();
+        seed.i32(RND_SEED).op(END);
+        globals.extend_from_slice(&seed.0);
+        section(&mut module, 6, 1, &globals);
+
+        let mut exports = Vec::new();
+        name(&mut exports, "run");
+        exports.push(0x00);
+        uleb(&mut exports, RUN as u64);
+        name(&mut exports, "memory");
+        exports.push(0x02);
+        uleb(&mut exports, 0);
+        section(&mut module, 7, 2, &exports);
+
+        let locals = self.locals.len() as u64;
+        let bodies: [(&[(u64, u8)], Code); 3] = [
+            (&[(1, I32)], Self::rnd()),
+            (&[], Self::randomize()),
+            (&[(locals, F64), (2, I32)], run),
+        ];
+        let mut code = Vec::new();
+        for (locals, body) in bodies {
+            let mut function = Vec::new();
+            uleb(&mut function, locals.len() as u64);
+            for (count, value_type) in locals {
+                uleb(&mut function, *count);
+                function.push(*value_type);
+            }
+            function.extend_from_slice(&body.0);
+            function.push(END);
+            vector(&mut code, function.len(), &function);
+        }
+        section(&mut module, 10, 3, &code);
+
+        let mut data = vec![0x00];
+        data.extend_from_slice(&Code::default().i32(STRINGS as i32).op(END).0);
+        vector(&mut data, self.data.len(), &self.data);
+        section(&mut module, 11, 1, &data);
+
+        module
+    }
+}
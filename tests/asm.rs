@@ -0,0 +1,141 @@
+//! Programs compiled with `--target x86_64-asm`, assembled with `as`, linked
+//! with `ld` and run. Each must print what the interpreter in `interp.rs`
+//! prints for it, and stop with the same error.
+
+use compiler::interp::{Interpreter, Io, RuntimeError};
+use compiler::{CodegenOptions, Options, Target, compile, parse};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What a program printed, and the error it stopped with.
+type Run = (String, Option<String>);
+
+/// A directory of its own for each program, removed again when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("basic-asm-{}-{}", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed));
+        let dir = std::env::temp_dir().join(name);
+        fs::create_dir_all(&dir).expect("directory made");
+        Scratch(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn tool(program: &str, args: &[&Path]) {
+    let status = Command::new(program).args(args).status().expect("the tool runs");
+    assert!(status.success(), "{} failed", program);
+}
+
+fn run_asm(source: &str, input: &str) -> Run {
+    let options = Options { target: Target::X86_64Asm, ..Options::default() };
+    let output = compile(source, &options).expect("program compiles");
+    let scratch = Scratch::new();
+    let (asm, object, exe) = (scratch.path("prog.s"), scratch.path("prog.o"), scratch.path("prog"));
+    fs::write(&asm, &output.code).expect("assembly written");
+    tool("as", &[asm.as_path(), Path::new("-o"), &object]);
+    tool("ld", &[object.as_path(), Path::new("-o"), &exe]);
+
+    let mut child = Command::new(&exe)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("program runs");
+    child.stdin.take().expect("stdin").write_all(input.as_bytes()).expect("input written");
+    let output = child.wait_with_output().expect("program finishes");
+    let stdout = String::from_utf8(output.stdout).expect("output is UTF-8");
+    let stderr = String::from_utf8(output.stderr).expect("errors are UTF-8");
+    let error = (!output.status.success()).then(|| stderr.trim_end().to_string());
+    (stdout, error)
+}
+
+struct Console {
+    output: String,
+    input: VecDeque<String>,
+}
+
+impl Io for Console {
+    fn print(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn input(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+
+    fn warn(&mut self, error: &RuntimeError) {
+        panic!("unexpected warning: {}", error);
+    }
+}
+
+fn interpret(source: &str, input: &str) -> Run {
+    let program = parse(source, &Options::default()).expect("program parses");
+    let mut interpreter = Interpreter::new(&program.statements, CodegenOptions::default());
+    let mut console = Console { output: String::new(), input: input.lines().map(str::to_string).collect() };
+    while !interpreter.finished() {
+        if let Err(error) = interpreter.step(&mut console) {
+            return (console.output, Some(error.to_string()));
+        }
+    }
+    (console.output, None)
+}
+
+/// Run `source` both ways and return what it did.
+fn check(source: &str, input: &str) -> Run {
+    let run = run_asm(source, input);
+    assert_eq!(run, interpret(source, input), "the program and the interpreter differ");
+    run
+}
+
+#[test]
+fn numbers_print_as_in_c() {
+    let (output, _) = check("10 PRINT 1 / 3; 0 - 2.5; 0.00001; 123456789; 0 * (0 - 1); 2 ^ 10\n", "");
+    assert_eq!(output, "0.333333 -2.5 1e-05 1.23457e+08 -0 1024 \n");
+}
+
+#[test]
+fn loops_subroutines_and_input() {
+    let source = "\
+10 INPUT N
+20 FOR I = 1 TO N
+30 PRINT I * 2.5; SQR(I),
+40 IF I = 2 THEN GOSUB 100
+50 NEXT I
+60 PRINT \"DONE\"; INT(7 / 2); ABS(0 - 3)
+70 END
+100 PRINT \"SUB\"
+110 RETURN
+";
+    let (output, error) = check(source, "3\n");
+    assert_eq!(error, None);
+    assert!(output.starts_with("? 2.5 1"), "{}", output);
+    assert!(output.ends_with("DONE3 3 \n"), "{}", output);
+}
+
+#[test]
+fn rnd_follows_the_c_runtime() {
+    let (output, _) = check("10 PRINT RND; RND; RND(0)\n20 RANDOMIZE 3\n30 PRINT RND\n", "");
+    assert!(output.starts_with("0.12135 0.651861 0.651861 \n"), "{}", output);
+}
+
+#[test]
+fn errors_stop_the_program() {
+    assert_eq!(check("10 PRINT 1\n20 RETURN\n", "").1.as_deref(), Some("RETURN without GOSUB in 20"));
+    assert_eq!(check("10 GOSUB 10\n", "").1.as_deref(), Some("Out of memory in 10"));
+}
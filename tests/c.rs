@@ -0,0 +1,286 @@
+//! Programs compiled to C: the shape of the C, and what it does when run
+//! through the `compiler` binary and the system C compiler.
+
+use compiler::{Options, Target, compile};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A source file of its own for each program, removed again when dropped.
+struct Source(PathBuf);
+
+impl Source {
+    fn new(text: &str) -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("basic-c-{}-{}.bas", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, text).expect("source written");
+        Source(path)
+    }
+}
+
+impl Drop for Source {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// `compiler <command> <args> source`.
+fn compiler(command: &str, args: &[&str], source: &Source) -> Command {
+    let mut compiler = Command::new(env!("CARGO_BIN_EXE_compiler"));
+    compiler.arg(command).args(args).arg(&source.0);
+    compiler
+}
+
+/// What `compiler run <args> source` prints to stdout and stderr given
+/// `input`, and whether it succeeded.
+fn run_with(source: &str, args: &[&str], input: &str) -> (String, String, bool) {
+    let source = Source::new(source);
+    let mut child = compiler("run", args, &source)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("compiler runs");
+    child.stdin.take().expect("stdin").write_all(input.as_bytes()).expect("input written");
+    let output = child.wait_with_output().expect("compiler finishes");
+    let text = |bytes: Vec<u8>| String::from_utf8(bytes).expect("output is UTF-8");
+    (text(output.stdout), text(output.stderr), output.status.success())
+}
+
+/// What `source` prints, which must run to the end.
+fn run(source: &str) -> String {
+    let (stdout, stderr, success) = run_with(source, &[], "");
+    assert!(success, "{}", stderr);
+    stdout
+}
+
+/// The `main` function of the C `source` compiles to.
+fn c_main(source: &str) -> String {
+    let output = compile(source, &Options::default()).expect("program compiles");
+    let code = String::from_utf8(output.code).expect("C is UTF-8");
+    let start = code.find("int main() {").expect("a main function");
+    code[start..].to_string()
+}
+
+/// What building `source` with `$CC` set to `cc` reports, calling the
+/// source `<source>`; the build must fail.
+fn c_errors(source: &str, args: &[&str], cc: &str) -> String {
+    let source = Source::new(source);
+    let exe = source.0.with_extension("");
+    let output = compiler("build", args, &source).arg("-o").arg(&exe).env("CC", cc).output().expect("compiler runs");
+    assert!(!output.status.success(), "the build fails");
+    let errors = String::from_utf8(output.stderr).expect("stderr is UTF-8");
+    errors.replace(source.0.to_str().expect("a UTF-8 path"), "<source>")
+}
+
+#[test]
+fn comments_end_on_their_line() {
+    // A // comment ending in \ or ??/ would swallow the next line of C
+    let source = "10 REM path C:\\\n20 PRINT 1\n30 REM ??/\n40 PRINT 2\n50 REM */ /*\n60 PRINT 3\n";
+    assert_eq!(run(source), "1 \n2 \n3 \n");
+}
+
+#[test]
+fn c_compiler_output_is_kept() {
+    // -Werror=float-equal makes errors of comparisons in the runtime and the program
+    let errors = c_errors("10 INPUT X\n20 IF X = 1 THEN PRINT 1\n", &[], "cc -Werror=float-equal");
+    assert!(errors.contains(": line 20: error: comparing floating-point"), "{}", errors);
+    // Errors in the runtime, the functions they are in, and the compiler's notes
+    assert!(errors.lines().any(|line| line.contains("is unsafe") && !line.contains(": line ")), "{}", errors);
+    assert!(errors.contains("In function 'rt_div':"), "{}", errors);
+    assert!(errors.contains("some warnings being treated as errors"), "{}", errors);
+}
+
+#[test]
+fn debug_builds_map_c_errors() {
+    let source = "10 INPUT X\n20 IF X = 1 THEN PRINT 1\n";
+    let comparisons = |args| {
+        let errors = c_errors(source, args, "cc -Werror=float-equal");
+        errors.lines().filter(|line| line.contains("error: comparing")).map(str::to_string).collect::<Vec<_>>()
+    };
+    let errors = comparisons(&["-g"]);
+    assert_eq!(errors.len(), comparisons(&[]).len());
+    // #line names the source for the program, and the C compiled for the rest
+    assert!(errors.iter().all(|line| line.starts_with("<source>:")), "{:?}", errors);
+    assert!(errors.iter().any(|line| line.starts_with("<source>:2:")), "{:?}", errors);
+}
+
+#[test]
+fn runtimes_behave_the_same() {
+    let source = "10 INPUT X\n20 PRINT \"X IS\"; X; RND\n30 PRINT 1 / (X - X)\n40 PRINT SQR(0 - X)\n";
+    let inline = run_with(source, &["--runtime", "inline"], "3\n");
+    assert_eq!(inline, run_with(source, &["--runtime", "external"], "3\n"));
+    assert!(inline.0.starts_with("? X IS3 0.12135 \n"), "{}", inline.0);
+}
+
+#[test]
+fn external_runtime_is_written_next_to_the_c() {
+    let source = Source::new("10 PRINT 6 * 7\n");
+    let dir = source.0.with_extension("d");
+    fs::create_dir_all(&dir).expect("directory made");
+    let c_file = dir.join("program.c");
+    let c_path = c_file.to_str().expect("a UTF-8 path");
+    let status = compiler("build", &["--emit", "c", "--runtime", "external", "-o", c_path], &source)
+        .status()
+        .expect("compiler runs");
+    assert!(status.success());
+    let code = fs::read_to_string(&c_file).expect("C written");
+    assert!(code.starts_with("#include \"basic_rt.h\"\n"), "{}", code);
+    assert!(!code.contains("void rt_print_number(double x) {"), "{}", code);
+
+    // The files written are all it takes to build the program
+    let exe = dir.join("program");
+    let cc = Command::new("cc")
+        .arg(&c_file)
+        .arg(dir.join("basic_rt.c"))
+        .arg("-o")
+        .arg(&exe)
+        .arg("-lm")
+        .status()
+        .expect("cc runs");
+    assert!(cc.success());
+    let output = Command::new(&exe).output().expect("program runs");
+    let _ = fs::remove_dir_all(&dir);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42 \n");
+}
+
+#[test]
+fn external_runtime_needs_a_c_file() {
+    let source = Source::new("10 PRINT 1\n");
+    let output = compiler("build", &["--emit", "c", "--runtime", "external"], &source).output().expect("compiler runs");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--runtime=external needs -o with --emit c"));
+}
+
+#[test]
+fn loops_become_while_and_do_while() {
+    let source = "10 I = 1\n20 IF I > 3 THEN 60\n30 PRINT I\n40 I = I + 1\n50 GOTO 20\n60 PRINT \"DONE\"\n";
+    let expected = "\
+int main() {
+    rt_init(1);
+    double I = 0.0;
+
+    I = 1.0;  // 10
+    while (!(I > 3.0)) {  // 20
+        rt_print_number(I);  // 30
+        rt_print_newline();
+        I = (I + 1.0);  // 40
+    }
+    rt_print_string(\"DONE\");  // 60
+    rt_print_newline();
+
+    return 0;
+}
+";
+    assert_eq!(c_main(source), expected);
+    assert_eq!(run(source), "1 \n2 \n3 \nDONE\n");
+
+    let source = "10 I = I + 1\n20 PRINT I\n30 IF I < 3 THEN 10\n";
+    assert!(c_main(source).contains("    do {\n"), "{}", c_main(source));
+    assert!(c_main(source).contains("    } while (I < 3.0);  // 30\n"), "{}", c_main(source));
+    assert_eq!(run(source), "1 \n2 \n3 \n");
+}
+
+#[test]
+fn branches_become_if_else() {
+    let source = "10 INPUT X\n20 IF X > 0 THEN 50\n30 PRINT \"NEG\"\n40 GOTO 60\n50 PRINT \"POS\"\n60 END\n";
+    let expected = "\
+    rt_input(&X);  // 10
+    if (X > 0.0) {  // 20
+        rt_print_string(\"POS\");  // 50
+        rt_print_newline();
+    } else {
+        rt_print_string(\"NEG\");  // 30
+        rt_print_newline();
+    }
+";
+    assert!(c_main(source).contains(expected), "{}", c_main(source));
+    assert!(!c_main(source).contains("goto"), "{}", c_main(source));
+    assert_eq!(run_with(source, &[], "1\n").0, "? POS\n");
+    assert_eq!(run_with(source, &[], "-1\n").0, "? NEG\n");
+}
+
+#[test]
+fn jumps_out_of_loops_become_break_and_skipped_code_if() {
+    let source = "10 I = I + 1\n20 IF I > 4 THEN 70\n30 IF I = 3 THEN 10\n40 PRINT I\n50 GOTO 10\n70 END\n";
+    let expected = "\
+    while (1) {
+        I = (I + 1.0);  // 10
+        if (I > 4.0) break;  // 20
+        if (I != 3.0) {  // 30
+            rt_print_number(I);  // 40
+            rt_print_newline();
+        }
+    }
+";
+    assert!(c_main(source).contains(expected), "{}", c_main(source));
+    assert_eq!(run(source), "1 \n2 \n4 \n");
+}
+
+#[test]
+fn jumps_into_loops_keep_their_labels() {
+    // Line 20 jumps into the middle of the loop from 30 to 60
+    let source = "10 INPUT X\n20 IF X > 0 THEN 40\n30 PRINT 1\n40 PRINT 2\n50 X = X + 3\n60 IF X < 5 THEN 30\n";
+    let main = c_main(source);
+    assert!(main.contains("    if (X > 0.0) goto line40;  // 20\n"), "{}", main);
+    assert!(main.contains("\nline40:\n"), "{}", main);
+    assert_eq!(main.matches("goto").count(), 1, "{}", main);
+    assert_eq!(run_with(source, &[], "7\n").0, "? 2 \n");
+    assert_eq!(run_with(source, &[], "-1\n").0, "? 1 \n2 \n1 \n2 \n");
+}
+
+#[test]
+fn checks_report_errors_and_go_on_in_gw_basic() {
+    let source = "10 X = 0\n20 PRINT 1 / X\n30 X = 10 ^ 300\n40 PRINT X * X\n50 PRINT \"AFTER\"\n";
+    let (stdout, stderr, success) = run_with(source, &["--checks"], "");
+    assert!(success, "{}", stderr);
+    // The largest number stands in for the result, as in GW-BASIC
+    assert_eq!(stdout, "1.79769e+308 \n1.79769e+308 \nAFTER\n");
+    assert_eq!(stderr, "Division by zero in 20\nOverflow in 40\n");
+    // Without --checks the C arithmetic goes on silently
+    assert_eq!(run(source), "inf \ninf \nAFTER\n");
+}
+
+#[test]
+fn checks_stop_on_errors_in_ansi() {
+    let source = "10 X = 0\n20 PRINT 1 / X\n30 PRINT \"AFTER\"\n40 END\n";
+    let (stdout, stderr, success) = run_with(source, &["--checks", "--dialect", "ansi"], "");
+    assert!(!success);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "Division by zero in 20\n");
+}
+
+#[test]
+fn illegal_function_calls_stop() {
+    let (stdout, stderr, success) = run_with("10 X = 0 - 4\n20 PRINT SQR(X)\n30 PRINT 2\n", &["--checks"], "");
+    assert!(!success);
+    assert_eq!(stdout, "");
+    assert_eq!(stderr, "Illegal function call in 20\n");
+}
+
+#[test]
+fn tron_traces_lines_as_gw_basic_does() {
+    let source = "10 PRINT 1\n20 TRON\n30 FOR I = 1 TO 2\n40 PRINT I\n50 NEXT I\n60 TROFF\n70 PRINT 3\n";
+    // TROFF's own line is traced, as the trace is still on when it starts
+    assert_eq!(run(source), "1 \n[30][40]1 \n[50][40]2 \n[50][60]3 \n");
+    // --trace traces from the start and TROFF does not stop it
+    let (stdout, stderr, success) = run_with(source, &["--trace"], "");
+    assert!(success, "{}", stderr);
+    assert_eq!(stdout, "[10]1 \n[20][30][40]1 \n[50][40]2 \n[50][60][70]3 \n");
+}
+
+#[test]
+fn programs_without_tron_do_not_trace() {
+    let main = c_main("10 PRINT 1\n20 GOTO 40\n30 PRINT 2\n40 END\n");
+    assert!(!main.contains("rt_trace"), "{}", main);
+    assert!(c_main("10 TRON\n20 PRINT 1\n").contains("rt_trace(20);"));
+}
+
+#[test]
+fn tron_needs_c() {
+    let options = Options { target: Target::Js, ..Options::default() };
+    assert!(compiler::parse("10 TRON\n20 PRINT 1\n", &options).is_err());
+}
@@ -0,0 +1,119 @@
+//! Control-flow graphs: the blocks a program is cut into, the edges between
+//! them, and their rendering as Graphviz DOT.
+
+use compiler::cfg::{Cfg, ENTRY, EXIT, EdgeKind};
+use compiler::{Options, Statement, parse};
+
+/// Jumps out of a loop into a subroutine and back.
+const SOURCE: &str = "\
+10 INPUT X
+20 IF X > 5 THEN 60
+30 FOR I = 1 TO X
+40 GOSUB 100
+50 NEXT I
+60 END
+100 PRINT \"I=\"; I
+110 RETURN
+";
+
+fn statements(source: &str) -> Vec<Statement> {
+    parse(source, &Options::default()).expect("program parses").statements
+}
+
+/// A block named by its first line, or `entry` or `exit`.
+fn name(cfg: &Cfg, block: usize) -> String {
+    match block {
+        ENTRY => "entry".to_string(),
+        EXIT => "exit".to_string(),
+        _ => cfg.blocks[block].steps[0].label().map_or("?".to_string(), |line| line.to_string()),
+    }
+}
+
+/// Every edge as `from -> to (kind)`, blocks named by their first line.
+fn edges(cfg: &Cfg) -> Vec<String> {
+    let mut edges = Vec::new();
+    for (index, block) in cfg.blocks.iter().enumerate() {
+        for edge in &block.successors {
+            edges.push(format!("{} -> {} ({})", name(cfg, index), name(cfg, edge.target), edge.kind.name()));
+        }
+    }
+    edges
+}
+
+#[test]
+fn blocks_and_edges() {
+    let statements = statements(SOURCE);
+    let cfg = Cfg::new(&statements);
+    let lines: Vec<Vec<i64>> = cfg
+        .statement_blocks()
+        .map(|(_, block)| block.steps.iter().filter_map(|step| step.label()).collect())
+        .collect();
+    // FOR and NEXT have blocks of their own
+    assert_eq!(lines, [vec![10, 20], vec![30], vec![40], vec![50], vec![60], vec![100, 110]]);
+    assert_eq!(
+        edges(&cfg),
+        [
+            "entry -> 10 (fallthrough)",
+            "10 -> 30 (else)",
+            "10 -> 60 (then)",
+            "30 -> 40 (fallthrough)",
+            "30 -> 60 (loop exit)",
+            "40 -> 100 (gosub)",
+            "50 -> 40 (loop)",
+            "50 -> 60 (fallthrough)",
+            "60 -> exit (end)",
+            "100 -> 50 (return)",
+        ]
+    );
+    for (index, block) in cfg.blocks.iter().enumerate() {
+        for edge in &block.successors {
+            assert!(cfg.blocks[edge.target].predecessors.contains(&index));
+        }
+    }
+}
+
+#[test]
+fn unreachable_blocks() {
+    let statements = statements("10 GOTO 30\n20 PRINT 1\n30 PRINT 2\n");
+    let cfg = Cfg::new(&statements);
+    let reachable = cfg.reachable();
+    let unreachable: Vec<String> = (0..cfg.blocks.len()).filter(|b| !reachable[*b]).map(|b| name(&cfg, b)).collect();
+    assert_eq!(unreachable, ["20"]);
+    // Falling off the end goes to the exit
+    assert!(edges(&cfg).contains(&"30 -> exit (fallthrough)".to_string()));
+    assert_eq!(cfg.blocks[2].successors[0].kind, EdgeKind::Goto);
+}
+
+#[test]
+fn empty_programs_go_straight_to_the_exit() {
+    let cfg = Cfg::new(&[]);
+    assert_eq!(edges(&cfg), ["entry -> exit (fallthrough)"]);
+}
+
+#[test]
+fn dot() {
+    let statements = statements(SOURCE);
+    let expected = r#"digraph cfg {
+    node [shape=box, fontname="monospace"];
+    b0 [label="entry", shape=oval];
+    b1 [label="exit", shape=oval];
+    b2 [label="10 INPUT X\l20 IF X > 5 THEN 60\l"];
+    b3 [label="30 FOR I = 1 TO X\l"];
+    b4 [label="40 GOSUB 100\l"];
+    b5 [label="50 NEXT I\l"];
+    b6 [label="60 END\l"];
+    b7 [label="100 PRINT \"I=\"; I\l110 RETURN\l"];
+    b0 -> b2;
+    b2 -> b3 [label="else"];
+    b2 -> b6 [label="then"];
+    b3 -> b4;
+    b3 -> b6 [label="loop exit"];
+    b4 -> b7 [label="gosub"];
+    b5 -> b4 [label="loop"];
+    b5 -> b6;
+    b6 -> b1 [label="end"];
+    b7 -> b5 [label="return"];
+}
+"#;
+    assert_eq!(Cfg::new(&statements).to_dot(), expected);
+}
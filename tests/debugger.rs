@@ -0,0 +1,123 @@
+//! Scripted debugger sessions: commands are read from a string and the
+//! whole transcript, prompts and the program's own output included, is
+//! checked.
+
+use compiler::codegen::CodegenOptions;
+use compiler::{Options, debugger, parse};
+
+/// Sets X, then triples it in a subroutine called from a loop.
+const SOURCE: &str = "\
+10 X = 1
+20 FOR I = 1 TO 2
+30 GOSUB 100
+40 NEXT I
+50 PRINT \"END\"; X
+60 END
+100 X = X * 3
+110 RETURN
+";
+
+/// The transcript of debugging `source` with `commands`.
+fn session(source: &str, commands: &str) -> String {
+    let statements = parse(source, &Options::default()).expect("program parses").statements;
+    let mut output = Vec::new();
+    debugger::debug(source, &statements, CodegenOptions::default(), commands.as_bytes(), &mut output)
+        .expect("output written");
+    String::from_utf8(output).expect("output is UTF-8")
+}
+
+#[test]
+fn breakpoints_stacks_and_variables() {
+    let commands = "break 100\nbreak\ncontinue\nstack\nvars\nprint X + 1; I\nlet X = 10\n?X\n";
+    let expected = "\
+Stopped at the start; help lists the commands
+  10 X = 1
+(debug) Breakpoint at line 100
+(debug) Breakpoints at 100
+(debug) Breakpoint at line 100
+  100 X = X * 3
+(debug) GOSUB stack:
+  GOSUB at 30
+FOR stack:
+  FOR I at 20, I = 1
+(debug)   I = 1
+  X = 1
+(debug) 2 1 \n(debug) (debug) 10 \n(debug) ";
+    assert_eq!(session(SOURCE, commands), expected);
+}
+
+#[test]
+fn step_goes_into_subroutines_and_next_over_them() {
+    let commands = "b 30\nc\ns\ns\ns\ns\nlist\n";
+    let expected = "\
+Stopped at the start; help lists the commands
+  10 X = 1
+(debug) Breakpoint at line 30
+(debug) Breakpoint at line 30
+  30 GOSUB 100
+(debug)   100 X = X * 3
+(debug)   110 RETURN
+(debug)   40 NEXT I
+(debug)   30 GOSUB 100
+(debug)    10 X = 1
+   20 FOR I = 1 TO 2
+=> 30 GOSUB 100
+   40 NEXT I
+   50 PRINT \"END\"; X
+   60 END
+(debug) ";
+    assert_eq!(session(SOURCE, commands), expected);
+    // next runs the GOSUB through, and stops at breakpoints on the way
+    let transcript = session(SOURCE, "b 30\nc\nn\nn\nd 30\nn\n");
+    let expected = "\
+(debug) Breakpoint at line 30
+  30 GOSUB 100
+(debug)   40 NEXT I
+(debug) Breakpoint at line 30
+  30 GOSUB 100
+(debug) (debug)   40 NEXT I
+(debug) ";
+    assert!(transcript.ends_with(expected), "{}", transcript);
+}
+
+#[test]
+fn runs_end_and_start_again() {
+    let transcript = session(SOURCE, "c\nc\nrun\nq\nc\n");
+    let ended = "END9 \nThe program has ended; run starts it again\n";
+    assert_eq!(transcript.matches(ended).count(), 2, "{}", transcript);
+    assert!(transcript.contains(&format!("(debug) {}(debug) The program has ended", ended)), "{}", transcript);
+    // Nothing after quit is read
+    assert!(transcript.ends_with(&format!("(debug) {}(debug) ", ended)), "{}", transcript);
+}
+
+#[test]
+fn tron_traces_lines() {
+    let transcript = session(SOURCE, "tron\nc\ntroff\nrun\n");
+    assert!(transcript.contains("[10][20][30][100][110][40][30][100][110][40][50]END9 \n[60]\n"), "{}", transcript);
+    assert!(transcript.contains("(debug) END9 \n"), "{}", transcript);
+}
+
+#[test]
+fn input_shares_the_commands_reader() {
+    let transcript = session("10 INPUT A\n20 PRINT A * 2\n", "c\n21\nq\n");
+    assert!(transcript.contains("(debug) ? 42 \nThe program has ended"), "{}", transcript);
+}
+
+#[test]
+fn mistakes_are_reported() {
+    let transcript = session(SOURCE, "b 999\nb x\nd 50\np INT()\nlet PRINT 1\nfoo\n");
+    for error in [
+        "error: there is no line 999",
+        "error: 'x' is not a line number",
+        "error: no breakpoint at '50'",
+        "error: INT takes 1 argument",
+        "error: Expected identifier after LET",
+        "error: unknown command 'foo'; help lists them",
+    ] {
+        assert!(transcript.contains(&format!("(debug) {}\n", error)), "{}", transcript);
+    }
+    let transcript = session("10 RETURN\n", "c\nbt\nvars\nl\n");
+    assert!(transcript.contains("(debug) RETURN without GOSUB in 10\n"), "{}", transcript);
+    let after = "(debug) No variables are set\n(debug) The program is not running\n";
+    assert!(transcript.contains(after), "{}", transcript);
+}
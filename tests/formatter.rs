@@ -0,0 +1,90 @@
+//! The formatter: the canonical form it prints, its options, and that
+//! printing a program and parsing it again gives back the same program.
+
+use compiler::formatter::{FormatOptions, Formatter};
+use compiler::json::JsonValue;
+use compiler::serialize::ast_to_json;
+use compiler::{Options, Statement, parse};
+
+const SOURCE: &str = "\
+10 rem  Hello, World
+20 for i=1 to 3 step .5
+30 x = (i+.1)*2^(2^2)
+40 if x>1 then print \"big\";x,
+50 next i
+60 input n
+70 print int(n)/(3-1); rnd; timer
+80 gosub 100
+90 end
+100 tron
+110 randomize 3
+120 return
+";
+
+fn statements(source: &str) -> Vec<Statement> {
+    parse(source, &Options::default()).expect("program parses").statements
+}
+
+fn format(source: &str, options: FormatOptions) -> String {
+    Formatter::new(options).format(&statements(source))
+}
+
+#[test]
+fn programs_print_in_canonical_form() {
+    let expected = "\
+10 REM Hello, World
+20 FOR i = 1 TO 3 STEP .5
+30 LET x = (i + .1) * 2 ^ (2 ^ 2)
+40 IF x > 1 THEN PRINT \"big\"; x,
+50 NEXT i
+60 INPUT n
+70 PRINT INT(n) / (3 - 1); RND; TIMER
+80 GOSUB 100
+90 END
+100 TRON
+110 RANDOMIZE 3
+120 RETURN
+";
+    assert_eq!(format(SOURCE, FormatOptions::default()), expected);
+}
+
+#[test]
+fn options_indent_for_bodies_and_leave_out_let() {
+    let formatted = format(SOURCE, FormatOptions { indent_for: true, explicit_let: false });
+    let lines: Vec<&str> = formatted.lines().collect();
+    let expected = [
+        "20 FOR i = 1 TO 3 STEP .5",
+        "30   x = (i + .1) * 2 ^ (2 ^ 2)",
+        "40   IF x > 1 THEN PRINT \"big\"; x,",
+        "50 NEXT i",
+    ];
+    assert_eq!(lines[1..5], expected);
+}
+
+#[test]
+fn formatting_round_trips() {
+    // Function names are kept as written, and printed in capitals
+    let source = SOURCE.replace("int(", "INT(").replace("rnd", "RND");
+    for options in [FormatOptions::default(), FormatOptions { indent_for: true, explicit_let: false }] {
+        let formatted = format(&source, options.clone());
+        // Spans move, so the programs are compared without them
+        let program = |source: &str| without_spans(&ast_to_json(&statements(source)));
+        assert_eq!(program(&formatted), program(&source));
+        assert_eq!(format(&formatted, options), formatted);
+    }
+}
+
+/// `json` without its `span` members.
+fn without_spans(json: &JsonValue) -> JsonValue {
+    match json {
+        JsonValue::Array(items) => JsonValue::Array(items.iter().map(without_spans).collect()),
+        JsonValue::Object(members) => JsonValue::Object(
+            members
+                .iter()
+                .filter(|(key, _)| key != "span")
+                .map(|(key, value)| (key.clone(), without_spans(value)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
@@ -0,0 +1,58 @@
+//! `#line` directives in the generated C: statements point at the BASIC
+//! source, and the code around them back at the C file itself.
+
+use compiler::{CodegenOptions, Options, compile};
+
+const PROGRAM: &str = "10 GOSUB 100\n20 GOTO 120\n100 PRINT 1\n110 RETURN\n120 PRINT 2\n";
+
+fn c(c_file: Option<&str>) -> String {
+    let codegen = CodegenOptions {
+        line_directives: Some("prog.bas".to_string()),
+        c_file: c_file.map(str::to_string),
+        ..CodegenOptions::default()
+    };
+    let output = compile(PROGRAM, &Options { codegen, ..Options::default() }).expect("program compiles");
+    String::from_utf8(output.code).expect("C is UTF-8")
+}
+
+/// The directives in `code` that name `file`, with the line each is on.
+fn directives<'a>(code: &'a str, file: &str) -> Vec<(usize, &'a str)> {
+    let suffix = format!(" \"{}\"", file);
+    code.lines()
+        .enumerate()
+        .filter_map(|(index, line)| Some((index + 1, line.strip_prefix("#line ")?.strip_suffix(&suffix)?)))
+        .collect()
+}
+
+#[test]
+fn generated_code_points_at_the_c_file() {
+    let code = c(Some("out/prog.c"));
+    let generated = directives(&code, "out/prog.c");
+    // Before the runtime, and before the end of main and the RETURN dispatch
+    assert_eq!(generated.len(), 2);
+    assert_eq!(generated[0], (1, "2"));
+    for (line, named) in generated {
+        assert_eq!(named, (line + 1).to_string(), "line {} names the line after it", line);
+    }
+    let (last, _) = directives(&code, "out/prog.c")[1];
+    let dispatch = code.lines().position(|line| line == "gosub_return:").expect("a dispatch") + 1;
+    assert!(directives(&code, "prog.bas").iter().all(|(line, _)| *line < last));
+    assert!(last < dispatch);
+}
+
+#[test]
+fn statements_point_at_the_source() {
+    let code = c(None);
+    let mut basic: Vec<&str> = directives(&code, "prog.bas").into_iter().map(|(_, line)| line).collect();
+    basic.sort();
+    // GOTO 120 is structured away: line 120's code follows line 10's
+    assert_eq!(basic, ["1", "3", "4", "5"]);
+    // Without a C file there is nothing to point back at
+    assert_eq!(code.matches("#line").count(), 4);
+}
+
+#[test]
+fn no_directives_by_default() {
+    let output = compile(PROGRAM, &Options::default()).expect("program compiles");
+    assert!(!String::from_utf8(output.code).expect("C is UTF-8").contains("#line"));
+}
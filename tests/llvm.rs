@@ -0,0 +1,106 @@
+//! The LLVM IR that `--emit llvm-ir` writes: its shape, its debug
+//! locations, and, where `llc` is installed, what it does when compiled and
+//! linked with the C runtime.
+
+use compiler::codegen::CodegenOptions;
+use compiler::{Options, Target, compile, runtime};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const SOURCE: &str = "\
+10 INPUT N
+20 FOR I = 1 TO N
+30 PRINT I * 2.5
+40 NEXT I
+50 GOSUB 100
+60 END
+100 PRINT \"SUB\"
+110 RETURN
+";
+
+fn ir(source: &str, codegen: CodegenOptions) -> String {
+    let options = Options { target: Target::LlvmIr, codegen, ..Options::default() };
+    String::from_utf8(compile(source, &options).expect("program compiles").code).expect("IR is UTF-8")
+}
+
+#[test]
+fn main_has_a_block_per_line_and_declares_the_runtime() {
+    let ir = ir(SOURCE, CodegenOptions::default());
+    for line in ["10", "20", "30", "40", "50", "60", "100"] {
+        assert!(ir.contains(&format!("\nline{}:  ; {}", line, line)), "no block for {}\n{}", line, ir);
+    }
+    assert!(ir.contains("\ndefine i32 @main() {\n"), "{}", ir);
+    // Variables live in allocas, so there is no phi to build
+    assert!(ir.contains("  %v.I = alloca double\n"), "{}", ir);
+    assert!(!ir.contains("phi"), "{}", ir);
+    let declared: Vec<&str> = ir.lines().filter(|line| line.starts_with("declare ")).collect();
+    assert!(declared.contains(&"declare void @rt_input(ptr)"), "{:?}", declared);
+    assert!(declared.iter().all(|line| line.contains("@rt_")), "{:?}", declared);
+}
+
+#[test]
+fn debug_locations_name_source_lines() {
+    let codegen = CodegenOptions { line_directives: Some("prog.bas".to_string()), ..CodegenOptions::default() };
+    let ir = ir(SOURCE, codegen);
+    assert!(ir.contains("define i32 @main() !dbg !2 {"), "{}", ir);
+    assert!(ir.contains("!DIFile(filename: \"prog.bas\", directory: \"\")"), "{}", ir);
+    assert!(ir.contains("call void @rt_input(ptr %v.N), !dbg !6\n"), "{}", ir);
+    assert!(ir.contains("!6 = !DILocation(line: 1, column: 1, scope: !2)"), "{}", ir);
+    assert!(ir.contains("!DILocation(line: 7, column: 1, scope: !2)"), "{}", ir);
+}
+
+/// A directory of its own, removed again when dropped.
+struct Scratch(PathBuf);
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Compile `ir` with `llc` and link it with the C runtime, or `None` if
+/// there is no `llc`.
+fn link(ir: &str, scratch: &Scratch) -> Option<PathBuf> {
+    if Command::new("llc").arg("--version").output().is_err() {
+        return None;
+    }
+    fs::write(scratch.0.join("prog.ll"), ir).expect("IR written");
+    fs::write(scratch.0.join(runtime::HEADER_NAME), runtime::HEADER).expect("header written");
+    fs::write(scratch.0.join(runtime::SOURCE_NAME), runtime::SOURCE).expect("runtime written");
+    let llc = |extra: &[&str]| {
+        Command::new("llc")
+            .args(extra)
+            .args(["-relocation-model=pic", "-filetype=obj", "prog.ll", "-o", "prog.o"])
+            .current_dir(&scratch.0)
+            .output()
+            .expect("llc runs")
+            .status
+            .success()
+    };
+    // LLVM 14 reads `ptr` only when asked to; later versions always do
+    assert!(llc(&[]) || llc(&["-opaque-pointers"]), "llc rejects the IR");
+    let status = Command::new("cc")
+        .args(["prog.o", runtime::SOURCE_NAME, "-o", "prog", "-lm"])
+        .current_dir(&scratch.0)
+        .status()
+        .expect("cc runs");
+    assert!(status.success());
+    Some(scratch.0.join("prog"))
+}
+
+#[test]
+fn compiled_ir_runs() {
+    let scratch = Scratch(std::env::temp_dir().join(format!("basic-llvm-{}", std::process::id())));
+    fs::create_dir_all(&scratch.0).expect("directory made");
+    let Some(exe) = link(&ir(SOURCE, CodegenOptions::default()), &scratch) else {
+        eprintln!("llc is not installed; not running the IR");
+        return;
+    };
+    let mut child = Command::new(exe).stdin(Stdio::piped()).stdout(Stdio::piped()).spawn().expect("program runs");
+    child.stdin.take().expect("stdin").write_all(b"2\n").expect("input written");
+    let output = child.wait_with_output().expect("program finishes");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "? 2.5 \n5 \nSUB\n");
+}
@@ -0,0 +1,173 @@
+//! A scripted client session with the language server: framed JSON-RPC
+//! messages in, the server's replies read back and checked in order.
+
+use compiler::json::JsonValue;
+use compiler::lsp::serve;
+
+const URI: &str = "file:///test.bas";
+
+/// Divides by zero, and line 30 can never run.
+const WARNINGS: &str = "10 X = INT(2.5) / 0\n20 GOTO 10\n30 PRINT X\n";
+/// INT needs an argument.
+const ERROR: &str = "10 PRINT INT()\n";
+
+fn frame(message: &str) -> String {
+    format!("Content-Length: {}\r\n\r\n{}", message.len(), message)
+}
+
+fn request(id: i64, method: &str, params: &str) -> String {
+    frame(&format!(r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#, id, method, params))
+}
+
+fn notify(method: &str, params: &str) -> String {
+    frame(&format!(r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#, method, params))
+}
+
+fn open(text: &str) -> String {
+    let text = JsonValue::string(text);
+    let document = format!(r#"{{"uri":"{}","languageId":"basic","version":1,"text":{}}}"#, URI, text);
+    notify("textDocument/didOpen", &format!(r#"{{"textDocument":{}}}"#, document))
+}
+
+/// Replace the whole document, as full synchronisation does.
+fn change(text: &str) -> String {
+    let changes = format!(r#"[{{"text":{}}}]"#, JsonValue::string(text));
+    let params = format!(r#"{{"textDocument":{{"uri":"{}","version":2}},"contentChanges":{}}}"#, URI, changes);
+    notify("textDocument/didChange", &params)
+}
+
+fn position(line: usize, character: usize) -> String {
+    format!(r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#, URI, line, character)
+}
+
+/// The messages the server wrote, unframed.
+fn replies(output: &[u8]) -> Vec<JsonValue> {
+    let mut output = std::str::from_utf8(output).expect("replies are UTF-8");
+    let mut replies = Vec::new();
+    while !output.is_empty() {
+        let (header, rest) = output.split_once("\r\n\r\n").expect("a header");
+        let length: usize = header.strip_prefix("Content-Length: ").expect("a length").parse().expect("a number");
+        let (body, rest) = rest.split_at(length);
+        replies.push(JsonValue::parse(body).expect("a reply is JSON"));
+        output = rest;
+    }
+    replies
+}
+
+fn keys(value: &JsonValue) -> Vec<&str> {
+    match value {
+        JsonValue::Object(members) => members.iter().map(|(key, _)| key.as_str()).collect(),
+        other => panic!("expected an object, found {}", other),
+    }
+}
+
+#[test]
+fn session() {
+    let input = [
+        request(0, "textDocument/hover", &position(0, 7)),
+        request(1, "initialize", "{}"),
+        notify("initialized", "{}"),
+        open(WARNINGS),
+        request(2, "textDocument/definition", &position(1, 8)),
+        request(3, "textDocument/hover", &position(0, 7)),
+        change(ERROR),
+        request(4, "basic/unknown", "{}"),
+        request(5, "shutdown", "null"),
+        request(6, "textDocument/hover", &position(0, 9)),
+        notify("exit", "null"),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    let code = serve(input.as_bytes(), &mut output).expect("the session runs");
+    assert_eq!(code, 0, "exit after shutdown succeeds");
+
+    let replies = replies(&output);
+    assert_eq!(replies.len(), 9);
+
+    // Nothing is answered before initialize but the error
+    assert_eq!(replies[0].get("id").as_i64(), Some(0));
+    assert_eq!(replies[0].get("error").get("code").as_i64(), Some(-32002));
+
+    assert_eq!(replies[1].get("id").as_i64(), Some(1));
+    assert_eq!(replies[1].get("result").get("capabilities").get("hoverProvider").as_bool(), Some(true));
+
+    let published = &replies[2];
+    assert_eq!(published.get("method").as_str(), Some("textDocument/publishDiagnostics"));
+    assert_eq!(published.get("params").get("uri").as_str(), Some(URI));
+    let diagnostics = published.get("params").get("diagnostics").as_array().expect("diagnostics");
+    let codes: Vec<_> = diagnostics.iter().map(|d| d.get("code").as_str()).collect();
+    assert_eq!(codes, [Some("division-by-zero"), Some("unreachable-code")]);
+    assert!(diagnostics.iter().all(|d| d.get("severity").as_i64() == Some(2)));
+    assert_eq!(diagnostics[1].get("range").get("start").get("line").as_i64(), Some(2));
+
+    // GOTO 10 goes to the label on the first line
+    let definition = replies[3].get("result");
+    assert_eq!(definition.get("uri").as_str(), Some(URI));
+    assert_eq!(definition.get("range").get("start").get("line").as_i64(), Some(0));
+    assert_eq!(definition.get("range").get("start").get("character").as_i64(), Some(0));
+
+    let hover = replies[4].get("result").get("contents").get("value").as_str().expect("hover text");
+    assert!(hover.contains("INT(x)"), "{}", hover);
+
+    // Errors have no code, and the key is left out rather than null
+    let diagnostics = replies[5].get("params").get("diagnostics").as_array().expect("diagnostics");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].get("severity").as_i64(), Some(1));
+    assert_eq!(keys(&diagnostics[0]), ["range", "severity", "source", "message"]);
+
+    assert_eq!(replies[6].get("error").get("code").as_i64(), Some(-32601));
+    assert_eq!(replies[7].get("id").as_i64(), Some(5));
+    assert!(replies[7].get("result").is_null());
+    assert_eq!(replies[8].get("error").get("code").as_i64(), Some(-32600));
+}
+
+#[test]
+fn exit_without_shutdown() {
+    let input = [request(1, "initialize", "{}"), notify("exit", "null")].concat();
+    let mut output = Vec::new();
+    assert_eq!(serve(input.as_bytes(), &mut output).expect("the session runs"), 1);
+    assert_eq!(replies(&output).len(), 1);
+}
+
+#[test]
+fn unreadable_messages_are_answered() {
+    let input = [
+        frame("{bad json"),
+        "X-Header: 1\r\n\r\n".to_string(),
+        request(1, "initialize", "{}"),
+        request(2, "shutdown", "null"),
+        notify("exit", "null"),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    assert_eq!(serve(input.as_bytes(), &mut output).expect("the session runs"), 0);
+    let replies = replies(&output);
+    assert_eq!(replies.len(), 4);
+    for reply in &replies[..2] {
+        assert!(reply.get("id").is_null());
+        assert_eq!(reply.get("error").get("code").as_i64(), Some(-32700));
+    }
+    assert_eq!(replies[2].get("id").as_i64(), Some(1));
+    assert_eq!(replies[3].get("id").as_i64(), Some(2));
+}
+
+#[test]
+fn keywords_that_are_builtins_hover() {
+    let input = [
+        request(1, "initialize", "{}"),
+        open("10 PRINT TIMER\n"),
+        request(2, "textDocument/hover", &position(0, 10)),
+        request(3, "textDocument/hover", &position(0, 14)),
+        request(4, "textDocument/hover", &position(0, 4)),
+    ]
+    .concat();
+    let mut output = Vec::new();
+    serve(input.as_bytes(), &mut output).expect("the session runs");
+    let replies = replies(&output);
+    for reply in &replies[2..4] {
+        let hover = reply.get("result").get("contents").get("value").as_str().expect("hover text");
+        assert!(hover.contains("Seconds since midnight."), "{}", hover);
+    }
+    // PRINT is a keyword but no builtin
+    assert!(replies[4].get("result").is_null());
+}
@@ -0,0 +1,149 @@
+//! Regression tests for how numbers behave when a program runs: GW-BASIC's
+//! MBF singles under `--numbers mbf`, and its RND sequence. Programs run in
+//! the interpreter, which the generated code is kept in step with.
+
+use compiler::interp::{Interpreter, Io, RuntimeError};
+use compiler::numbers::{mbf_max, round_mbf};
+use compiler::{CodegenOptions, Numbers, Options, parse};
+use std::collections::VecDeque;
+
+struct Console {
+    output: String,
+    input: VecDeque<String>,
+}
+
+impl Io for Console {
+    fn print(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn input(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+
+    fn warn(&mut self, error: &RuntimeError) {
+        self.output.push_str(&format!("[{}]", error));
+    }
+}
+
+/// What `source` prints, ending with the error that stopped it, if any.
+fn run(source: &str, input: &str, options: CodegenOptions) -> String {
+    let program = parse(source, &Options::default()).expect("program parses");
+    let mut interpreter = Interpreter::new(&program.statements, options);
+    let mut console = Console { output: String::new(), input: input.lines().map(str::to_string).collect() };
+    while !interpreter.finished() {
+        if let Err(error) = interpreter.step(&mut console) {
+            console.output.push_str(&format!("[{}]", error));
+        }
+    }
+    console.output
+}
+
+fn mbf() -> CodegenOptions {
+    CodegenOptions { numbers: Numbers::Mbf, ..CodegenOptions::default() }
+}
+
+#[test]
+fn mbf_rounding() {
+    assert_eq!(round_mbf(0.0), Some(0.0));
+    assert_eq!(round_mbf(16777216.0), Some(16777216.0));
+    // Halves round away from zero, past the 24-bit mantissa
+    assert_eq!(round_mbf(16777217.0), Some(16777218.0));
+    assert_eq!(round_mbf(-16777217.0), Some(-16777218.0));
+    assert_eq!(round_mbf(0.1), Some(0.1f32 as f64));
+    assert_eq!(round_mbf(mbf_max()), Some(mbf_max()));
+    assert_eq!(round_mbf(mbf_max() * 2.0), None);
+    assert_eq!(round_mbf(f64::INFINITY), None);
+    assert_eq!(round_mbf(1e-40), Some(0.0));
+    assert!(round_mbf(f64::NAN).is_some_and(f64::is_nan));
+}
+
+#[test]
+fn mbf_printing() {
+    let cases = [
+        (0.0, " 0 "),
+        (1.0, " 1 "),
+        (-2.5, "-2.5 "),
+        (1.0 / 3.0, " .3333333 "),
+        (0.01, " .01 "),
+        (0.001, " 1E-03 "),
+        (1234567.0, " 1234567 "),
+        (12345678.0, " 1.234568E+07 "),
+        (mbf_max(), " 1.701412E+38 "),
+    ];
+    for (x, printed) in cases {
+        assert_eq!(Numbers::Mbf.format(round_mbf(x).expect("in range")), printed, "{}", x);
+    }
+}
+
+#[test]
+fn double_printing() {
+    let cases = [
+        (0.0, "0 "),
+        (-0.0, "-0 "),
+        (1.0 / 3.0, "0.333333 "),
+        (100000.0, "100000 "),
+        (1000000.0, "1e+06 "),
+        (0.0001, "0.0001 "),
+        (0.00001, "1e-05 "),
+        (f64::INFINITY, "inf "),
+    ];
+    for (x, printed) in cases {
+        assert_eq!(Numbers::Double.format(x), printed, "{}", x);
+    }
+}
+
+#[test]
+fn mbf_arithmetic() {
+    let source = "10 X = 1 / 3\n20 PRINT X; X * 3; 16777216 + 1\n";
+    assert_eq!(run(source, "", CodegenOptions::default()), "0.333333 1 1.67772e+07 \n");
+    assert_eq!(run(source, "", mbf()), " .3333333  1  1.677722E+07 \n");
+}
+
+#[test]
+fn mbf_overflow() {
+    // GW-BASIC reports the overflow, carries on with the largest single
+    let source = "10 X = 10000000000\n20 X = X * X * X * X\n30 PRINT X\n";
+    assert_eq!(run(source, "", mbf()), "[Overflow in 20] 1.701412E+38 \n");
+}
+
+#[test]
+fn rnd_sequence() {
+    // The numbers GW-BASIC prints
+    let source = "10 FOR I = 1 TO 5\n20 PRINT RND;\n30 NEXT I\n";
+    assert_eq!(run(source, "", mbf()), " .1213501  .651861  .8688611  .7297624  .798853 ");
+    assert_eq!(run(source, "", CodegenOptions::default()), "0.12135 0.651861 0.868861 0.729762 0.798853 ");
+}
+
+#[test]
+fn rnd_repeats_and_reseeds() {
+    let source = "\
+10 A = RND(0 - 7)
+20 B = RND
+30 C = RND(0)
+40 D = RND(0 - 7)
+50 PRINT A; B; C; D
+";
+    let output = run(source, "", CodegenOptions::default());
+    let numbers: Vec<&str> = output.split_whitespace().collect();
+    assert_eq!(numbers.len(), 4);
+    // RND(0) repeats the last number; the same negative seed restarts
+    assert_eq!(numbers[2], numbers[1]);
+    assert_eq!(numbers[3], numbers[0]);
+    assert_ne!(numbers[0], "0.12135");
+}
+
+#[test]
+fn randomize_changes_the_sequence() {
+    let source = "10 RANDOMIZE 1\n20 PRINT RND; RND\n30 RANDOMIZE 1\n40 PRINT RND; RND\n50 RANDOMIZE 2\n60 PRINT RND\n";
+    let output = run(source, "", CodegenOptions::default());
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_ne!(lines[0], "0.12135 0.651861 ");
+    assert!(!lines[2].is_empty() && lines[0] != lines[2]);
+
+    // Without an argument the seed comes from --seed instead of a prompt
+    let options = CodegenOptions { seed: Some(1), ..CodegenOptions::default() };
+    let seeded = run("10 RANDOMIZE\n20 PRINT RND; RND\n", "", options);
+    assert_eq!(seeded.lines().next(), lines.first().copied());
+}
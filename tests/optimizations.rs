@@ -0,0 +1,187 @@
+//! Regression tests for the passes between parsing and code generation:
+//! which variables can be `int32_t`, constant folding and dead code
+//! elimination. Programs are checked as the formatter prints them after
+//! the passes run.
+
+use compiler::formatter::{FormatOptions, Formatter};
+use compiler::infer::Types;
+use compiler::numbers::round_mbf;
+use compiler::{Numbers, Options, PrintItem, Statement, StatementNode, Target, compile, dce, fold, parse};
+
+fn statements(source: &str) -> Vec<Statement> {
+    parse(source, &Options::default()).expect("program parses").statements
+}
+
+fn format(statements: &[Statement]) -> String {
+    Formatter::new(FormatOptions { indent_for: false, explicit_let: false }).format(statements)
+}
+
+/// `source` after folding and dead code elimination, as `compile` runs them.
+fn optimize(source: &str, numbers: Numbers) -> String {
+    let mut statements = statements(source);
+    fold::fold(&mut statements, numbers);
+    dce::eliminate(&mut statements, numbers == Numbers::Mbf, false);
+    format(&statements)
+}
+
+/// The variables of `source` that can be stored in an `int32_t`.
+fn integers(source: &str, numbers: Numbers) -> Vec<&'static str> {
+    let types = Types::infer(&statements(source), numbers);
+    ["A", "B", "I", "N", "X", "Z"].into_iter().filter(|var| types.is_integer(var)).collect()
+}
+
+#[test]
+fn loop_counters_are_integers() {
+    let source = "\
+10 FOR I = 1 TO 100
+20 N = I * 2
+30 A = A + I
+40 NEXT I
+50 X = I / 2
+60 PRINT N; A; X
+";
+    // A sum's bound grows every round until it is widened to infinity
+    assert_eq!(integers(source, Numbers::Double), ["I", "N"]);
+}
+
+#[test]
+fn fractions_and_input_are_doubles() {
+    let source = "\
+10 A = 1.5
+20 B = INT(A) * 3
+30 INPUT X
+40 I = SQR(4)
+50 PRINT A; B; X; I
+";
+    assert_eq!(integers(source, Numbers::Double), ["B"]);
+}
+
+#[test]
+fn unbounded_counters_are_doubles() {
+    // N grows without bound, so after widening it may leave int32_t's range
+    let source = "10 N = N + 1\n20 IF N > 0 THEN 10\n";
+    assert!(integers(source, Numbers::Double).is_empty());
+}
+
+#[test]
+fn counters_assigned_elsewhere_are_stepped_from_any_value() {
+    // NEXT steps I from 2147483647, which int32_t cannot hold
+    let source = "10 FOR I = 1 TO 10\n20 I = 2147483647\n30 NEXT I\n40 PRINT I\n";
+    assert!(integers(source, Numbers::Double).is_empty());
+    let source = "10 FOR I = 1 TO 10\n20 GOSUB 100\n30 NEXT I\n40 END\n100 I = 2147483647\n110 RETURN\n";
+    assert!(integers(source, Numbers::Double).is_empty());
+    // A counter only its FOR assigns stays within the loop's bounds
+    assert_eq!(integers("10 FOR I = 1 TO 2147483646\n20 NEXT I\n", Numbers::Double), ["I"]);
+}
+
+#[test]
+fn negative_zero_stays_double() {
+    let source = "10 Z = 0 * (0 - 1)\n20 A = 0 - 1\n30 PRINT Z; A\n";
+    assert_eq!(integers(source, Numbers::Double), ["A"]);
+}
+
+#[test]
+fn mbf_integers_are_exact_singles() {
+    // 2^24 + 1 is an int32_t but not an MBF single
+    let source = "10 A = 16777216\n20 B = 16777217\n30 PRINT A; B\n";
+    assert_eq!(integers(source, Numbers::Double), ["A", "B"]);
+    assert_eq!(integers(source, Numbers::Mbf), ["A"]);
+}
+
+#[test]
+fn integers_are_declared_in_c() {
+    let output = compile("10 FOR I = 1 TO 3\n20 X = I / 2\n30 PRINT X\n40 NEXT I\n", &Options::default())
+        .expect("program compiles");
+    let code = String::from_utf8(output.code).expect("C is UTF-8");
+    assert!(code.contains("int32_t I = 0;"), "{}", code);
+    assert!(code.contains("double X = 0.0;"), "{}", code);
+}
+
+#[test]
+fn constants_fold() {
+    let source = "10 PRINT 2 * 3 + 4; SQR(16); INT(7 / 2); 2 ^ 10\n";
+    assert_eq!(optimize(source, Numbers::Double), "10 PRINT 10.0; 4.0; 3.0; 1024.0\n");
+}
+
+#[test]
+fn identities_are_removed() {
+    let source = "10 INPUT X\n20 PRINT X * 1; 0 + X; X - 0; X / 1; X ^ 1; X ^ 2\n";
+    assert_eq!(optimize(source, Numbers::Double), "10 INPUT X\n20 PRINT X; X; X; X; X; X * X\n");
+}
+
+#[test]
+fn errors_are_left_for_the_runtime() {
+    let source = "10 PRINT 1 / 0; SQR(0 - 1)\n";
+    assert_eq!(optimize(source, Numbers::Double), "10 PRINT 1 / 0; SQR(-1.0)\n");
+}
+
+#[test]
+fn folding_rounds_to_mbf() {
+    let Some(StatementNode::Print { items, .. }) = statements("10 PRINT 1 / 3\n").pop().map(|stmt| stmt.node) else {
+        panic!("a PRINT");
+    };
+    let PrintItem::Expr(expr) = &items[0] else {
+        panic!("an expression");
+    };
+    let double = fold::constant(&fold::fold_expression(expr, Numbers::Double)).expect("folds");
+    let single = fold::constant(&fold::fold_expression(expr, Numbers::Mbf)).expect("folds");
+    assert_eq!(double, 1.0 / 3.0);
+    assert_eq!(single, round_mbf(1.0 / 3.0).expect("in range"));
+    assert_ne!(single, double);
+}
+
+#[test]
+fn unreachable_code_is_removed() {
+    let source = "\
+10 PRINT 1
+20 GOTO 50
+30 PRINT 2
+40 PRINT 3
+50 END
+60 PRINT 4
+";
+    assert_eq!(optimize(source, Numbers::Double), "10 PRINT 1\n20 GOTO 50\n50 END\n");
+}
+
+#[test]
+fn dead_stores_are_removed() {
+    let source = "\
+10 A = 5
+20 B = A * 2
+30 X = RND
+40 N = 3
+50 PRINT N
+";
+    // RND moves the sequence on, so its store stays
+    assert_eq!(optimize(source, Numbers::Double), "30 X = RND\n40 N = 3\n50 PRINT N\n");
+}
+
+#[test]
+fn jump_targets_keep_their_lines() {
+    let source = "\
+10 A = 1
+20 REM
+30 INPUT X
+40 IF X > 0 THEN 10
+50 IF X < 0 THEN 20
+";
+    assert_eq!(optimize(source, Numbers::Double), "10\n20 REM\n30 INPUT X\n40 IF X > 0 THEN 10\n50 IF X < 0 THEN 20\n");
+}
+
+#[test]
+fn mbf_keeps_stores_that_may_overflow() {
+    let source = "10 INPUT X\n20 A = X * X\n30 PRINT X\n";
+    assert_eq!(optimize(source, Numbers::Double), "10 INPUT X\n30 PRINT X\n");
+    assert_eq!(optimize(source, Numbers::Mbf), "10 INPUT X\n20 A = X * X\n30 PRINT X\n");
+}
+
+#[test]
+fn no_opt_compiles_everything() {
+    let source = "10 GOTO 30\n20 PRINT 12345\n30 END\n";
+    let c = |no_opt| {
+        let options = Options { target: Target::C, no_opt, ..Options::default() };
+        String::from_utf8(compile(source, &options).expect("program compiles").code).expect("C is UTF-8")
+    };
+    assert!(c(true).contains("12345"));
+    assert!(!c(false).contains("12345"));
+}
@@ -0,0 +1,88 @@
+//! RENUM: which references are rewritten, the renumberings refused, and the
+//! debugger's `renum` command.
+
+use compiler::codegen::CodegenOptions;
+use compiler::renum::{RenumOptions, renumber};
+use compiler::{Options, debugger, parse};
+
+fn renum(source: &str, args: &str) -> Result<String, String> {
+    let options = RenumOptions::parse(args)?;
+    renumber(source, &options).map(|renumbered| renumbered.source)
+}
+
+#[test]
+fn every_reference_is_rewritten() {
+    let source = "\
+1 ON ERROR GOTO 7
+2 IF X THEN 3 ELSE 4
+3 ON X GOSUB 1, 2, 4
+4 RESTORE 5
+5 GOTO 6
+6 DATA 1
+7 IF ERL = 3 OR 4 <> ERL THEN RESUME 2
+";
+    let expected = "\
+10 ON ERROR GOTO 70
+20 IF X THEN 30 ELSE 40
+30 ON X GOSUB 10, 20, 40
+40 RESTORE 50
+50 GOTO 60
+60 DATA 1
+70 IF ERL = 30 OR 40 <> ERL THEN RESUME 20
+";
+    assert_eq!(renum(source, "").unwrap(), expected);
+}
+
+#[test]
+fn numbers_that_name_no_line_are_kept() {
+    let source = "10 X = 20\n20 PRINT 10 + X\n30 IF X = 10 THEN 20\n";
+    assert_eq!(renum(source, "100").unwrap(), "100 X = 20\n110 PRINT 10 + X\n120 IF X = 10 THEN 110\n");
+}
+
+#[test]
+fn arguments_pick_the_lines_and_numbers() {
+    let source = "10 GOTO 30\n20 GOTO 10\n30 GOTO 20\n";
+    assert_eq!(renum(source, "100,20,5").unwrap(), "10 GOTO 105\n100 GOTO 10\n105 GOTO 100\n");
+    assert_eq!(renum(source, ",,1").unwrap(), "10 GOTO 12\n11 GOTO 10\n12 GOTO 11\n");
+    assert!(renum(source, "1,2,3,4").is_err());
+    assert!(renum(source, "70000").is_err());
+    assert!(renum(source, ",,0").is_err());
+}
+
+#[test]
+fn undefined_lines_are_warned_about() {
+    let renumbered = renumber("10 GOTO 99\n20 GOSUB 10\n", &RenumOptions::default()).unwrap();
+    assert_eq!(renumbered.source, "10 GOTO 99\n20 GOSUB 10\n");
+    assert_eq!(renumbered.warnings, ["Undefined line 99 in 10"]);
+}
+
+#[test]
+fn reordering_and_collisions_are_refused() {
+    let source = "10 PRINT 1\n20 PRINT 2\n30 PRINT 3\n";
+    // 20 and 30 would come before 10
+    assert_eq!(renum(source, "1,20").unwrap_err(), "Illegal function call: line 20 would move before line 10");
+    // 30 would become 20, and the program would go beyond 65529
+    assert_eq!(renum(source, "20,30").unwrap_err(), "Illegal function call: lines 20 and 30 would both become 20");
+    assert!(renum(source, "65520").is_err());
+}
+
+#[test]
+fn duplicate_lines_are_refused() {
+    let source = "10 PRINT 1\n10 PRINT 2\n20 GOTO 10\n";
+    assert_eq!(renum(source, "100").unwrap_err(), "Illegal function call: line 10 is defined more than once");
+}
+
+#[test]
+fn debugger_renumbers_and_moves_breakpoints() {
+    let source = "10 PRINT 1\n20 GOSUB 40\n30 END\n40 PRINT 2\n50 RETURN\n";
+    let statements = parse(source, &Options::default()).expect("program parses").statements;
+    let commands = "break 40\nrenum 100,,5\nbreak\ncontinue\nrenum 1,,0\nquit\n";
+    let mut output = Vec::new();
+    debugger::debug(source, &statements, CodegenOptions::default(), commands.as_bytes(), &mut output)
+        .expect("output written");
+    let output = String::from_utf8(output).expect("output is UTF-8");
+    assert!(output.contains("Renumbered; the program starts again\n  100 PRINT 1\n"), "{}", output);
+    assert!(output.contains("Breakpoints at 115\n"), "{}", output);
+    assert!(output.contains("Breakpoint at line 115\n  115 PRINT 2\n"), "{}", output);
+    assert!(output.contains("error: RENUM increment must not be zero\n"), "{}", output);
+}
@@ -0,0 +1,89 @@
+//! The token and AST JSON that `--emit tokens-json` and `--emit ast-json`
+//! write. Tools read these, so their shape is pinned here: a change that
+//! breaks a test needs a new format version.
+
+use compiler::json::JsonValue;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// What `compiler build --emit <emit>` writes for `source`, as JSON.
+fn emit(emit: &str, source: &str) -> JsonValue {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!("basic-json-{}-{}.bas", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed));
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, source).expect("source written");
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .args(["build", "--emit", emit])
+        .arg(&path)
+        .output()
+        .expect("compiler runs");
+    let _ = std::fs::remove_file(&path);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    JsonValue::parse(&String::from_utf8(output.stdout).expect("JSON is UTF-8")).expect("output is JSON")
+}
+
+fn json(text: &str) -> JsonValue {
+    JsonValue::parse(text).expect("expected JSON parses")
+}
+
+#[test]
+fn tokens() {
+    let expected = json(
+        r#"{"format":"basic-tokens","version":1,"tokens":[
+        {"kind":"number","value":10,"span":{"start":0,"end":2,"line":1,"column":1}},
+        {"kind":"keyword","value":"PRINT","span":{"start":3,"end":8,"line":1,"column":4}},
+        {"kind":"float","value":0.5,"text":".5","span":{"start":9,"end":11,"line":1,"column":10}},
+        {"kind":"punctuation","value":";","span":{"start":11,"end":12,"line":1,"column":12}},
+        {"kind":"string","value":"A","span":{"start":13,"end":16,"line":1,"column":14}},
+        {"kind":"punctuation","value":",","span":{"start":16,"end":17,"line":1,"column":17}},
+        {"kind":"newline","value":null,"span":{"start":17,"end":18,"line":1,"column":18}},
+        {"kind":"eof","value":null,"span":{"start":18,"end":18,"line":2,"column":1}}]}"#,
+    );
+    assert_eq!(emit("tokens-json", "10 PRINT .5; \"A\",\n"), expected);
+}
+
+#[test]
+fn expressions_and_nested_statements() {
+    let expected = json(
+        r#"{"format":"basic-ast","version":1,"statements":[
+        {"label":10,"span":{"start":0,"end":13,"line":1,"column":1},"kind":"let","var":"X",
+         "value":{"kind":"binary","op":"add","left":{"kind":"float","value":0.5,"text":".5"},
+                  "right":{"kind":"number","value":2}}},
+        {"label":20,"span":{"start":14,"end":38,"line":2,"column":1},"kind":"if",
+         "left":{"kind":"variable","name":"X"},"op":">","right":{"kind":"number","value":1},
+         "then":{"label":null,"span":{"start":31,"end":38,"line":2,"column":18},"kind":"goto","target":10}}]}"#,
+    );
+    assert_eq!(emit("ast-json", "10 X = .5 + 2\n20 IF X > 1 THEN GOTO 10\n"), expected);
+}
+
+#[test]
+fn loops_and_optional_members() {
+    // A FOR holds its body and NEXT's line; RANDOMIZE without a seed has none
+    let expected = json(
+        r#"{"format":"basic-ast","version":1,"statements":[
+        {"label":10,"span":{"start":0,"end":34,"line":1,"column":1},"kind":"for","var":"I",
+         "start":{"kind":"number","value":1},"end":{"kind":"number","value":2},
+         "step":{"kind":"number","value":1},"body":[],"next_label":20},
+        {"label":30,"span":{"start":35,"end":47,"line":3,"column":1},"kind":"randomize"},
+        {"label":40,"span":{"start":48,"end":57,"line":4,"column":1},"kind":"rem","text":"hi"}]}"#,
+    );
+    assert_eq!(emit("ast-json", "10 FOR I = 1 TO 2 STEP 1\n20 NEXT I\n30 RANDOMIZE\n40 REM hi\n"), expected);
+}
+
+#[test]
+fn every_statement_has_a_kind() {
+    let source = "\
+10 INPUT A
+20 PRINT SQR(A) * 2
+30 GOSUB 60
+40 TRON
+50 END
+60 TROFF
+70 RETURN
+";
+    let ast = emit("ast-json", source);
+    let statements = ast.get("statements").as_array().expect("statements");
+    let kinds: Vec<_> = statements.iter().map(|stmt| stmt.get("kind").as_str()).collect();
+    let expected = ["input", "print", "gosub", "tron", "end", "troff", "return"];
+    assert_eq!(kinds, expected.map(Some));
+}
@@ -0,0 +1,67 @@
+//! The Rust and JavaScript generated for a few programs, compared with the
+//! files in `tests/snapshots`. The runtime each starts with is left out.
+//! Run with `UPDATE_SNAPSHOTS=1` to write the files again after a change
+//! meant to alter the output.
+
+use compiler::{Options, Target, compile, jsgen, rustgen};
+use std::fs;
+use std::path::Path;
+
+/// Native loops: FOR, an IF with a statement, and a loop back to a line.
+const STRUCTURED: &str = "\
+10 REM Sum the odd numbers
+20 S = 0
+30 FOR I = 1 TO 9 STEP 2
+40 S = S + I
+50 NEXT I
+60 IF S > 20 THEN PRINT \"BIG\"; S
+70 N = 1
+80 N = N * 2
+90 IF N < 100 THEN 80
+100 PRINT N; INT(N / 3)
+";
+
+/// GOSUB and input, which need the state machine.
+const DISPATCH: &str = "\
+10 INPUT X
+20 GOSUB 100
+30 IF X > 0 THEN 10
+40 END
+100 PRINT \"SQUARE\"; X * X; RND
+110 RETURN
+";
+
+fn check(name: &str, target: Target, runtime: &str, source: &str) {
+    let options = Options { target, ..Options::default() };
+    let output = compile(source, &options).expect("program compiles");
+    let code = String::from_utf8(output.code).expect("source is UTF-8");
+    let program = code.strip_prefix(runtime).expect("output starts with the runtime");
+
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, program).expect("snapshot written");
+        return;
+    }
+    let expected = fs::read_to_string(&path).expect("snapshot exists");
+    assert_eq!(program, expected, "{} differs from its snapshot", name);
+}
+
+#[test]
+fn js_structured() {
+    check("structured.js", Target::Js, jsgen::RUNTIME, STRUCTURED);
+}
+
+#[test]
+fn js_dispatch() {
+    check("dispatch.js", Target::Js, jsgen::RUNTIME, DISPATCH);
+}
+
+#[test]
+fn rust_structured() {
+    check("structured.rs", Target::Rust, rustgen::RUNTIME, STRUCTURED);
+}
+
+#[test]
+fn rust_dispatch() {
+    check("dispatch.rs", Target::Rust, rustgen::RUNTIME, DISPATCH);
+}
@@ -0,0 +1,35 @@
+
+const GOSUB_STACK_SIZE = 256;
+
+async function program(rt) {
+    let X = 0.0;
+
+    let pc = 2;
+    for (;;) {
+        switch (pc) {
+            case 2: { // 10-20
+                X = await rt.input();  // 10
+                rt.gosub(3, 20); pc = 5;  // 20
+                break;
+            }
+            case 3: { // 30
+                if (X > 0.0) { pc = 2; } else { pc = 4; }  // 30
+                break;
+            }
+            case 4: { // 40
+                pc = 1;  // 40
+                break;
+            }
+            case 5: { // 100-110
+                rt.printString("SQUARE");  // 100
+                rt.printNumber((X * X));
+                rt.printNumber(rt.rnd(1.0));
+                rt.printNewline();
+                pc = rt.gosubReturn(110);  // 110
+                break;
+            }
+            default:
+                return;
+        }
+    }
+}
@@ -0,0 +1,31 @@
+
+const GOSUB_STACK_SIZE: usize = 256;
+
+fn program<I: Io>(mut rt: Runtime<I>) -> Result<(), Stop> {
+    let mut X: f64 = 0.0;
+
+    let mut pc = 2;
+    loop {
+        match pc {
+            2 => { // 10-20
+                X = rt.input()?;  // 10
+                rt.gosub(3, Some(20))?; pc = 5;  // 20
+            }
+            3 => { // 30
+                if X > 0.0 { pc = 2; } else { pc = 4; }  // 30
+            }
+            4 => { // 40
+                pc = 1;  // 40
+            }
+            5 => { // 100-110
+                rt.print_string("SQUARE");  // 100
+                rt.print_number((X * X));
+                let value = rt.rnd(1.0);
+                rt.print_number(value);
+                rt.print_newline();
+                pc = rt.gosub_return(Some(110))?;  // 110
+            }
+            _ => return Ok(()),
+        }
+    }
+}
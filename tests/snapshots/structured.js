@@ -0,0 +1,31 @@
+
+const GOSUB_STACK_SIZE = 256;
+
+async function program(rt) {
+    let I = 0.0;
+    let N = 0.0;
+    let S = 0.0;
+
+    // Sum the odd numbers
+    S = 0.0;  // 20
+    I = 1.0;  // 30
+    while (I <= 9.0) {
+        S = (S + I);  // 40
+        I += 2.0;
+    }
+    if (S > 20.0) {  // 60
+        rt.printString("BIG");
+        rt.printNumber(S);
+        rt.printNewline();
+    }
+    N = 1.0;  // 70
+    for (;;) {
+        N = (N * 2.0);  // 80
+        if (!(N < 100.0)) {  // 90
+            break;
+        }
+    }
+    rt.printNumber(N);  // 100
+    rt.printNumber(Math.floor((N / 3.0)));
+    rt.printNewline();
+}
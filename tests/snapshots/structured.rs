@@ -0,0 +1,32 @@
+
+const GOSUB_STACK_SIZE: usize = 256;
+
+fn program<I: Io>(mut rt: Runtime<I>) -> Result<(), Stop> {
+    let mut I: f64 = 0.0;
+    let mut N: f64 = 0.0;
+    let mut S: f64 = 0.0;
+
+    // Sum the odd numbers
+    S = 0.0;  // 20
+    I = 1.0;  // 30
+    while I <= 9.0 {
+        S = (S + I);  // 40
+        I += 2.0;
+    }
+    if S > 20.0 {  // 60
+        rt.print_string("BIG");
+        rt.print_number(S);
+        rt.print_newline();
+    }
+    N = 1.0;  // 70
+    loop {
+        N = (N * 2.0);  // 80
+        if !(N < 100.0) {  // 90
+            break;
+        }
+    }
+    rt.print_number(N);  // 100
+    rt.print_number(f64::floor((N / 3.0)));
+    rt.print_newline();
+    Ok(())
+}
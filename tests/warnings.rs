@@ -0,0 +1,60 @@
+//! `-W` flags and the warning codes they name.
+
+use compiler::diagnostic::WARNING_CODES;
+use compiler::{Options, WarningConfig, parse};
+
+/// A program with as many kinds of warning as fit in one.
+const WARNINGS: &str = "\
+10 FOR I = 1 TO 3
+20 I = 2
+30 NEXT I
+40 PRINT Y / 0
+50 GOSUB 100
+60 END
+70 PRINT 1
+100 X = 1
+90 PRINT 2
+";
+
+fn warning_codes(options: &Options) -> Vec<&'static str> {
+    let diagnostics = match parse(WARNINGS, options) {
+        Ok(program) => program.warnings,
+        Err(diagnostics) => diagnostics,
+    };
+    diagnostics.iter().filter_map(|d| d.code).collect()
+}
+
+#[test]
+fn every_warning_has_a_known_code() {
+    let codes = warning_codes(&Options::default());
+    assert!(codes.len() >= 5, "{:?}", codes);
+    for code in codes {
+        assert!(WARNING_CODES.contains(&code), "{} is missing from WARNING_CODES", code);
+    }
+}
+
+#[test]
+fn flags_turn_warnings_off_and_on() {
+    let mut warnings = WarningConfig::default();
+    for code in WARNING_CODES {
+        warnings.set(&format!("no-{}", code)).expect("a known code");
+    }
+    let options = Options { warnings: warnings.clone(), ..Options::default() };
+    assert!(warning_codes(&options).is_empty());
+
+    warnings.set("unreachable-code").expect("a known code");
+    assert!(warnings.is_enabled("unreachable-code"));
+    assert!(!warnings.is_enabled("unused-variable"));
+    let options = Options { warnings, ..Options::default() };
+    assert_eq!(warning_codes(&options), ["unreachable-code"]);
+}
+
+#[test]
+fn unknown_codes_are_rejected() {
+    let mut warnings = WarningConfig::default();
+    assert_eq!(warnings.set("unused-varible"), Err("unknown warning 'unused-varible'".to_string()));
+    assert_eq!(warnings.set("no-such-thing"), Err("unknown warning 'such-thing'".to_string()));
+    warnings.set("error").expect("error is a flag");
+    warnings.set("no-error").expect("no-error is a flag");
+    assert!(!warnings.warnings_as_errors);
+}
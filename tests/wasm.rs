@@ -0,0 +1,690 @@
+//! The WebAssembly a few programs compile to, run by a small interpreter of
+//! the instructions `wasm.rs` emits, with the `basic` imports implemented
+//! here. Each program must print what the interpreter in `interp.rs` prints
+//! for it, so the module is checked against the same behaviour as C without
+//! needing a WebAssembly engine.
+
+use compiler::interp::{Interpreter, Io, RuntimeError};
+use compiler::{CodegenOptions, Numbers, Options, Target, compile, parse};
+use std::collections::{HashMap, VecDeque};
+
+/// Instructions, as in `wasm.rs`.
+const BLOCK: u8 = 0x02;
+const LOOP: u8 = 0x03;
+const IF: u8 = 0x04;
+const ELSE: u8 = 0x05;
+const END: u8 = 0x0b;
+const BR: u8 = 0x0c;
+const BR_TABLE: u8 = 0x0e;
+const RETURN: u8 = 0x0f;
+const CALL: u8 = 0x10;
+const LOCAL_GET: u8 = 0x20;
+const LOCAL_SET: u8 = 0x21;
+const GLOBAL_GET: u8 = 0x23;
+const GLOBAL_SET: u8 = 0x24;
+const I32_LOAD: u8 = 0x28;
+const I32_STORE: u8 = 0x36;
+const I32_CONST: u8 = 0x41;
+const I64_CONST: u8 = 0x42;
+const F64_CONST: u8 = 0x44;
+const I32_EQZ: u8 = 0x45;
+const I32_GE_U: u8 = 0x4f;
+const F64_EQ: u8 = 0x61;
+const F64_NE: u8 = 0x62;
+const F64_LT: u8 = 0x63;
+const F64_GT: u8 = 0x64;
+const F64_LE: u8 = 0x65;
+const F64_GE: u8 = 0x66;
+const I32_ADD: u8 = 0x6a;
+const I32_SUB: u8 = 0x6b;
+const I32_MUL: u8 = 0x6c;
+const I32_AND: u8 = 0x71;
+const I32_OR: u8 = 0x72;
+const I32_SHL: u8 = 0x74;
+const I32_SHR_U: u8 = 0x76;
+const I64_REM_S: u8 = 0x81;
+const F64_ABS: u8 = 0x99;
+const F64_FLOOR: u8 = 0x9c;
+const F64_SQRT: u8 = 0x9f;
+const F64_ADD: u8 = 0xa0;
+const F64_SUB: u8 = 0xa1;
+const F64_MUL: u8 = 0xa2;
+const F64_DIV: u8 = 0xa3;
+const I32_WRAP_I64: u8 = 0xa7;
+const F32_DEMOTE_F64: u8 = 0xb6;
+const F64_CONVERT_I32_U: u8 = 0xb8;
+const I32_REINTERPRET_F32: u8 = 0xbc;
+/// The prefix of `i64.trunc_sat_f64_s`, which is 0x06 after it.
+const PREFIX: u8 = 0xfc;
+const I64_TRUNC_SAT_F64_S: u64 = 0x06;
+
+const I32: u8 = 0x7f;
+const F64: u8 = 0x7c;
+const PAGE: usize = 65536;
+
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl Value {
+    fn zero(value_type: u8) -> Self {
+        match value_type {
+            I32 => Value::I32(0),
+            F64 => Value::F64(0.0),
+            other => panic!("unexpected value type {:#04x}", other),
+        }
+    }
+
+    fn i32(self) -> i32 {
+        match self {
+            Value::I32(x) => x,
+            other => panic!("expected an i32, found {:?}", other),
+        }
+    }
+
+    fn i64(self) -> i64 {
+        match self {
+            Value::I64(x) => x,
+            other => panic!("expected an i64, found {:?}", other),
+        }
+    }
+
+    fn f32(self) -> f32 {
+        match self {
+            Value::F32(x) => x,
+            other => panic!("expected an f32, found {:?}", other),
+        }
+    }
+
+    fn f64(self) -> f64 {
+        match self {
+            Value::F64(x) => x,
+            other => panic!("expected an f64, found {:?}", other),
+        }
+    }
+}
+
+/// The bytes of a module, read from the front.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn byte(&mut self) -> u8 {
+        let byte = self.bytes[self.pos];
+        self.pos += 1;
+        byte
+    }
+
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let bytes = &self.bytes[self.pos..self.pos + len];
+        self.pos += len;
+        bytes
+    }
+
+    fn uleb(&mut self) -> u64 {
+        let (mut value, mut shift) = (0, 0);
+        loop {
+            let byte = self.byte();
+            value |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> i64 {
+        let (mut value, mut shift) = (0i64, 0);
+        loop {
+            let byte = self.byte();
+            value |= ((byte & 0x7f) as i64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    value |= -1 << shift;
+                }
+                return value;
+            }
+        }
+    }
+
+    fn index(&mut self) -> usize {
+        self.uleb() as usize
+    }
+
+    fn name(&mut self) -> String {
+        let len = self.index();
+        String::from_utf8(self.take(len).to_vec()).expect("names are UTF-8")
+    }
+
+    fn value_types(&mut self) -> Vec<u8> {
+        let len = self.index();
+        self.take(len).to_vec()
+    }
+
+    /// A constant expression: one `i32.const` and `end`.
+    fn constant(&mut self) -> i32 {
+        assert_eq!(self.byte(), I32_CONST, "constant expressions are an i32.const");
+        let value = self.sleb() as i32;
+        assert_eq!(self.byte(), END);
+        value
+    }
+}
+
+struct Function {
+    type_index: usize,
+    locals: Vec<u8>,
+    code: Vec<u8>,
+    /// For each BLOCK, LOOP and IF, by where it starts: where its ELSE is,
+    /// if it has one, and where its END is.
+    blocks: HashMap<usize, (Option<usize>, usize)>,
+}
+
+/// Where every block in `code` ends, found by skipping the immediates of
+/// each instruction.
+fn match_blocks(code: &[u8]) -> HashMap<usize, (Option<usize>, usize)> {
+    let mut blocks = HashMap::new();
+    let mut open: Vec<(usize, Option<usize>)> = Vec::new();
+    let mut reader = Reader::new(code);
+    while !reader.done() {
+        let at = reader.pos;
+        match reader.byte() {
+            BLOCK | LOOP | IF => {
+                reader.byte();
+                open.push((at, None));
+            }
+            ELSE => open.last_mut().expect("ELSE inside an IF").1 = Some(at),
+            END => {
+                if let Some((start, otherwise)) = open.pop() {
+                    blocks.insert(start, (otherwise, at));
+                }
+            }
+            BR | CALL | LOCAL_GET | LOCAL_SET | GLOBAL_GET | GLOBAL_SET | PREFIX => {
+                reader.uleb();
+            }
+            BR_TABLE => {
+                let len = reader.uleb();
+                for _ in 0..=len {
+                    reader.uleb();
+                }
+            }
+            I32_LOAD | I32_STORE => {
+                reader.uleb();
+                reader.uleb();
+            }
+            I32_CONST | I64_CONST => {
+                reader.sleb();
+            }
+            F64_CONST => {
+                reader.take(8);
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// A decoded module, before it runs.
+#[derive(Default)]
+struct Module {
+    /// The number of parameters and results of each function type.
+    types: Vec<(usize, usize)>,
+    /// The name and type of each import from `basic`.
+    imports: Vec<(String, usize)>,
+    functions: Vec<Function>,
+    globals: Vec<Value>,
+    memory: Vec<u8>,
+    exports: HashMap<String, usize>,
+}
+
+impl Module {
+    fn decode(bytes: &[u8]) -> Self {
+        let mut reader = Reader::new(bytes);
+        assert_eq!(reader.take(8), b"\0asm\x01\0\0\0", "a version 1 module");
+        let mut module = Module::default();
+        let mut function_types = Vec::new();
+        while !reader.done() {
+            let id = reader.byte();
+            let len = reader.index();
+            let mut section = Reader::new(reader.take(len));
+            // Custom sections have no count
+            if id == 0 {
+                continue;
+            }
+            for _ in 0..section.uleb() {
+                match id {
+                    1 => {
+                        assert_eq!(section.byte(), 0x60, "a function type");
+                        let params = section.value_types().len();
+                        let results = section.value_types().len();
+                        module.types.push((params, results));
+                    }
+                    2 => {
+                        assert_eq!(section.name(), "basic");
+                        let field = section.name();
+                        assert_eq!(section.byte(), 0x00, "only functions are imported");
+                        module.imports.push((field, section.index()));
+                    }
+                    3 => function_types.push(section.index()),
+                    5 => {
+                        assert_eq!(section.byte(), 0x00, "memory has no maximum");
+                        module.memory = vec![0; section.index() * PAGE];
+                    }
+                    6 => {
+                        assert_eq!(section.byte(), I32);
+                        section.byte();
+                        module.globals.push(Value::I32(section.constant()));
+                    }
+                    7 => {
+                        let name = section.name();
+                        section.byte();
+                        module.exports.insert(name, section.index());
+                    }
+                    10 => {
+                        let len = section.index();
+                        let mut body = Reader::new(section.take(len));
+                        let mut locals = Vec::new();
+                        for _ in 0..body.uleb() {
+                            let count = body.index();
+                            locals.extend(std::iter::repeat_n(body.byte(), count));
+                        }
+                        let code = body.bytes[body.pos..].to_vec();
+                        let type_index = function_types[module.functions.len()];
+                        let blocks = match_blocks(&code);
+                        module.functions.push(Function { type_index, locals, code, blocks });
+                    }
+                    11 => {
+                        assert_eq!(section.byte(), 0x00, "an active segment in memory 0");
+                        let offset = section.constant() as usize;
+                        let len = section.index();
+                        module.memory[offset..offset + len].copy_from_slice(section.take(len));
+                    }
+                    other => panic!("unexpected section {}", other),
+                }
+            }
+            assert!(section.done(), "section {} has bytes left over", id);
+        }
+        module
+    }
+}
+
+/// Input ran out, which ends the program.
+struct OutOfInput;
+
+/// A branch target: the code after a block's END, or the start of a loop.
+#[derive(Clone, Copy)]
+struct Label {
+    target: usize,
+    height: usize,
+    is_loop: bool,
+}
+
+/// A running module and the host it imports from.
+struct Machine<'a> {
+    module: &'a Module,
+    memory: Vec<u8>,
+    globals: Vec<Value>,
+    output: String,
+    input: VecDeque<String>,
+    error: Option<String>,
+}
+
+impl<'a> Machine<'a> {
+    fn new(module: &'a Module, input: &str) -> Self {
+        Machine {
+            module,
+            memory: module.memory.clone(),
+            globals: module.globals.clone(),
+            output: String::new(),
+            input: input.lines().map(str::to_string).collect(),
+            error: None,
+        }
+    }
+
+    fn string(&self, ptr: i32, len: i32) -> &str {
+        let bytes = &self.memory[ptr as usize..(ptr + len) as usize];
+        std::str::from_utf8(bytes).expect("strings are UTF-8")
+    }
+
+    fn host(&mut self, name: &str, args: &[Value]) -> Result<Option<Value>, OutOfInput> {
+        let result = match name {
+            "print_string" => {
+                let text = self.string(args[0].i32(), args[1].i32()).to_string();
+                self.output.push_str(&text);
+                None
+            }
+            "print_number" => {
+                self.output.push_str(&Numbers::Double.format(args[0].f64()));
+                None
+            }
+            "print_newline" => {
+                self.output.push('\n');
+                None
+            }
+            "input" => {
+                let prompt = self.string(args[0].i32(), args[1].i32()).to_string();
+                loop {
+                    self.output.push_str(&prompt);
+                    let Some(line) = self.input.pop_front() else {
+                        self.output.push('\n');
+                        return Err(OutOfInput);
+                    };
+                    match line.trim().parse() {
+                        Ok(x) => break Some(Value::F64(x)),
+                        Err(_) => self.output.push_str("?Redo from start\n"),
+                    }
+                }
+            }
+            "error" => {
+                let message = self.string(args[0].i32(), args[1].i32());
+                let line = args[2].i32();
+                self.error = Some(if line < 0 { message.to_string() } else { format!("{} in {}", message, line) });
+                None
+            }
+            "exp" => Some(Value::F64(args[0].f64().exp())),
+            "pow" => Some(Value::F64(args[0].f64().powf(args[1].f64()))),
+            "timer" => Some(Value::F64(0.0)),
+            other => panic!("unknown import {}", other),
+        };
+        Ok(result)
+    }
+
+    fn load(&self, address: usize) -> i32 {
+        i32::from_le_bytes(self.memory[address..address + 4].try_into().expect("four bytes"))
+    }
+
+    fn call(&mut self, index: usize, args: Vec<Value>) -> Result<Option<Value>, OutOfInput> {
+        let module = self.module;
+        if let Some((name, _)) = module.imports.get(index) {
+            return self.host(name, &args);
+        }
+        let function = &module.functions[index - module.imports.len()];
+        let results = module.types[function.type_index].1;
+        let mut locals = args;
+        locals.extend(function.locals.iter().map(|value_type| Value::zero(*value_type)));
+        let mut stack: Vec<Value> = Vec::new();
+        let mut labels: Vec<Label> = Vec::new();
+        let mut code = Reader::new(&function.code);
+        macro_rules! pop {
+            ($kind:ident) => {
+                stack.pop().expect("a value on the stack").$kind()
+            };
+        }
+        macro_rules! unary {
+            ($kind:ident, $wrap:expr) => {{
+                let x = pop!($kind);
+                stack.push($wrap(x));
+            }};
+        }
+        macro_rules! binary {
+            ($kind:ident, $wrap:expr) => {{
+                let b = pop!($kind);
+                let a = pop!($kind);
+                stack.push($wrap(a, b));
+            }};
+        }
+        loop {
+            let at = code.pos;
+            let mut depth = None;
+            match code.byte() {
+                op @ (BLOCK | LOOP) => {
+                    code.byte();
+                    let target = if op == LOOP { code.pos } else { function.blocks[&at].1 + 1 };
+                    labels.push(Label { target, height: stack.len(), is_loop: op == LOOP });
+                }
+                IF => {
+                    code.byte();
+                    let (otherwise, end) = function.blocks[&at];
+                    let label = Label { target: end + 1, height: stack.len() - 1, is_loop: false };
+                    if pop!(i32) != 0 {
+                        labels.push(label);
+                    } else if let Some(otherwise) = otherwise {
+                        code.pos = otherwise + 1;
+                        labels.push(label);
+                    } else {
+                        code.pos = end + 1;
+                    }
+                }
+                ELSE => code.pos = labels.pop().expect("ELSE inside an IF").target,
+                END => {
+                    if labels.pop().is_none() {
+                        return Ok(stack.pop().filter(|_| results == 1));
+                    }
+                }
+                BR => depth = Some(code.index()),
+                BR_TABLE => {
+                    let targets: Vec<usize> = (0..code.uleb()).map(|_| code.index()).collect();
+                    let default = code.index();
+                    let chosen = pop!(i32) as u32 as usize;
+                    depth = Some(targets.get(chosen).copied().unwrap_or(default));
+                }
+                RETURN => return Ok(stack.pop().filter(|_| results == 1)),
+                CALL => {
+                    let callee = code.index();
+                    let type_index = match module.imports.get(callee) {
+                        Some((_, type_index)) => *type_index,
+                        None => module.functions[callee - module.imports.len()].type_index,
+                    };
+                    let args = stack.split_off(stack.len() - module.types[type_index].0);
+                    stack.extend(self.call(callee, args)?);
+                }
+                LOCAL_GET => stack.push(locals[code.index()]),
+                LOCAL_SET => locals[code.index()] = stack.pop().expect("a value on the stack"),
+                GLOBAL_GET => stack.push(self.globals[code.index()]),
+                GLOBAL_SET => self.globals[code.index()] = stack.pop().expect("a value on the stack"),
+                I32_LOAD => {
+                    code.uleb();
+                    let offset = code.index();
+                    let address = pop!(i32) as u32 as usize + offset;
+                    stack.push(Value::I32(self.load(address)));
+                }
+                I32_STORE => {
+                    code.uleb();
+                    let offset = code.index();
+                    let value = pop!(i32);
+                    let address = pop!(i32) as u32 as usize + offset;
+                    self.memory[address..address + 4].copy_from_slice(&value.to_le_bytes());
+                }
+                I32_CONST => stack.push(Value::I32(code.sleb() as i32)),
+                I64_CONST => stack.push(Value::I64(code.sleb())),
+                F64_CONST => stack.push(Value::F64(f64::from_le_bytes(code.take(8).try_into().expect("eight bytes")))),
+                I32_EQZ => unary!(i32, |x| Value::I32((x == 0) as i32)),
+                I32_GE_U => binary!(i32, |a: i32, b: i32| Value::I32((a as u32 >= b as u32) as i32)),
+                F64_EQ => binary!(f64, |a, b| Value::I32((a == b) as i32)),
+                F64_NE => binary!(f64, |a, b| Value::I32((a != b) as i32)),
+                F64_LT => binary!(f64, |a, b| Value::I32((a < b) as i32)),
+                F64_GT => binary!(f64, |a, b| Value::I32((a > b) as i32)),
+                F64_LE => binary!(f64, |a, b| Value::I32((a <= b) as i32)),
+                F64_GE => binary!(f64, |a, b| Value::I32((a >= b) as i32)),
+                I32_ADD => binary!(i32, |a: i32, b| Value::I32(a.wrapping_add(b))),
+                I32_SUB => binary!(i32, |a: i32, b| Value::I32(a.wrapping_sub(b))),
+                I32_MUL => binary!(i32, |a: i32, b| Value::I32(a.wrapping_mul(b))),
+                I32_AND => binary!(i32, |a, b| Value::I32(a & b)),
+                I32_OR => binary!(i32, |a, b| Value::I32(a | b)),
+                I32_SHL => binary!(i32, |a: i32, b| Value::I32(a.wrapping_shl(b as u32))),
+                I32_SHR_U => binary!(i32, |a: i32, b| Value::I32((a as u32).wrapping_shr(b as u32) as i32)),
+                I64_REM_S => binary!(i64, |a: i64, b| Value::I64(a.wrapping_rem(b))),
+                F64_ABS => unary!(f64, |x: f64| Value::F64(x.abs())),
+                F64_FLOOR => unary!(f64, |x: f64| Value::F64(x.floor())),
+                F64_SQRT => unary!(f64, |x: f64| Value::F64(x.sqrt())),
+                F64_ADD => binary!(f64, |a, b| Value::F64(a + b)),
+                F64_SUB => binary!(f64, |a, b| Value::F64(a - b)),
+                F64_MUL => binary!(f64, |a, b| Value::F64(a * b)),
+                F64_DIV => binary!(f64, |a, b| Value::F64(a / b)),
+                I32_WRAP_I64 => unary!(i64, |x| Value::I32(x as i32)),
+                F32_DEMOTE_F64 => unary!(f64, |x| Value::F32(x as f32)),
+                F64_CONVERT_I32_U => unary!(i32, |x| Value::F64(x as u32 as f64)),
+                I32_REINTERPRET_F32 => unary!(f32, |x: f32| Value::I32(x.to_bits() as i32)),
+                PREFIX => {
+                    assert_eq!(code.uleb(), I64_TRUNC_SAT_F64_S);
+                    // Rust's casts saturate, and NaN becomes 0, as here
+                    unary!(f64, |x| Value::I64(x as i64));
+                }
+                other => panic!("unexpected instruction {:#04x} at {}", other, at),
+            }
+            if let Some(depth) = depth {
+                if depth == labels.len() {
+                    return Ok(stack.pop().filter(|_| results == 1));
+                }
+                let label = labels[labels.len() - 1 - depth];
+                stack.truncate(label.height);
+                labels.truncate(labels.len() - depth - !label.is_loop as usize);
+                code.pos = label.target;
+            }
+        }
+    }
+}
+
+/// What a program printed, and the error that stopped it, if any.
+type Run = (String, Option<String>);
+
+fn run_wasm(source: &str, input: &str) -> Run {
+    let options = Options { target: Target::Wasm, ..Options::default() };
+    let output = compile(source, &options).expect("program compiles");
+    let module = Module::decode(&output.code);
+    let mut machine = Machine::new(&module, input);
+    // Running out of input ends the program like END
+    let _ = machine.call(module.exports["run"], Vec::new());
+    (machine.output, machine.error)
+}
+
+struct Console {
+    output: String,
+    input: VecDeque<String>,
+}
+
+impl Io for Console {
+    fn print(&mut self, text: &str) {
+        self.output.push_str(text);
+    }
+
+    fn input(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+
+    fn warn(&mut self, error: &RuntimeError) {
+        panic!("unexpected warning: {}", error);
+    }
+}
+
+fn interpret(source: &str, input: &str) -> Run {
+    let program = parse(source, &Options::default()).expect("program parses");
+    let mut interpreter = Interpreter::new(&program.statements, CodegenOptions::default());
+    let mut console = Console { output: String::new(), input: input.lines().map(str::to_string).collect() };
+    while !interpreter.finished() {
+        if let Err(error) = interpreter.step(&mut console) {
+            return (console.output, Some(error.to_string()));
+        }
+    }
+    (console.output, None)
+}
+
+/// Run `source` both ways and return what it did.
+fn check(source: &str, input: &str) -> Run {
+    let run = run_wasm(source, input);
+    assert_eq!(run, interpret(source, input), "the module and the interpreter differ");
+    run
+}
+
+#[test]
+fn loops_and_arithmetic() {
+    let (output, error) = check(
+        "\
+10 FOR I = 1 TO 3
+20 FOR J = 2 TO 10 STEP 4
+30 PRINT I * J; I / 4; I ^ 2; 0 - J;
+40 NEXT J
+50 PRINT
+60 NEXT I
+70 PRINT \"ROOTS\"; SQR(2); INT(0 - 2.5); ABS(0 - 7); EXP(1); 10000000000 / 3; 1 / 3000000
+80 N = 0
+90 N = N + 1
+100 IF N * N < 50 THEN 90
+110 PRINT N
+",
+        "",
+    );
+    assert_eq!(error, None);
+    assert!(output.starts_with("2 0.25 1 -2 6 0.25 1 -6 10 0.25 1 -10 \n4 0.5 4 -2 "), "{}", output);
+    assert!(output.ends_with("ROOTS1.41421 -3 7 2.71828 3.33333e+09 3.33333e-07 \n8 \n"), "{}", output);
+}
+
+#[test]
+fn gosub_and_input() {
+    let (output, error) = check(
+        "\
+10 INPUT X
+20 IF X = 0 THEN 60
+30 GOSUB 100
+40 PRINT \"TWICE\"; Y
+50 GOTO 10
+60 END
+100 Y = X * 2
+110 GOSUB 200
+120 RETURN
+200 Y = Y + 1
+210 RETURN
+",
+        "3\nabc\n-1.5\n0\n",
+    );
+    assert_eq!(error, None);
+    assert_eq!(output, "? TWICE7 \n? ?Redo from start\n? TWICE-2 \n? ");
+}
+
+#[test]
+fn input_running_out() {
+    let (output, error) = check("10 INPUT X\n20 PRINT X\n30 GOTO 10\n", "1\n2\n");
+    assert_eq!(error, None);
+    assert_eq!(output, "? 1 \n? 2 \n? \n");
+}
+
+#[test]
+fn rnd_sequence() {
+    let (output, error) = check(
+        "\
+10 FOR I = 1 TO 4
+20 PRINT RND;
+30 NEXT I
+40 PRINT RND(0); INT(RND * 6) + 1
+50 RANDOMIZE 42
+60 PRINT RND; RND(0 - 3); RND
+",
+        "",
+    );
+    assert_eq!(error, None);
+    // GW-BASIC's sequence, as the C runtime has it
+    assert!(output.starts_with("0.12135 0.651861 0.868861 0.729762 0.729762 "), "{}", output);
+}
+
+#[test]
+fn undefined_line() {
+    let (_, error) = check("10 PRINT 1\n20 GOTO 99\n", "");
+    assert_eq!(error.as_deref(), Some("Undefined line number in 20"));
+}
+
+#[test]
+fn return_without_gosub() {
+    let (output, error) = check("10 PRINT 1\n20 RETURN\n", "");
+    assert_eq!(output, "1 \n");
+    assert_eq!(error.as_deref(), Some("RETURN without GOSUB in 20"));
+}
+
+#[test]
+fn gosub_stack_overflow() {
+    let (_, error) = check("10 N = N + 1\n20 GOSUB 10\n", "");
+    assert_eq!(error.as_deref(), Some("Out of memory in 20"));
+}